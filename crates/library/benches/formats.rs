@@ -0,0 +1,132 @@
+use std::{hint::black_box, io::Cursor};
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use senobi_library::{
+  byml::{
+    reader::{BymlReader, BymlReaderArray, BymlReaderDict, BymlReaderNode},
+    writer::{BymlWriter, BymlWriterArray, BymlWriterDict, Version},
+  },
+  yaz0::{self, MAX_WINDOW},
+};
+use zerocopy::{ByteOrder, LittleEndian};
+
+const HOME_BED_SZS: &[u8] = include_bytes!("../examples/HomeBed.szs");
+
+fn yaz0(c: &mut Criterion) {
+  let decompressed = yaz0::decompress(&mut Cursor::new(HOME_BED_SZS)).unwrap();
+
+  let mut group = c.benchmark_group("yaz0");
+  group.throughput(Throughput::Bytes(decompressed.len() as u64));
+  group.bench_function("decompress HomeBed.szs", |b| {
+    b.iter(|| yaz0::decompress(&mut Cursor::new(black_box(HOME_BED_SZS))).unwrap())
+  });
+  group.sample_size(10);
+  group.bench_function("compress HomeBed.sarc", |b| {
+    b.iter(|| {
+      let mut compressed = Vec::new();
+      yaz0::compress(&mut black_box(&*decompressed), &mut compressed, MAX_WINDOW).unwrap();
+      compressed
+    })
+  });
+  group.finish();
+}
+
+/// A document shaped like a large stage file: a list of objects, each with a handful of
+/// properties and a nested transform.
+fn large_document() -> BymlWriter {
+  BymlWriter::from_dictionary(large_tree())
+}
+
+/// The tree behind [`large_document`], before it's handed to a writer.
+fn large_tree() -> BymlWriterDict {
+  let mut objects = BymlWriterArray::new();
+  for index in 0..10_000 {
+    let mut translate = BymlWriterDict::new();
+    translate.insert_f32("X", index as f32);
+    translate.insert_f32("Y", 0.0);
+    translate.insert_f32("Z", -(index as f32));
+
+    let mut object = BymlWriterDict::new();
+    object.insert_string("Id", format!("obj{index}"));
+    object.insert_string("UnitConfigName", ["Coin", "Goomba", "Bed"][index % 3]);
+    object.insert_i32("Layer", (index % 8) as i32);
+    object.insert_bool("IsLinkDest", index % 2 == 0);
+    object.insert_u64("Hash", (index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    object.insert_dict("Translate", translate);
+    objects.push_dict(object);
+  }
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("Objs", objects);
+  root
+}
+
+fn walk_array<O: ByteOrder>(array: &BymlReaderArray<'_, O>) -> usize {
+  array.values().map(|node| walk_node(&node.unwrap())).sum()
+}
+
+fn walk_dict<O: ByteOrder>(dict: &BymlReaderDict<'_, O>) -> usize {
+  dict
+    .entries()
+    .map(|entry| walk_node(&entry.unwrap().1))
+    .sum()
+}
+
+fn walk_node<O: ByteOrder>(node: &BymlReaderNode<'_, O>) -> usize {
+  match node {
+    BymlReaderNode::Array(array) => walk_array(array),
+    BymlReaderNode::Dictionary(dict) => walk_dict(dict),
+    node => {
+      black_box(node);
+      1
+    }
+  }
+}
+
+fn byml(c: &mut Criterion) {
+  let writer = large_document();
+  let mut data = Vec::new();
+  writer
+    .write::<LittleEndian>(&mut Cursor::new(&mut data), Version::V3)
+    .unwrap();
+
+  let mut group = c.benchmark_group("byml");
+  group.throughput(Throughput::Bytes(data.len() as u64));
+  group.bench_function("open and walk", |b| {
+    b.iter(|| {
+      let BymlReader::Dictionary(dict) = BymlReader::<LittleEndian>::new(black_box(&data)).unwrap()
+      else {
+        unreachable!()
+      };
+      walk_dict(&dict)
+    })
+  });
+  // the shared path clones every value out of its `Rc`, the owned one moves them into the arena
+  group.bench_function("build from shared tree", |b| {
+    b.iter_batched(
+      large_tree,
+      BymlWriter::from_dictionary,
+      BatchSize::LargeInput,
+    )
+  });
+  group.bench_function("build from owned tree", |b| {
+    b.iter_batched(
+      large_tree,
+      BymlWriter::from_owned_dictionary,
+      BatchSize::LargeInput,
+    )
+  });
+  group.bench_function("write", |b| {
+    b.iter(|| {
+      let mut data = Vec::with_capacity(data.len());
+      black_box(&writer)
+        .write::<LittleEndian>(&mut Cursor::new(&mut data), Version::V3)
+        .unwrap();
+      data
+    })
+  });
+  group.finish();
+}
+
+criterion_group!(benches, yaz0, byml);
+criterion_main!(benches);
@@ -1,6 +1,13 @@
-use std::{fs, io::Cursor};
+use std::{
+  fs::{self, File},
+  io::{BufWriter, Cursor, Write},
+};
 
-use senobi_library::{nw::bfres::reader::BfresReaderV8, sarc::reader::SarcReader, yaz0};
+use senobi_library::{
+  nw::bfres::{export_obj, reader::BfresReaderV8},
+  sarc::reader::SarcReader,
+  yaz0,
+};
 use zerocopy::LittleEndian;
 
 fn main() {
@@ -9,5 +16,9 @@ fn main() {
   let sarc = SarcReader::<LittleEndian>::new(&whopper).unwrap();
   let bfres = sarc.get("HomeBed.bfres").unwrap();
   fs::write("crates/library/examples/HomeBed.bfres", bfres).unwrap();
-  let _reader = BfresReaderV8::read(bfres).unwrap();
+  let reader = BfresReaderV8::new(bfres).unwrap();
+
+  let mut obj = BufWriter::new(File::create("HomeBed.obj").unwrap());
+  export_obj(&reader.models["HomeBed"], &mut obj).unwrap();
+  obj.flush().unwrap();
 }
@@ -4,17 +4,19 @@ use std::{
 };
 
 use ddsfile::{Caps2, Dds, DxgiFormat, Header, NewDxgiParams};
-use senobi_library::nw::{
-  bntx::reader::BntxReader,
-  gfx::{ChannelFormat, FormatInfo, TypeFormat},
+use senobi_library::{
+  nw::{
+    bntx::reader::BntxReader,
+    gfx::{ChannelFormat, FormatInfo, TypeFormat},
+  },
+  report_error,
 };
-use snafu::ErrorCompat;
 use tegra_swizzle::surface::deswizzle_surface;
 use zerocopy::LittleEndian;
 
 fn main() {
   let file_data = include_bytes!("HomeBed.bntx");
-  match BntxReader::<LittleEndian>::read(file_data) {
+  match BntxReader::<LittleEndian>::new(file_data) {
     Ok(bntx) => {
       for (name, texture) in bntx.textures {
         let params = NewDxgiParams {
@@ -43,13 +45,6 @@ fn main() {
       let body = image::load(&mut Cursor::new(&data), image::ImageFormat::Dds).unwrap();
       body.save("target/BedBody_alb.png").unwrap();
     }
-    Err(error) => {
-      for ele in error.iter_chain() {
-        println!("{}", ele);
-      }
-      if let Some(backtrace) = error.backtrace() {
-        println!("{:?}", backtrace)
-      }
-    }
+    Err(error) => report_error(&error),
   }
 }
@@ -1,4 +1,4 @@
-use std::{fs, io::Cursor};
+use std::fs;
 
 use senobi_library::byml::{
   self,
@@ -16,7 +16,7 @@ fn main() {
 
   let element = dict.get_string("UnitConfigName").unwrap().unwrap();
   println!("{element:?}");
-  // println!("{dict:?}");
+  // println!("{:?}", dict.pretty_print());
 
   let mut array = BymlWriterArray::new();
   array.push_u64(42);
@@ -28,10 +28,8 @@ fn main() {
   dict.insert_f64("hr", 53.84);
   dict.insert_array("haray", array);
   let dict = BymlWriter::from_dictionary(dict);
-  let mut data = Vec::new();
-  let mut writer = Cursor::new(&mut data);
-  dict
-    .write::<LittleEndian>(&mut writer, byml::writer::Version::V3)
+  let data = dict
+    .write_to_vec::<LittleEndian>(byml::writer::Version::V3)
     .unwrap();
   fs::write("target/my_awesome.byml", &data).unwrap();
 
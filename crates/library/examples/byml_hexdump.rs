@@ -0,0 +1,21 @@
+use std::{env, fs};
+
+use senobi_library::byml;
+use zerocopy::LittleEndian;
+
+fn main() {
+  let data = match env::args().nth(1) {
+    Some(path) => fs::read(path).unwrap(),
+    None => include_bytes!("./Bed.byml").to_vec(),
+  };
+
+  for annotation in byml::annotate::<LittleEndian>(&data) {
+    println!("{:?} 0x{:X}..0x{:X}", annotation.region, annotation.range.start, annotation.range.end);
+    let start = annotation.range.start.min(data.len());
+    let end = annotation.range.end.min(data.len());
+    for (row, bytes) in data[start..end].chunks(16).enumerate() {
+      let hex = bytes.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<_>>();
+      println!("  {:08X}  {}", start + row * 16, hex.join(" "));
+    }
+  }
+}
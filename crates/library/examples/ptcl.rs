@@ -11,7 +11,7 @@ fn main() {
   let whopper = yaz0::decompress(&mut Cursor::new(whopper)).unwrap();
   let sarc = SarcReader::<LittleEndian>::new(&whopper).unwrap();
 
-  for (key, value) in sarc.entries() {
+  for (key, value) in &sarc {
     println!("{key:?}");
     fs::write(format!("target/{key:?}"), value).unwrap();
   }
@@ -4,25 +4,30 @@ use std::{
 };
 
 use senobi_library::{
-  byml::reader::BymlReader, sarc::{self, reader::SarcReader}, yaz0::{self, DecompressionError}
+  Error, byml::reader::BymlReader, report_error, sarc::reader::SarcReader, yaz0,
 };
 use zerocopy::LittleEndian;
 
-#[snafu::report]
-fn main() -> Result<(), DecompressionError> {
+fn main() {
+  if let Err(error) = run() {
+    report_error(&error);
+  }
+}
+
+fn run() -> Result<(), Error> {
   let slice = include_bytes!("./Bed.szs").as_slice();
   let sarc = yaz0::decompress(&mut Cursor::new(slice))?;
   fs::write("target/Bed.sarc", &sarc)?;
-  let reader =SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&sarc)?;
   reader.entries().for_each(|(index, data)| {
     println!("{index:?}, {}", data.len());
   });
   
-  let reader = BymlReader::<LittleEndian>::new(reader.get("Bed.byml").expect("what")).expect("not located properly");
+  let reader = BymlReader::<LittleEndian>::new(reader.get("Bed.byml").expect("what"))?;
   
   for ele in reader.unwrap_dictionary().cstr_entries() {
     
-    println!("{ele:?}");
+    println!("{:?}", ele?);
   }
   Ok(())
 }
@@ -0,0 +1,147 @@
+use std::{collections::HashSet, ops::Range};
+
+use zerocopy::{ByteOrder, FromBytes, U32};
+
+use crate::{
+  byml::{
+    raw::{RawContainer, RawContainerBody},
+    types::{ContainerHeader, DataType, Header},
+  },
+  util::align_up,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+  Header,
+  HashKeyTable,
+  StringTable,
+  /// An array or dictionary container, `offset` is where its header starts.
+  Node { offset: u32, data_type: u8 },
+  /// The 8 bytes pointed to by an `I64`, `U64` or `F64` element.
+  LongValue,
+  /// The bytes pointed to by a `Binary` element, starting with their length.
+  Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionAnnotation {
+  pub range: Range<usize>,
+  pub region: Region,
+}
+
+/// Labels every byte range the reader would dereference, sorted by start offset.
+///
+/// Anything that can't be parsed is left unlabeled rather than failing, so the
+/// result is still useful for a damaged or unfamiliar file.
+pub fn annotate<O: ByteOrder>(data: &[u8]) -> Vec<RegionAnnotation> {
+  let Ok((header, _)) = Header::<O>::ref_from_prefix(data) else {
+    return Vec::new();
+  };
+
+  let mut annotations = vec![RegionAnnotation {
+    range: 0..size_of::<Header<O>>(),
+    region: Region::Header,
+  }];
+
+  for (offset, region) in [
+    (header.hash_key_offset.get(), Region::HashKeyTable),
+    (header.string_table_offset.get(), Region::StringTable),
+  ] {
+    if offset == 0 {
+      continue;
+    }
+    if let Some(range) = string_table_range::<O>(data, offset) {
+      annotations.push(RegionAnnotation { range, region });
+    }
+  }
+
+  let mut visited = HashSet::new();
+  let mut pending = vec![header.root_node_offset.get()];
+  while let Some(offset) = pending.pop() {
+    if !visited.insert(offset) {
+      continue;
+    }
+    let Ok(container) = RawContainer::<O>::new(data, offset) else {
+      continue;
+    };
+    if container.data_type() == DataType::StringTable as u8 {
+      continue;
+    }
+
+    let start = offset as usize;
+    let entries = container.entries() as usize;
+    let end = match container.body {
+      RawContainerBody::Array { .. } => {
+        align_up(start + size_of::<ContainerHeader<O>>() + entries, 4) + entries * size_of::<u32>()
+      }
+      _ => start + size_of::<ContainerHeader<O>>() + entries * 8,
+    };
+    annotations.push(RegionAnnotation {
+      range: start..end,
+      region: Region::Node {
+        offset,
+        data_type: container.data_type(),
+      },
+    });
+
+    for element in container.elements() {
+      match element.data_type {
+        value if value == DataType::Array as u8 || value == DataType::Dictionary as u8 => {
+          pending.push(element.value)
+        }
+        value
+          if value == DataType::I64 as u8
+            || value == DataType::U64 as u8
+            || value == DataType::F64 as u8 =>
+        {
+          let start = element.value as usize;
+          if visited.insert(element.value) && start + size_of::<u64>() <= data.len() {
+            annotations.push(RegionAnnotation {
+              range: start..start + size_of::<u64>(),
+              region: Region::LongValue,
+            });
+          }
+        }
+        value if value == DataType::Binary as u8 => {
+          let start = element.value as usize;
+          let Some((length, _)) = data
+            .get(start..)
+            .and_then(|bytes| U32::<O>::read_from_prefix(bytes).ok())
+          else {
+            continue;
+          };
+          let end = start + size_of::<u32>() + length.get() as usize;
+          if visited.insert(element.value) && end <= data.len() {
+            annotations.push(RegionAnnotation {
+              range: start..end,
+              region: Region::Binary,
+            });
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+
+  annotations.sort_by_key(|annotation| (annotation.range.start, annotation.range.end));
+  annotations
+}
+
+fn string_table_range<O: ByteOrder>(data: &[u8], offset: u32) -> Option<Range<usize>> {
+  let container = RawContainer::<O>::new(data, offset).ok()?;
+  let RawContainerBody::StringTable { offsets } = container.body else {
+    return None;
+  };
+
+  let start = offset as usize;
+  let end = match offsets.len().checked_sub(2) {
+    // the terminating offset isn't written consistently, so find the last string's nul instead
+    Some(last) => {
+      let last = start.checked_add(offsets[last].get() as usize)?;
+      let length = data.get(last..)?.iter().position(|&byte| byte == 0)?;
+      last + length + 1
+    }
+    None => start + size_of::<ContainerHeader<O>>() + size_of::<u32>(),
+  };
+  Some(start..end)
+}
@@ -0,0 +1,197 @@
+use std::fmt::Display;
+
+use serde::de::{
+  self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+  value::MapAccessDeserializer,
+};
+use snafu::{Backtrace, GenerateImplicitData, ResultExt, Snafu};
+use zerocopy::ByteOrder;
+
+use crate::byml::{
+  ElementReadError,
+  reader::{BymlReaderArray, BymlReaderNode},
+};
+
+#[derive(Snafu, Debug)]
+pub enum DeserializeError {
+  #[snafu(display("{message}"))]
+  Custom {
+    message: String,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("failed to read element: {source}"))]
+  Element {
+    #[snafu(backtrace)]
+    source: ElementReadError,
+  },
+}
+
+impl de::Error for DeserializeError {
+  fn custom<T: Display>(message: T) -> Self {
+    DeserializeError::Custom {
+      message: message.to_string(),
+      backtrace: Backtrace::generate(),
+    }
+  }
+}
+
+/// Deserializes `T` from a node, reading the document lazily as `T` asks for fields.
+///
+/// Dictionary keys are matched against field names as-is, so structs usually want
+/// `#[serde(rename_all = "PascalCase")]` to line up with the game's naming.
+pub fn from_node<T: DeserializeOwned, O: ByteOrder>(
+  node: &BymlReaderNode<'_, O>,
+) -> Result<T, DeserializeError> {
+  T::deserialize(NodeDeserializer { node })
+}
+
+struct NodeDeserializer<'r, 'a, O: ByteOrder> {
+  node: &'r BymlReaderNode<'a, O>,
+}
+
+impl<'de, O: ByteOrder> de::Deserializer<'de> for NodeDeserializer<'_, '_, O> {
+  type Error = DeserializeError;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    match self.node {
+      BymlReaderNode::Array(array) => visitor.visit_seq(ArrayAccess { array, index: 0 }),
+      BymlReaderNode::Dictionary(dict) => visitor.visit_map(DictAccess {
+        entries: dict.entries(),
+        value: None,
+      }),
+      BymlReaderNode::Bool(value) => visitor.visit_bool(*value),
+      BymlReaderNode::I32(value) => visitor.visit_i32(*value),
+      BymlReaderNode::F32(value) => visitor.visit_f32(*value),
+      BymlReaderNode::U32(value) => visitor.visit_u32(*value),
+      BymlReaderNode::I64(value) => visitor.visit_i64(*value),
+      BymlReaderNode::U64(value) => visitor.visit_u64(*value),
+      BymlReaderNode::F64(value) => visitor.visit_f64(*value),
+      BymlReaderNode::String(value) => visitor.visit_str(
+        value
+          .to_str()
+          .map_err(|source| ElementReadError::NonUtf8String {
+            source,
+            backtrace: Backtrace::generate(),
+          })
+          .context(ElementSnafu)?,
+      ),
+      BymlReaderNode::Binary(value) => visitor.visit_bytes(value),
+      BymlReaderNode::Null => visitor.visit_unit(),
+    }
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    match self.node {
+      BymlReaderNode::Null => visitor.visit_none(),
+      _ => visitor.visit_some(self),
+    }
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  // unit variants are stored as their name, anything else as a dictionary with a single entry
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match self.node {
+      BymlReaderNode::String(value) => {
+        let value = value
+          .to_str()
+          .map_err(|source| ElementReadError::NonUtf8String {
+            source,
+            backtrace: Backtrace::generate(),
+          })
+          .context(ElementSnafu)?;
+        visitor.visit_enum(value.into_deserializer())
+      }
+      BymlReaderNode::Dictionary(dict) if dict.len() == 1 => {
+        visitor.visit_enum(MapAccessDeserializer::new(DictAccess {
+          entries: dict.entries(),
+          value: None,
+        }))
+      }
+      _ => Err(de::Error::custom(
+        "expected a string or a dictionary with a single entry for an enum",
+      )),
+    }
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct seq tuple
+    tuple_struct map struct identifier ignored_any
+  }
+}
+
+struct ArrayAccess<'r, 'a, O> {
+  array: &'r BymlReaderArray<'a, O>,
+  index: u32,
+}
+
+impl<'de, O: ByteOrder> SeqAccess<'de> for ArrayAccess<'_, '_, O> {
+  type Error = DeserializeError;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, Self::Error> {
+    let Some(node) = self.array.get_element(self.index).context(ElementSnafu)? else {
+      return Ok(None);
+    };
+    self.index += 1;
+
+    seed.deserialize(NodeDeserializer { node: &node }).map(Some)
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.array.len() - self.index as usize)
+  }
+}
+
+struct DictAccess<'r, O: ByteOrder, I> {
+  entries: I,
+  value: Option<BymlReaderNode<'r, O>>,
+}
+
+impl<'de, 'r, O, I> MapAccess<'de> for DictAccess<'r, O, I>
+where
+  O: ByteOrder,
+  I: ExactSizeIterator<Item = Result<(&'r str, BymlReaderNode<'r, O>), ElementReadError>>,
+{
+  type Error = DeserializeError;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(
+    &mut self,
+    seed: K,
+  ) -> Result<Option<K::Value>, Self::Error> {
+    let Some(entry) = self.entries.next() else {
+      return Ok(None);
+    };
+    let (key, value) = entry.context(ElementSnafu)?;
+    self.value = Some(value);
+
+    seed.deserialize(key.into_deserializer()).map(Some)
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+    let node = self
+      .value
+      .take()
+      .expect("next_value_seed was called before next_key_seed");
+
+    seed.deserialize(NodeDeserializer { node: &node })
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.entries.len())
+  }
+}
@@ -0,0 +1,48 @@
+//! Converts documents to [`serde_json::Value`]s, for dumping them while debugging or handing
+//! them to tools that only understand JSON.
+
+use serde_json::{Map, Number, Value};
+use zerocopy::ByteOrder;
+
+use crate::byml::{ElementReadError, reader::BymlReaderNode};
+
+/// Converts `node` and everything beneath it.
+///
+/// Dictionaries become objects and arrays become arrays. Every number type becomes a JSON
+/// number, with 64-bit integers kept exact. Floats that JSON can't represent, NaN and the
+/// infinities, become `null`.
+///
+/// Invalid UTF-8 in keys and strings is replaced with U+FFFD. JSON has no byte strings, so binary
+/// values become arrays of numbers, the same as `serde_json` writes a `Vec<u8>`.
+pub fn to_json<O: ByteOrder>(node: &BymlReaderNode<'_, O>) -> Result<Value, ElementReadError> {
+  Ok(match node {
+    BymlReaderNode::Array(array) => Value::Array(
+      array
+        .values()
+        .map(|element| to_json(&element?))
+        .collect::<Result<_, _>>()?,
+    ),
+    BymlReaderNode::Dictionary(dict) => {
+      let mut object = Map::new();
+      for entry in dict.cstr_entries() {
+        let (key, value) = entry?;
+        object.insert(key.to_string_lossy().into_owned(), to_json(&value)?);
+      }
+      Value::Object(object)
+    }
+    BymlReaderNode::Bool(value) => Value::Bool(*value),
+    BymlReaderNode::I32(value) => Value::from(*value),
+    BymlReaderNode::F32(value) => float(*value as f64),
+    BymlReaderNode::U32(value) => Value::from(*value),
+    BymlReaderNode::I64(value) => Value::from(*value),
+    BymlReaderNode::U64(value) => Value::from(*value),
+    BymlReaderNode::F64(value) => float(*value),
+    BymlReaderNode::String(value) => Value::String(value.to_string_lossy().into_owned()),
+    BymlReaderNode::Binary(value) => Value::from(*value),
+    BymlReaderNode::Null => Value::Null,
+  })
+}
+
+fn float(value: f64) -> Value {
+  Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
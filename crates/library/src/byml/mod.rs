@@ -1,10 +1,40 @@
+mod annotate;
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "bytes")]
+pub mod owned;
+pub mod raw;
 pub mod reader;
-mod types;
+#[cfg(feature = "serde")]
+mod ser;
+pub mod types;
+mod value;
+mod visit;
 pub mod writer;
 
-pub const MAXIMUM_SUPPORTED_VERSION: u16 = 3;
+pub use annotate::{Region, RegionAnnotation, annotate};
+#[cfg(feature = "serde")]
+pub use de::{DeserializeError, from_node};
+#[cfg(feature = "serde")]
+pub use ser::{SerializeError, to_writer};
+pub use value::BymlValue;
+pub use visit::{BymlVisitor, NodeKey};
 
-use string_table_error::StringTableError;
+/// Versions 4 through 7 keep the version 2 header and containers. Version 4 adds binary values,
+/// and node types from later versions that aren't implemented yet are read as
+/// [`ElementReadError::InvalidDataType`].
+pub const MAXIMUM_SUPPORTED_VERSION: u16 = 7;
+/// How many containers deep [`reader::BymlReader::new`] lets elements be read, the root counting
+/// as the first. See [`reader::BymlReader::with_max_depth`].
+pub const DEFAULT_MAX_DEPTH: u32 = 128;
+/// Version 1 documents have an extra path table offset in their header and are rejected. Version
+/// 0 isn't a real version, but shipped files leave the field zeroed while using the version 2
+/// layout, so it's still accepted.
+pub const MINIMUM_SUPPORTED_VERSION: u16 = 2;
+
+pub use string_table_error::StringTableError;
 mod string_table_error {
   use snafu::Backtrace;
 
@@ -33,6 +63,7 @@ mod string_table_error {
   }
 }
 
+pub use array_error::ContainerError;
 mod array_error {
   use snafu::Snafu;
 
@@ -66,7 +97,7 @@ mod array_error {
   }
 }
 
-use open_error::OpenError;
+pub use open_error::OpenError;
 mod open_error {
   use snafu::Backtrace;
 
@@ -82,8 +113,16 @@ mod open_error {
       actual: Order,
       backtrace: Backtrace,
     },
-    #[snafu(display("unsupported version {actual}, greatest supported version is {maximum}"))]
+    #[snafu(display("expected magic to be \"BY\" or \"YB\", got {actual:02X?}"))]
+    IncorrectMagic {
+      actual: [u8; 2],
+      backtrace: Backtrace,
+    },
+    #[snafu(display(
+      "unsupported version {actual}, supported versions are {minimum} through {maximum}"
+    ))]
     UnsupportedVersion {
+      minimum: u16,
       maximum: u16,
       actual: u16,
       backtrace: Backtrace,
@@ -100,6 +139,15 @@ mod open_error {
       offset: u32,
       backtrace: Backtrace,
     },
+    #[snafu(display(
+      "root node at 0x{offset:X} points into a string table spanning 0x{table_start:X}..0x{table_end:X}"
+    ))]
+    RootNodeOverlapsTable {
+      offset: u32,
+      table_start: usize,
+      table_end: usize,
+      backtrace: Backtrace,
+    },
     #[snafu(display("root node pointer is misaligned"))]
     RootNodeMisaligned {
       size: usize,
@@ -152,30 +200,40 @@ mod open_error {
       #[snafu(backtrace)]
       source: ContainerError,
     },
+    #[snafu(display(
+      "the header's offsets are out of bounds as {magic_order:?}, but not as {likely:?}; the magic may not match the data's byte order"
+    ))]
+    OffsetsSuggestWrongEndianness {
+      magic_order: Order,
+      likely: Order,
+      backtrace: Backtrace,
+    },
   }
 }
 
-use string_read_error::StringReadError;
+pub use string_read_error::StringReadError;
 mod string_read_error {
   use std::str::Utf8Error;
 
-  use snafu::Snafu;
+  use snafu::{Backtrace, Snafu};
 
   #[derive(Snafu, Debug)]
   pub enum StringReadError {
-    OffsetEntryOutOfBounds {
-      offset: u32,
-    },
-    OffsetOutsideOfStringData,
-    UnterminatedString,
+    #[snafu(display("string index {offset} is past the end of the string table"))]
+    OffsetEntryOutOfBounds { offset: u32, backtrace: Backtrace },
+    #[snafu(display("string offset points outside of the string data"))]
+    OffsetOutsideOfStringData { backtrace: Backtrace },
+    #[snafu(display("string is missing its nul terminator"))]
+    UnterminatedString { backtrace: Backtrace },
     #[snafu(display("{error}"))]
     NonUtf8String {
       error: Utf8Error,
+      backtrace: Backtrace,
     },
   }
 }
 
-use element_error::ElementReadError;
+pub use element_error::ElementReadError;
 mod element_error {
   use std::str::Utf8Error;
 
@@ -218,7 +276,7 @@ mod element_error {
       backtrace: snafu::Backtrace,
     },
     #[snafu(display("string table was referenced as an element"))]
-    UnexpectedStringTable,
+    UnexpectedStringTable { backtrace: snafu::Backtrace },
     #[snafu(display("error while deserializing container: {source}"))]
     Container {
       #[snafu(backtrace)]
@@ -234,6 +292,78 @@ mod element_error {
       source: Utf8Error,
       backtrace: snafu::Backtrace,
     },
+    #[snafu(display("container at 0x{offset:X} contains itself"))]
+    CyclicContainer {
+      offset: u32,
+      backtrace: snafu::Backtrace,
+    },
+    #[snafu(display(
+      "binary value at 0x{offset:X} claims {length} bytes, past the end of the {size} byte buffer"
+    ))]
+    BinaryOutOfBounds {
+      size: usize,
+      offset: u32,
+      length: u32,
+      backtrace: snafu::Backtrace,
+    },
+    #[snafu(display("containers are nested more than {depth} deep"))]
+    MaxDepthExceeded {
+      depth: u32,
+      backtrace: snafu::Backtrace,
+    },
+  }
+}
+
+pub use insert_error::InsertError;
+mod insert_error {
+  use std::ffi::NulError;
+
+  use snafu::{Backtrace, Snafu};
+
+  #[derive(Snafu, Debug)]
+  pub enum InsertError {
+    #[snafu(display("dictionary already has an entry for {key:?}"))]
+    DuplicateKey { key: String, backtrace: Backtrace },
+    #[snafu(display("key contains a nul byte: {source}"))]
+    NulInKey {
+      source: NulError,
+      backtrace: Backtrace,
+    },
+  }
+}
+
+pub use validation_error::ValidationError;
+mod validation_error {
+  use snafu::{Backtrace, Snafu};
+
+  #[derive(Snafu, Debug)]
+  pub enum ValidationError {
+    #[snafu(display(
+      "container at {path} has {entries} entries, more than a byml container can hold"
+    ))]
+    ContainerTooLarge {
+      path: String,
+      entries: usize,
+      backtrace: Backtrace,
+    },
+    #[snafu(display("string at {path} is {length} bytes long, too long to be stored"))]
+    StringTooLong {
+      path: String,
+      length: usize,
+      backtrace: Backtrace,
+    },
+    #[snafu(display("{table} table has {entries} unique strings, more than it can hold"))]
+    TooManyStrings {
+      table: &'static str,
+      entries: usize,
+      backtrace: Backtrace,
+    },
+    #[snafu(display("{table} table's strings take up {size} bytes, more than it can address"))]
+    StringTableTooLarge {
+      table: &'static str,
+      size: usize,
+      backtrace: Backtrace,
+    },
   }
 }
 
@@ -242,6 +372,8 @@ pub mod write_error {
 
   use snafu::{Backtrace, GenerateImplicitData, Snafu};
 
+  use crate::yaz0::CompressionError;
+
   #[derive(Snafu, Debug)]
   pub enum WriteError {
     #[snafu(display("error while writing: {source}"))]
@@ -251,6 +383,13 @@ pub mod write_error {
     },
     #[snafu(display("overflowed, may be too large to serialize"))]
     Overflowed { backtrace: Backtrace },
+    #[snafu(display("binary values need version 4 or later, but version {version} was requested"))]
+    BinaryNeedsVersion4 { version: u16, backtrace: Backtrace },
+    #[snafu(display("error while compressing: {source}"))]
+    Compression {
+      #[snafu(backtrace)]
+      source: CompressionError,
+    },
   }
 
   impl From<io::Error> for WriteError {
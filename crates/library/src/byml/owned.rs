@@ -0,0 +1,44 @@
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use zerocopy::ByteOrder;
+
+use crate::byml::{OpenError, reader::BymlReader};
+
+/// A document that owns its data, so that it can be cloned and sent between threads without
+/// borrowing from a buffer. Clones share the underlying [`Bytes`].
+///
+/// The data is validated once by [`Self::from_bytes`]; [`Self::reader`] then borrows a
+/// [`BymlReader`] over it, which only rereads the header and root container.
+pub struct OwnedBymlReader<O> {
+  data: Bytes,
+  phantom: PhantomData<O>,
+}
+
+impl<O: ByteOrder> OwnedBymlReader<O> {
+  pub fn from_bytes(data: Bytes) -> Result<Self, OpenError> {
+    BymlReader::<O>::new(&data)?;
+
+    Ok(Self {
+      data,
+      phantom: PhantomData,
+    })
+  }
+
+  pub fn reader(&self) -> BymlReader<'_, O> {
+    BymlReader::new(&self.data).expect("data was validated when the reader was created")
+  }
+
+  pub fn bytes(&self) -> &Bytes {
+    &self.data
+  }
+}
+
+impl<O> Clone for OwnedBymlReader<O> {
+  fn clone(&self) -> Self {
+    Self {
+      data: self.data.clone(),
+      phantom: PhantomData,
+    }
+  }
+}
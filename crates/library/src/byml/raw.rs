@@ -0,0 +1,155 @@
+use std::marker::PhantomData;
+
+use either::Either;
+use snafu::{Backtrace, OptionExt, Snafu};
+use zerocopy::{ByteOrder, FromBytes, U32};
+
+use crate::{
+  byml::types::{ContainerHeader, DataType, TryDictEntry},
+  util::align_up,
+};
+
+#[derive(Snafu, Debug)]
+pub enum RawContainerError {
+  #[snafu(display("container header is out of bounds: offset is 0x{offset:X}"))]
+  HeaderOutOfBounds { offset: u32, backtrace: Backtrace },
+  #[snafu(display("data type 0x{value:02X} at 0x{offset:X} is not a container"))]
+  NotAContainer {
+    offset: u32,
+    value: u8,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("container with {entries} entries is out of bounds: offset is 0x{offset:X}"))]
+  BodyOutOfBounds {
+    offset: u32,
+    entries: u32,
+    backtrace: Backtrace,
+  },
+}
+
+/// The on-disk layout of a container, without resolving strings or following pointers.
+pub enum RawContainerBody<'a, O> {
+  Array {
+    data_types: &'a [u8],
+    values: &'a [U32<O>],
+  },
+  Dictionary {
+    entries: &'a [TryDictEntry<O>],
+  },
+  StringTable {
+    /// One more than the entry count, the last offset marks the end of the string data.
+    offsets: &'a [U32<O>],
+  },
+}
+
+/// A single raw element of a container, `key_index` is only present for dictionaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawElement {
+  pub key_index: Option<u32>,
+  pub data_type: u8,
+  pub value: u32,
+}
+
+pub struct RawContainer<'a, O> {
+  pub offset: u32,
+  pub header: &'a ContainerHeader<O>,
+  pub body: RawContainerBody<'a, O>,
+  _p: PhantomData<O>,
+}
+
+impl<'a, O: ByteOrder> RawContainer<'a, O> {
+  pub fn new(data: &'a [u8], offset: u32) -> Result<Self, RawContainerError> {
+    let start = offset as usize;
+    let header = start
+      .checked_add(size_of::<ContainerHeader<O>>())
+      .and_then(|end| data.get(start..end))
+      .context(HeaderOutOfBoundsSnafu { offset })?;
+    let header = ContainerHeader::<O>::ref_from_bytes(header).unwrap();
+    let entries = header.entries();
+    // the header is in bounds, so the body starting right after it can't overflow
+    let body_start = start + size_of::<ContainerHeader<O>>();
+    let out_of_bounds = BodyOutOfBoundsSnafu { offset, entries };
+    let body_range = |size: usize, count: usize| {
+      let end = size
+        .checked_mul(count)
+        .and_then(|length| body_start.checked_add(length))?;
+      data.get(body_start..end)
+    };
+
+    let body = match header.data_type {
+      value if value == DataType::Array as u8 => {
+        let data_types = body_range(1, entries as usize).context(out_of_bounds)?;
+        let values_start = align_up(body_start + data_types.len(), 4);
+        let values = (entries as usize)
+          .checked_mul(size_of::<u32>())
+          .and_then(|length| data.get(values_start..values_start.checked_add(length)?))
+          .context(out_of_bounds)?;
+
+        RawContainerBody::Array {
+          data_types,
+          values: <[U32<O>]>::ref_from_bytes(values).unwrap(),
+        }
+      }
+      value if value == DataType::Dictionary as u8 => {
+        let dict_entries =
+          body_range(size_of::<TryDictEntry<O>>(), entries as usize).context(out_of_bounds)?;
+
+        RawContainerBody::Dictionary {
+          entries: <[TryDictEntry<O>]>::ref_from_bytes(dict_entries).unwrap(),
+        }
+      }
+      value if value == DataType::StringTable as u8 => {
+        let offsets = body_range(size_of::<u32>(), entries as usize + 1).context(out_of_bounds)?;
+
+        RawContainerBody::StringTable {
+          offsets: <[U32<O>]>::ref_from_bytes(offsets).unwrap(),
+        }
+      }
+      value => return NotAContainerSnafu { offset, value }.fail(),
+    };
+
+    Ok(Self {
+      offset,
+      header,
+      body,
+      _p: PhantomData,
+    })
+  }
+
+  pub fn data_type(&self) -> u8 {
+    self.header.data_type
+  }
+
+  pub fn entries(&self) -> u32 {
+    self.header.entries()
+  }
+
+  /// Iterates over the container's elements, string tables yield their offsets as values.
+  pub fn elements(&self) -> impl Iterator<Item = RawElement> + '_ {
+    match &self.body {
+      RawContainerBody::Array { data_types, values } => {
+        Either::Left(data_types.iter().zip(values.iter()).map(|(data_type, value)| {
+          RawElement {
+            key_index: None,
+            data_type: *data_type,
+            value: value.get(),
+          }
+        }))
+      }
+      RawContainerBody::Dictionary { entries } => {
+        Either::Right(Either::Left(entries.iter().map(|entry| RawElement {
+          key_index: Some(entry.hash_key_index()),
+          data_type: entry.data_type,
+          value: entry.value.get(),
+        })))
+      }
+      RawContainerBody::StringTable { offsets } => {
+        Either::Right(Either::Right(offsets.iter().map(|offset| RawElement {
+          key_index: None,
+          data_type: DataType::StringTable as u8,
+          value: offset.get(),
+        })))
+      }
+    }
+  }
+}
@@ -1,13 +1,20 @@
-use std::{ffi::CStr, fmt::Debug, marker::PhantomData};
+use std::{
+  borrow::Cow, collections::HashMap, ffi::CStr, fmt::Debug, marker::PhantomData, ops::Range, rc::Rc,
+};
 
 use num_traits::FromPrimitive;
 use snafu::{Backtrace, GenerateImplicitData};
-use zerocopy::{ByteOrder, F64, FromBytes, I64, Order as ZCOrder, TryFromBytes, U32, U64};
+use zerocopy::{
+  BigEndian, ByteOrder, F64, FromBytes, I64, Immutable, LittleEndian, Order as ZCOrder,
+  TryFromBytes, U32, U64,
+};
 
 use crate::{
   byml::{
-    array_error::ContainerError, types::{ContainerHeader, DataType, DictEntry, Header, TryDictEntry}, ElementReadError, OpenError, StringReadError, StringTableError
+    annotate, array_error::ContainerError, visit::{self, BymlVisitor}, types::{ContainerHeader, DataType, DictEntry, Header, InvalidDataType, TryDictEntry}, writer::{BymlWriterArray, BymlWriterDict, BymlWriterNode, RawFloat}, ElementReadError, OpenError, StringReadError, StringTableError, DEFAULT_MAX_DEPTH, MAXIMUM_SUPPORTED_VERSION, MINIMUM_SUPPORTED_VERSION
   },
+  magic,
+  trace::{self, TraceError},
   util::{align_up, Order},
 };
 
@@ -21,27 +28,22 @@ struct StringTable<'a, O> {
 impl<'a, O: ByteOrder> StringTable<'a, O> {
   fn get_string_table(data: &'a [u8], offset: u32) -> Result<Self, StringTableError> {
     let usize_offset = offset as usize;
-    let header =
-      data
-        .get(usize_offset..usize_offset + 4)
-        .ok_or(StringTableError::HeaderOutOfBounds {
-          size: data.len(),
-          offset,
-          backtrace: Backtrace::generate(),
-        })?;
-    let header = ContainerHeader::<O>::read_from_bytes(header).unwrap();
+    let header = read_at::<ContainerHeader<O>>(data, usize_offset).ok_or(
+      StringTableError::HeaderOutOfBounds {
+        size: data.len(),
+        offset,
+        backtrace: Backtrace::generate(),
+      },
+    )?;
     let entries = header.entries();
-    let offset_table_end = usize_offset + 4 + (entries as usize * 4);
-    let offset_table = data.get(usize_offset + 4..offset_table_end).ok_or(
+    let offset_table = slice_at::<U32<O>>(data, usize_offset + 4, entries as usize).ok_or(
       StringTableError::AddressTableOutOfBounds {
         size: data.len(),
         offset: offset + 4,
         backtrace: Backtrace::generate(),
       },
     )?;
-
-    let offset_table =
-      <[U32<O>]>::ref_from_bytes_with_elems(offset_table, entries as usize).unwrap();
+    trace::debug!(offset, entries, "resolved string table");
 
     Ok(Self {
       offset_table,
@@ -50,17 +52,114 @@ impl<'a, O: ByteOrder> StringTable<'a, O> {
     })
   }
 
-  fn read_string(&self, index: u32) -> Result<&CStr, StringReadError> {
-    let offset = self
+  fn string_offset(&self, index: u32) -> Result<usize, StringReadError> {
+    self
       .offset_table
       .get(index as usize)
-      .ok_or(StringReadError::OffsetEntryOutOfBounds { offset: index })
+      .ok_or_else(|| StringReadError::OffsetEntryOutOfBounds {
+        offset: index,
+        backtrace: Backtrace::generate(),
+      })
       .map(|offset| offset.get() as usize)?
       .checked_add(self.start_offset)
-      .ok_or(StringReadError::OffsetOutsideOfStringData)?;
+      .ok_or_else(|| StringReadError::OffsetOutsideOfStringData {
+        backtrace: Backtrace::generate(),
+      })
+  }
+
+  fn read_string(&self, index: u32) -> Result<&'a CStr, StringReadError> {
+    let offset = self.string_offset(index)?;
+
+    let string_data = self
+      .string_data
+      .get(offset..)
+      .ok_or_else(|| StringReadError::OffsetOutsideOfStringData {
+        backtrace: Backtrace::generate(),
+      })?;
+
+    CStr::from_bytes_until_nul(string_data).map_err(|_| StringReadError::UnterminatedString {
+      backtrace: Backtrace::generate(),
+    })
+  }
+
+  /// The bytes covered by the table, from its header to the end of its last string.
+  fn byte_range(&self) -> Range<usize> {
+    let offset_table_end = self.start_offset + 4 + self.offset_table.len() * 4;
+    let strings_end = self
+      .offset_table
+      .len()
+      .checked_sub(1)
+      .and_then(|last| {
+        let offset = self.string_offset(last as u32).ok()?;
+        Some(offset + self.read_string(last as u32).ok()?.count_bytes() + 1)
+      })
+      .unwrap_or(0);
+
+    self.start_offset..offset_table_end.max(strings_end)
+  }
+
+  /// Binary searches the (sorted) table for `key`, returning its index.
+  fn find(&self, key: &[u8]) -> Result<Option<u32>, StringReadError> {
+    let mut low = 0;
+    let mut high = self.offset_table.len();
+
+    while low < high {
+      let mid = low + (high - low) / 2;
+      match self.read_string(mid as u32)?.to_bytes().cmp(key) {
+        std::cmp::Ordering::Less => low = mid + 1,
+        std::cmp::Ordering::Equal => return Ok(Some(mid as u32)),
+        std::cmp::Ordering::Greater => high = mid,
+      }
+    }
+
+    Ok(None)
+  }
+}
+
+/// Memoizes which hash key table index a key resolves to, so looking up the same key in many
+/// dictionaries of a document only searches the hash key table once.
+///
+/// All dictionaries of a document share one hash key table. The cache borrows it, so it can't
+/// outlive the document, and clears itself if it is used with a different document.
+pub struct KeyCache<'a, O> {
+  table: Option<&'a [U32<O>]>,
+  indices: HashMap<Box<str>, Option<u32>>,
+}
+
+impl<O> Default for KeyCache<'_, O> {
+  fn default() -> Self {
+    Self {
+      table: None,
+      indices: HashMap::new(),
+    }
+  }
+}
+
+impl<'a, O: ByteOrder> KeyCache<'a, O> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn resolve(
+    &mut self,
+    table: &StringTable<'a, O>,
+    key: &str,
+  ) -> Result<Option<u32>, StringReadError> {
+    if !self
+      .table
+      .is_some_and(|cached| std::ptr::eq(cached, table.offset_table))
+    {
+      self.table = Some(table.offset_table);
+      self.indices.clear();
+    }
 
-    CStr::from_bytes_until_nul(self.string_data.get(offset..).unwrap())
-      .map_err(|_| StringReadError::UnterminatedString)
+    if let Some(index) = self.indices.get(key) {
+      return Ok(*index);
+    }
+
+    let index = table.find(key.as_bytes())?;
+    self.indices.insert(key.into(), index);
+    Ok(index)
   }
 }
 
@@ -72,18 +171,79 @@ pub enum BymlReader<'a, O> {
 
 impl<'a, O: ByteOrder> BymlReader<'a, O> {
   pub fn new(data: &'a [u8]) -> Result<Self, OpenError> {
-    let header = data
-      .get(..size_of::<Header<O>>())
-      .ok_or(OpenError::NotEnoughDataForHeader {
-        size: data.len(),
-        offset: 0,
-        backtrace: Backtrace::generate(),
-      })?;
-    let header = Header::<O>::ref_from_bytes(header).unwrap();
+    trace::enter_span!("byml::open", size = data.len());
+    Self::open(data, DEFAULT_MAX_DEPTH).trace_error()
+  }
+
+  /// Like [`Self::new`], but lets elements be read `max_depth` containers deep instead of
+  /// [`DEFAULT_MAX_DEPTH`]. The root is the first, and is always readable.
+  ///
+  /// Anything that walks the whole document, like [`BymlReaderNode::to_writer_node`], recurses
+  /// once per container, so the limit keeps malicious files from overflowing the stack. Reading
+  /// past it fails with [`ElementReadError::MaxDepthExceeded`].
+  pub fn with_max_depth(data: &'a [u8], max_depth: u32) -> Result<Self, OpenError> {
+    trace::enter_span!("byml::open", size = data.len());
+    Self::open(data, max_depth).trace_error()
+  }
+
+  /// Like [`Self::new`], but first checks that the header's offsets fit in `data` in the byte
+  /// order the magic picked. Offsets that only fit when byte swapped return
+  /// [`OpenError::OffsetsSuggestWrongEndianness`] rather than whatever reading them as they are
+  /// would fail with, which helps with files whose magic was changed without their data.
+  pub fn new_checked(data: &'a [u8]) -> Result<Self, OpenError> {
+    trace::enter_span!("byml::open", size = data.len());
+    Self::check_offsets(data)
+      .and_then(|()| Self::open(data, DEFAULT_MAX_DEPTH))
+      .trace_error()
+  }
+
+  fn check_offsets(data: &'a [u8]) -> Result<(), OpenError> {
+    let Some(header) = read_at::<Header<O>>(data, 0) else {
+      // the full open reports this
+      return Ok(());
+    };
+    let (magic_order, likely) = match (&header.magic, O::ORDER) {
+      (&magic::BYML_LITTLE_ENDIAN, ZCOrder::LittleEndian) => {
+        (Order::LittleEndian, Order::BigEndian)
+      }
+      (&magic::BYML_BIG_ENDIAN, ZCOrder::BigEndian) => (Order::BigEndian, Order::LittleEndian),
+      // the full open reports the mismatched magic
+      _ => return Ok(()),
+    };
+
+    let offsets = [
+      header.hash_key_offset.get(),
+      header.string_table_offset.get(),
+      header.root_node_offset.get(),
+    ];
+
+    // null offsets are fine, anything else has to point past the header and into the data
+    let plausible = |offsets: [u32; 3]| {
+      offsets.iter().all(|&offset| {
+        offset == 0 || (size_of::<Header<O>>()..data.len()).contains(&(offset as usize))
+      })
+    };
+    if plausible(offsets) || !plausible(offsets.map(u32::swap_bytes)) {
+      return Ok(());
+    }
+
+    Err(OpenError::OffsetsSuggestWrongEndianness {
+      magic_order,
+      likely,
+      backtrace: Backtrace::generate(),
+    })
+  }
+
+  fn open(data: &'a [u8], max_depth: u32) -> Result<Self, OpenError> {
+    let header = read_at::<Header<O>>(data, 0).ok_or(OpenError::NotEnoughDataForHeader {
+      size: data.len(),
+      offset: 0,
+      backtrace: Backtrace::generate(),
+    })?;
 
     match (&header.magic, O::ORDER) {
-      (b"YB", ZCOrder::LittleEndian) => {}
-      (b"BY", ZCOrder::BigEndian) => {}
+      (&magic::BYML_LITTLE_ENDIAN, ZCOrder::LittleEndian) => {}
+      (&magic::BYML_BIG_ENDIAN, ZCOrder::BigEndian) => {}
       (_, ZCOrder::BigEndian) => {
         return Err(OpenError::EndiannessMismatch {
           expected: Order::BigEndian,
@@ -100,6 +260,16 @@ impl<'a, O: ByteOrder> BymlReader<'a, O> {
       }
     }
 
+    let version = header.version.get();
+    if version != 0 && !(MINIMUM_SUPPORTED_VERSION..=MAXIMUM_SUPPORTED_VERSION).contains(&version) {
+      return Err(OpenError::UnsupportedVersion {
+        minimum: MINIMUM_SUPPORTED_VERSION,
+        maximum: MAXIMUM_SUPPORTED_VERSION,
+        actual: version,
+        backtrace: Backtrace::generate(),
+      });
+    }
+
     fn get_string_table<'a, O: ByteOrder>(
       offset: u32,
       data: &'a [u8],
@@ -145,14 +315,25 @@ impl<'a, O: ByteOrder> BymlReader<'a, O> {
       });
     };
 
-    let container_header = data
-      .get(root_node_offset as usize..(root_node_offset as usize + 4))
-      .ok_or(OpenError::RootNodeOutOfBounds {
+    for table in [&string_table, &hash_key_table].into_iter().flatten() {
+      let range = table.byte_range();
+      if range.contains(&(root_node_offset as usize)) {
+        return Err(OpenError::RootNodeOverlapsTable {
+          offset: root_node_offset,
+          table_start: range.start,
+          table_end: range.end,
+          backtrace: Backtrace::generate(),
+        });
+      }
+    }
+
+    let container_header = read_at::<ContainerHeader<O>>(data, root_node_offset as usize).ok_or(
+      OpenError::RootNodeOutOfBounds {
         size: data.len(),
         offset: root_node_offset,
         backtrace: Backtrace::generate(),
-      })?;
-    let container_header = ContainerHeader::<O>::read_from_bytes(container_header).unwrap();
+      },
+    )?;
 
     let data_type =
       DataType::from_u8(container_header.data_type).ok_or(OpenError::InvalidDataType {
@@ -174,6 +355,8 @@ impl<'a, O: ByteOrder> BymlReader<'a, O> {
           hash_key_table,
           data_types,
           values,
+          depth: 1,
+          max_depth,
           _p: PhantomData,
         }))
       }
@@ -191,6 +374,8 @@ impl<'a, O: ByteOrder> BymlReader<'a, O> {
           string_table,
           hash_key_table,
           entries,
+          depth: 1,
+          max_depth,
           _p: PhantomData,
         }))
       }
@@ -201,6 +386,38 @@ impl<'a, O: ByteOrder> BymlReader<'a, O> {
     }
   }
 
+  /// The number of elements in the root container, zero for an empty document.
+  pub fn len(&self) -> usize {
+    match self {
+      BymlReader::Array(array) => array.len(),
+      BymlReader::Dictionary(dict) => dict.len(),
+      BymlReader::Empty => 0,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// The end of the furthest byte the document references, found by walking every container.
+  /// Anything in the buffer past this is trailing data; a shorter buffer would have failed to
+  /// open or fails when reading elements.
+  ///
+  /// A document without a root only accounts for its header.
+  pub fn consumed_len(&self) -> usize {
+    let data = match self {
+      BymlReader::Array(array) => array.data,
+      BymlReader::Dictionary(dict) => dict.data,
+      BymlReader::Empty => return size_of::<Header<O>>(),
+    };
+
+    annotate::<O>(data)
+      .iter()
+      .map(|annotation| annotation.range.end)
+      .max()
+      .unwrap_or_default()
+  }
+
   pub fn unwrap_array(self) -> BymlReaderArray<'a, O> {
     let BymlReader::Array(array) = self else {
       panic!("unwrapped a non array type")
@@ -218,6 +435,93 @@ impl<'a, O: ByteOrder> BymlReader<'a, O> {
     }
 }
 
+impl<'a, O: ByteOrder> TryFrom<&'a [u8]> for BymlReader<'a, O> {
+  type Error = OpenError;
+
+  fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+    Self::new(data)
+  }
+}
+
+/// The name [`BymlReader::open_auto`] returns [`BymlDocument`] under.
+pub type AnyEndianReader<'a> = BymlDocument<'a>;
+
+/// Stands in for the byte order of a [`BymlReader`] that hasn't been opened yet, so that
+/// [`BymlReader::open_auto`] can be called without naming one.
+pub enum DetectEndian {}
+
+impl<'a> BymlReader<'a, DetectEndian> {
+  /// Opens `data` in the byte order its magic names, see [`detect_order`].
+  pub fn open_auto(data: &'a [u8]) -> Result<AnyEndianReader<'a>, OpenError> {
+    BymlDocument::new(data)
+  }
+}
+
+/// Picks the byte order from the document's magic, `YB` for little endian and `BY` for big endian.
+pub fn detect_order(data: &[u8]) -> Result<Order, OpenError> {
+  let actual = *data
+    .first_chunk()
+    .ok_or(OpenError::NotEnoughDataForHeader {
+      size: data.len(),
+      offset: 0,
+      backtrace: Backtrace::generate(),
+    })?;
+
+  match actual {
+    magic::BYML_LITTLE_ENDIAN => Ok(Order::LittleEndian),
+    magic::BYML_BIG_ENDIAN => Ok(Order::BigEndian),
+    _ => Err(OpenError::IncorrectMagic {
+      actual,
+      backtrace: Backtrace::generate(),
+    }),
+  }
+}
+
+/// A [`BymlReader`] whose byte order is picked from the document's magic,
+/// for code that shouldn't have to be generic over it.
+pub enum BymlDocument<'a> {
+  LittleEndian(BymlReader<'a, LittleEndian>),
+  BigEndian(BymlReader<'a, BigEndian>),
+}
+
+impl<'a> BymlDocument<'a> {
+  pub fn new(data: &'a [u8]) -> Result<Self, OpenError> {
+    match detect_order(data)? {
+      Order::LittleEndian => BymlReader::new(data).map(Self::LittleEndian),
+      Order::BigEndian => BymlReader::new(data).map(Self::BigEndian),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    match self {
+      Self::LittleEndian(reader) => reader.len(),
+      Self::BigEndian(reader) => reader.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    match self {
+      Self::LittleEndian(reader) => reader.is_empty(),
+      Self::BigEndian(reader) => reader.is_empty(),
+    }
+  }
+
+  pub fn consumed_len(&self) -> usize {
+    match self {
+      Self::LittleEndian(reader) => reader.consumed_len(),
+      Self::BigEndian(reader) => reader.consumed_len(),
+    }
+  }
+}
+
+impl<'a> TryFrom<&'a [u8]> for BymlDocument<'a> {
+  type Error = OpenError;
+
+  fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+    Self::new(data)
+  }
+}
+
 macro_rules! getter_impls {
   (
     [$ty: ty, $param: ident: $param_ty: ty]
@@ -241,12 +545,97 @@ macro_rules! getter_impls {
   };
 }
 
+/// Reads a `T` from `data` at `offset`, `None` if it doesn't fit.
+fn read_at<T: FromBytes>(data: &[u8], offset: usize) -> Option<T> {
+  let (value, _) = T::read_from_prefix(data.get(offset..)?).ok()?;
+  Some(value)
+}
+
+/// Borrows `count` `T`s from `data` at `offset`, `None` if they don't fit.
+fn slice_at<T: FromBytes + Immutable>(data: &[u8], offset: usize, count: usize) -> Option<&[T]> {
+  let (slice, _) = <[T]>::ref_from_prefix_with_elems(data.get(offset..)?, count).ok()?;
+  Some(slice)
+}
+
+/// Reads the `T` an element's value points to.
+fn read_pointee<T: FromBytes>(data: &[u8], offset: u32) -> Result<T, ElementReadError> {
+  read_at(data, offset as usize).ok_or(ElementReadError::ValueOutOfBounds {
+    size: data.len(),
+    offset,
+    backtrace: Backtrace::generate(),
+  })
+}
+
+/// Reads the length prefixed bytes a binary element's value points to.
+fn read_binary<O: ByteOrder>(data: &[u8], offset: u32) -> Result<&[u8], ElementReadError> {
+  let length = read_pointee::<U32<O>>(data, offset)?.get();
+  data
+    .get(offset as usize + size_of::<u32>()..)
+    .and_then(|bytes| bytes.get(..length as usize))
+    .ok_or(ElementReadError::BinaryOutOfBounds {
+      size: data.len(),
+      offset,
+      length,
+      backtrace: Backtrace::generate(),
+    })
+}
+
+/// Checks that a followed pointer lands on the container its element claims, rather than on a
+/// string table or whatever else the offset happens to point into.
+fn check_container_type<O: ByteOrder>(
+  header: &ContainerHeader<O>,
+  expected: DataType,
+) -> Result<(), ElementReadError> {
+  match DataType::from_u8(header.data_type) {
+    Some(actual) if actual == expected => Ok(()),
+    Some(actual) => Err(ElementReadError::UnexpectedDataType {
+      expected,
+      actual,
+      backtrace: Backtrace::generate(),
+    }),
+    None => Err(ElementReadError::InvalidDataType {
+      value: header.data_type,
+      backtrace: Backtrace::generate(),
+    }),
+  }
+}
+
+/// Checks that the container at `offset` can be nested in the one at `parent`, which is `depth`
+/// containers deep, and returns the nested container's depth. Runs after
+/// [`check_container_type`], so a pointer to the wrong kind of container is reported as such
+/// even when it points back at its parent.
+fn check_nesting(
+  parent: u32,
+  depth: u32,
+  max_depth: u32,
+  offset: u32,
+) -> Result<u32, ElementReadError> {
+  // the depth limit catches longer cycles, this one is just reported sooner
+  if offset == parent {
+    return Err(ElementReadError::CyclicContainer {
+      offset,
+      backtrace: Backtrace::generate(),
+    });
+  }
+  if depth >= max_depth {
+    return Err(ElementReadError::MaxDepthExceeded {
+      depth: max_depth,
+      backtrace: Backtrace::generate(),
+    });
+  }
+
+  Ok(depth + 1)
+}
+
+#[derive(Clone)]
 pub struct BymlReaderArray<'a, O> {
   data: &'a [u8],
   string_table: Option<StringTable<'a, O>>,
   hash_key_table: Option<StringTable<'a, O>>,
   data_types: &'a [DataType],
   values: &'a [U32<O>],
+  depth: u32,
+  max_depth: u32,
   _p: PhantomData<O>,
 }
 
@@ -256,16 +645,18 @@ impl<'a, O: ByteOrder> BymlReaderArray<'a, O> {
     entries: u32,
     start: usize,
   ) -> Result<(&[DataType], &[U32<O>]), ContainerError> {
-    let entries_end = start + 4 + entries as usize;
+    trace::trace!(offset = start, entries, "following array");
+    let out_of_bounds = || ContainerError::DataTypesOutOfBounds {
+      size: data.len(),
+      offset: (start as u32).saturating_add(4),
+      backtrace: Backtrace::generate(),
+    };
+    let (types_start, types_end) = start
+      .checked_add(size_of::<ContainerHeader<O>>())
+      .and_then(|types_start| Some((types_start, types_start.checked_add(entries as usize)?)))
+      .ok_or_else(out_of_bounds)?;
 
-    let data_types =
-      data
-        .get(start + 4..entries_end)
-        .ok_or(ContainerError::DataTypesOutOfBounds {
-          size: data.len(),
-          offset: start as u32 + 4,
-          backtrace: Backtrace::generate(),
-        })?;
+    let data_types = data.get(types_start..types_end).ok_or_else(out_of_bounds)?;
 
     data_types.iter().enumerate().try_for_each(
       |(index, data_type)| -> Result<(), ContainerError> {
@@ -280,44 +671,31 @@ impl<'a, O: ByteOrder> BymlReaderArray<'a, O> {
     )?;
 
     let data_types =
-      <[DataType]>::try_ref_from_bytes_with_elems(data_types, entries as usize).unwrap();
+      <[DataType]>::try_ref_from_bytes(data_types).expect("every data type was checked above");
 
-    let values_start = align_up(entries_end, 4);
+    // the types are in bounds, so the padding after them can't overflow
+    let values_start = align_up(types_end, 4);
 
-    let values = data
-      .get(values_start..values_start + entries as usize * 4)
-      .ok_or(ContainerError::ValuesOutOfBounds {
+    let values = slice_at::<U32<O>>(data, values_start, entries as usize).ok_or(
+      ContainerError::ValuesOutOfBounds {
         size: data.len(),
         offset: values_start as u32,
         backtrace: Backtrace::generate(),
-      })?;
-
-    let values = <[U32<O>]>::ref_from_bytes_with_elems(values, entries as usize).unwrap();
+      },
+    )?;
 
     Ok((data_types, values))
   }
 
-  pub fn get_element(
-    &'a self,
-    index: u32,
-  ) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
+  /// Returns the element at `index` without checking its type, `None` if it is out of range;
+  /// the typed getters build on this.
+  pub fn get_element(&self, index: u32) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
     let Some(data_type) = self.data_types.get(index as usize) else {
       return Ok(None);
     };
 
     let value = self.values.get(index as usize).unwrap().get();
 
-    let read_from_pointer = |size: usize| -> Result<&[u8], ElementReadError> {
-      self
-        .data
-        .get(value as usize..(value as usize + size))
-        .ok_or(ElementReadError::ValueOutOfBounds {
-          size: self.data.len(),
-          offset: value,
-          backtrace: Backtrace::generate(),
-        })
-    };
-
     match data_type {
       DataType::String => {
         let string = self
@@ -335,12 +713,14 @@ impl<'a, O: ByteOrder> BymlReaderArray<'a, O> {
         Ok(Some(BymlReaderNode::<O>::String(string)))
       }
       DataType::Array => {
-        let container_header =
-          ContainerHeader::<O>::read_from_bytes(read_from_pointer(4)?).unwrap();
+        let container_header = read_pointee::<ContainerHeader<O>>(self.data, value)?;
+        check_container_type(&container_header, DataType::Array)?;
+        let depth = check_nesting(self.offset(), self.depth, self.max_depth, value)?;
 
         let (data_types, values) =
           BymlReaderArray::get_components(self.data, container_header.entries(), value as usize)
-            .map_err(|source| ElementReadError::Container { source })?;
+            .map_err(|source| ElementReadError::Container { source })
+            .trace_error()?;
 
         Ok(Some(BymlReaderNode::Array(BymlReaderArray {
           data: self.data,
@@ -348,12 +728,15 @@ impl<'a, O: ByteOrder> BymlReaderArray<'a, O> {
           hash_key_table: self.hash_key_table,
           data_types,
           values,
+          depth,
+          max_depth: self.max_depth,
           _p: PhantomData,
         })))
       }
       DataType::Dictionary => {
-        let container_header =
-          ContainerHeader::<O>::read_from_bytes(read_from_pointer(4)?).unwrap();
+        let container_header = read_pointee::<ContainerHeader<O>>(self.data, value)?;
+        check_container_type(&container_header, DataType::Dictionary)?;
+        let depth = check_nesting(self.offset(), self.depth, self.max_depth, value)?;
 
         let (entries, hash_key_table) = BymlReaderDict::<O>::get_components(
           self.data,
@@ -361,17 +744,22 @@ impl<'a, O: ByteOrder> BymlReaderArray<'a, O> {
           value as usize,
           self.hash_key_table.as_ref(),
         )
-        .map_err(|source| ElementReadError::Container { source })?;
+        .map_err(|source| ElementReadError::Container { source })
+        .trace_error()?;
 
         Ok(Some(BymlReaderNode::Dictionary(BymlReaderDict {
           data: self.data,
           string_table: self.string_table,
           hash_key_table: hash_key_table,
           entries,
+          depth,
+          max_depth: self.max_depth,
           _p: PhantomData,
         })))
       }
-      DataType::StringTable => Err(ElementReadError::UnexpectedStringTable),
+      DataType::StringTable => Err(ElementReadError::UnexpectedStringTable {
+        backtrace: Backtrace::generate(),
+      }),
       DataType::Bool => Ok(Some(BymlReaderNode::<O>::Bool(value > 0))),
       DataType::I32 => Ok(Some(BymlReaderNode::<O>::I32(i32::from_ne_bytes(
         value.to_ne_bytes(),
@@ -383,30 +771,112 @@ impl<'a, O: ByteOrder> BymlReaderArray<'a, O> {
         value.to_ne_bytes(),
       )))),
       DataType::I64 => {
-        let value = read_from_pointer(8)?;
-        let value = I64::<O>::read_from_bytes(value).unwrap();
+        let value = read_pointee::<I64<O>>(self.data, value)?;
 
         Ok(Some(BymlReaderNode::<O>::I64(value.get())))
       }
       DataType::U64 => {
-        let value = read_from_pointer(8)?;
-        let value = U64::<O>::read_from_bytes(value).unwrap();
+        let value = read_pointee::<U64<O>>(self.data, value)?;
 
         Ok(Some(BymlReaderNode::<O>::U64(value.get())))
       }
       DataType::F64 => {
-        let value = read_from_pointer(8)?;
-        let value = F64::<O>::read_from_bytes(value).unwrap();
+        let value = read_pointee::<F64<O>>(self.data, value)?;
 
         Ok(Some(BymlReaderNode::<O>::F64(value.get())))
       }
+      DataType::Binary => Ok(Some(BymlReaderNode::Binary(read_binary::<O>(
+        self.data, value,
+      )?))),
       DataType::Null => Ok(Some(BymlReaderNode::Null)),
     }
   }
 
-  pub fn values(&'_ self) -> impl Iterator<Item = Result<BymlReaderNode<'_, O>, ElementReadError>> {
+  /// Like [`Self::get_element`], indexed with a `usize` like a slice. Indices past `u32::MAX`
+  /// can't exist in an array, so they read as `None`.
+  pub fn get(&'a self, index: usize) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
+    match u32::try_from(index) {
+      Ok(index) => self.get_element(index),
+      Err(_) => Ok(None),
+    }
+  }
+
+  /// Returns the element at `index`, for scripts and tests that know the document is valid.
+  ///
+  /// This stands in for `array[index]`, which [`std::ops::Index`] can't provide since nodes are
+  /// read by value. Production code should use [`Self::get_element`] instead.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of range or the element fails to read.
+  #[track_caller]
+  pub fn at(&self, index: usize) -> BymlReaderNode<'a, O> {
+    let element = u32::try_from(index).map_or(Ok(None), |index| self.get_element(index));
+    match element {
+      Ok(Some(node)) => node,
+      Ok(None) => panic!(
+        "index out of bounds: the len is {} but the index is {index}",
+        self.len()
+      ),
+      Err(error) => panic!("failed to read element {index}: {error}"),
+    }
+  }
+
+  /// Returns the first element, `None` if the array is empty.
+  pub fn first(&'a self) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
+    self.get(0)
+  }
+
+  /// Returns the last element, `None` if the array is empty.
+  pub fn last(&'a self) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
+    match self.len().checked_sub(1) {
+      Some(index) => self.get(index),
+      None => Ok(None),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.data_types.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data_types.is_empty()
+  }
+
+  /// Lazily reads each element in order. The iterator knows its length up front, so callers
+  /// can refuse arrays larger than they're willing to walk before decoding anything.
+  pub fn values(
+    &'_ self,
+  ) -> impl ExactSizeIterator<Item = Result<BymlReaderNode<'_, O>, ElementReadError>> {
     (0..self.data_types.len()).map(|index| self.get_element(index as u32).transpose().unwrap())
   }
+
+  /// Returns a [`Debug`] view that prints every element beneath this array.
+  /// The array's own [`Debug`] only prints its length, as decoding a large document can be costly.
+  pub fn pretty_print(&self) -> PrettyPrint<'_, Self> {
+    PrettyPrint(self, None)
+  }
+
+  /// Like [`Self::pretty_print`], but only descends `max_depth` containers deep, this array
+  /// included. Containers past that are printed as `...`.
+  pub fn pretty_print_limited(&self, max_depth: usize) -> PrettyPrint<'_, Self> {
+    PrettyPrint(self, Some(max_depth))
+  }
+
+  /// Walks this array and everything beneath it in document order, see [`BymlVisitor`].
+  ///
+  /// The walk keeps its own stack instead of recursing, so arbitrarily deep documents can't
+  /// overflow the call stack, though they still have to be opened with
+  /// [`BymlReader::with_max_depth`]. It stops at the first element that fails to read.
+  pub fn visit(&self, visitor: &mut impl BymlVisitor<'a, O>) -> Result<(), ElementReadError> {
+    visit::walk(BymlReaderNode::Array(self.clone()), visitor)
+  }
+
+  /// Where the array's header starts.
+  pub(crate) fn offset(&self) -> u32 {
+    (self.data_types.as_ptr() as usize - self.data.as_ptr() as usize) as u32
+      - size_of::<ContainerHeader<O>>() as u32
+  }
 }
 
 getter_impls! {
@@ -420,20 +890,38 @@ getter_impls! {
   (get_i64, i64, I64),
   (get_u64, u64, U64),
   (get_f64, f64, F64),
-  (get_cstring, &'a CStr, String)
+  (get_cstring, &'a CStr, String),
+  (get_binary, &'a [u8], Binary)
 }
 
 impl<'a, O: ByteOrder> Debug for BymlReaderArray<'a, O> {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-    self.values().collect::<Result<Vec<_>, _>>().fmt(f)
+    f.debug_struct("BymlReaderArray")
+      .field("len", &self.len())
+      .finish()
+  }
+}
+
+/// Compares element by element, so equal arrays stored at different offsets, or in different
+/// documents, are equal. See [`BymlReaderNode`]'s implementation for how elements compare.
+impl<'a, 'b, O: ByteOrder> PartialEq<BymlReaderArray<'b, O>> for BymlReaderArray<'a, O> {
+  fn eq(&self, other: &BymlReaderArray<'b, O>) -> bool {
+    self.len() == other.len()
+      && self
+        .values()
+        .zip(other.values())
+        .all(|pair| matches!(pair, (Ok(value), Ok(other)) if value == other))
   }
 }
 
+#[derive(Clone)]
 pub struct BymlReaderDict<'a, O> {
   data: &'a [u8],
   string_table: Option<StringTable<'a, O>>,
   hash_key_table: StringTable<'a, O>,
   entries: &'a [DictEntry<O>],
+  depth: u32,
+  max_depth: u32,
   _p: PhantomData<O>,
 }
 
@@ -444,73 +932,160 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
     start: usize,
     hash_key_table: Option<&StringTable<'a, O>>,
   ) -> Result<(&'a [DictEntry<O>], StringTable<'a, O>), ContainerError> {
-    let Some(hash_key_table) = hash_key_table else {
-      return Err(ContainerError::NoHashKeyTable {
-        backtrace: Backtrace::generate(),
-      });
-    };
-
-    let entries_end = start + 4 + entries as usize * size_of::<DictEntry<O>>();
-
-    let dict_entries =
-      data
-        .get(start + 4..entries_end)
-        .ok_or(ContainerError::DataTypesOutOfBounds {
-          size: data.len(),
-          offset: start as u32 + 4,
-          backtrace: Backtrace::generate(),
-        })?;
-
-    let try_dict_entries =
-      <[TryDictEntry<O>]>::ref_from_bytes_with_elems(dict_entries, entries as usize).unwrap();
-
-    try_dict_entries.iter().enumerate().try_for_each(
-      |(index, entry)| -> Result<(), ContainerError> {
-        DataType::from_u8(entry.data_type).ok_or(ContainerError::InvalidElementDataType {
-          element_index: index,
-          value: entry.data_type,
+    trace::trace!(offset = start, entries, "following dictionary");
+    // the writer leaves out the hash key table when there are no keys, so empty dictionaries
+    // have to be readable without one
+    let hash_key_table = match hash_key_table {
+      Some(hash_key_table) => *hash_key_table,
+      None if entries == 0 => StringTable {
+        offset_table: &[],
+        start_offset: 0,
+        string_data: &[],
+      },
+      None => {
+        return Err(ContainerError::NoHashKeyTable {
           backtrace: Backtrace::generate(),
-        })?;
+        });
+      }
+    };
 
-        Ok(())
+    let try_dict_entries = start
+      .checked_add(size_of::<ContainerHeader<O>>())
+      .and_then(|entries_start| slice_at::<TryDictEntry<O>>(data, entries_start, entries as usize))
+      .ok_or(ContainerError::DataTypesOutOfBounds {
+        size: data.len(),
+        offset: (start as u32).saturating_add(4),
+        backtrace: Backtrace::generate(),
+      })?;
+    let dict_entries = TryDictEntry::validate_all(try_dict_entries).map_err(
+      |(element_index, InvalidDataType(value))| ContainerError::InvalidElementDataType {
+        element_index,
+        value,
+        backtrace: Backtrace::generate(),
       },
     )?;
 
-    let dict_entries =
-      <[DictEntry<O>]>::try_ref_from_bytes_with_elems(dict_entries, entries as usize).unwrap();
-
-    Ok((dict_entries, *hash_key_table))
+    Ok((dict_entries, hash_key_table))
   }
 
+  /// Returns the element for `key` without checking its type, `None` if the key is absent; the
+  /// typed getters build on this.
+  ///
+  /// Keys should be unique, but some files repeat them. For those it is unspecified which of
+  /// the duplicates is returned; [`Self::entries`] yields all of them.
   pub fn get_element(
     &'a self,
-    index: &str,
+    key: &str,
   ) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
-    self.get_element_by_key_bytes(index.as_bytes())
+    self.get_by_bytes(key.as_bytes())
   }
 
-  pub fn get_element_by_key_bytes(
+  /// Returns the element for `key`, for scripts and tests that know the document is valid.
+  ///
+  /// This stands in for `dict[key]`, which [`std::ops::Index`] can't provide since nodes are
+  /// read by value. Production code should use [`Self::get_element`] instead.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `key` is absent or its element fails to read.
+  #[track_caller]
+  pub fn at(&'a self, key: &str) -> BymlReaderNode<'a, O> {
+    match self.get_element(key) {
+      Ok(Some(node)) => node,
+      Ok(None) => panic!("no entry found for key {key:?}"),
+      Err(error) => panic!("failed to read element {key:?}: {error}"),
+    }
+  }
+
+  /// Like [`Self::get_element`], but resolves the key through `cache`.
+  ///
+  /// Entries are then found by their hash key index, without reading any keys.
+  pub fn get_with_cache(
+    &self,
+    cache: &mut KeyCache<'a, O>,
+    key: &str,
+  ) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
+    let index = cache
+      .resolve(&self.hash_key_table, key)
+      .map_err(|source| ElementReadError::HashKeyReadError {
+        source,
+        backtrace: Backtrace::generate(),
+      })?;
+    let Some(index) = index else {
+      return Ok(None);
+    };
+    let Ok(position) = self
+      .entries
+      .binary_search_by_key(&index, |entry| entry.hash_key_index())
+    else {
+      return Ok(None);
+    };
+
+    let entry = &self.entries[position];
+    self.get_element_from_entry(entry.value.get(), entry.data_type)
+  }
+
+  /// Like [`Self::get_element`], for keys that aren't valid UTF-8. Keys are compared byte by byte,
+  /// the same order the hash key table is sorted in.
+  pub fn get_by_bytes(
     &'a self,
-    index: &[u8],
+    key: &[u8],
   ) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
-    let Some((value, data_type)) = self.get_entry_by_key_bytes(index)? else {
+    let Some((value, data_type)) = self.get_entry_by_key_bytes(key)? else {
       return Ok(None);
     };
 
     self.get_element_from_entry(value, data_type)
   }
 
+  #[deprecated(note = "renamed to `get_by_bytes`")]
+  pub fn get_element_by_key_bytes(
+    &'a self,
+    key: &[u8],
+  ) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
+    self.get_by_bytes(key)
+  }
+
+  /// Follows a `/` separated path of keys down from this dictionary, such as
+  /// `"UnitConfig/DisplayScale/X"`. Segments that meet an array are parsed as indices.
+  ///
+  /// Returns `None` if a key or index is absent, or the path continues past a value that isn't
+  /// a container.
+  pub fn get_by_path(&self, path: &str) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
+    let mut node = BymlReaderNode::Dictionary(self.clone());
+    for segment in path.split('/') {
+      let next = match &node {
+        BymlReaderNode::Dictionary(dict) => {
+          match dict.get_entry_by_key_bytes(segment.as_bytes())? {
+            Some((value, data_type)) => dict.get_element_from_entry(value, data_type)?,
+            None => None,
+          }
+        }
+        BymlReaderNode::Array(array) => match segment.parse() {
+          Ok(index) => array.get_element(index)?,
+          Err(_) => None,
+        },
+        _ => None,
+      };
+      let Some(next) = next else {
+        return Ok(None);
+      };
+      node = next;
+    }
+
+    Ok(Some(node))
+  }
+
   fn get_entry_by_key_bytes(
     &self,
-    index: &[u8],
+    key: &[u8],
   ) -> Result<Option<(u32, DataType)>, ElementReadError> {
     // try_binary_search_by doesn't exist, unfortunately
     let mut low = 0;
-    let mut high = self.entries.len() - 1;
-    let mut found_entry = None;
+    let mut high = self.entries.len();
 
-    while low <= high {
-      let mid = (low + high) / 2;
+    while low < high {
+      let mid = low + (high - low) / 2;
       let entry = &self.entries[mid];
       let value = self
         .hash_key_table
@@ -520,48 +1095,30 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
           backtrace: Backtrace::generate(),
         })?;
 
-      let ordering = index
-        .partial_cmp(&value.to_bytes())
-        .expect("invalid partial comparison between two byte ");
-      match ordering {
-        std::cmp::Ordering::Less => {
-          high = if let Some(new_high) = mid.checked_sub(1) {
-            new_high
-          } else {
-            return Ok(None);
-          }
-        }
-        std::cmp::Ordering::Equal => {
-          found_entry = Some(entry);
-          break;
-        }
-        std::cmp::Ordering::Greater => low = mid + 1,
+      match value.to_bytes().cmp(key) {
+        std::cmp::Ordering::Less => low = mid + 1,
+        std::cmp::Ordering::Equal => return Ok(Some((entry.value.get(), entry.data_type))),
+        std::cmp::Ordering::Greater => high = mid,
       }
     }
 
-    Ok(found_entry.map(
-      |DictEntry {
-         data_type, value, ..
-       }| (value.get(), *data_type),
-    ))
+    Ok(None)
+  }
+
+  fn get_element_at(&self, index: usize) -> Result<BymlReaderNode<'a, O>, ElementReadError> {
+    let entry = &self.entries[index];
+    Ok(
+      self
+        .get_element_from_entry(entry.value.get(), entry.data_type)?
+        .unwrap(),
+    )
   }
 
   fn get_element_from_entry(
-    &'_ self,
+    &self,
     value: u32,
     data_type: DataType,
-  ) -> Result<Option<BymlReaderNode<'_, O>>, ElementReadError> {
-    let read_from_pointer = |size: usize| -> Result<&[u8], ElementReadError> {
-      self
-        .data
-        .get(value as usize..(value as usize + size))
-        .ok_or(ElementReadError::ValueOutOfBounds {
-          size: self.data.len(),
-          offset: value,
-          backtrace: Backtrace::generate(),
-        })
-    };
-
+  ) -> Result<Option<BymlReaderNode<'a, O>>, ElementReadError> {
     match data_type {
       DataType::String => {
         let string = self
@@ -579,12 +1136,14 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
         Ok(Some(BymlReaderNode::<O>::String(string)))
       }
       DataType::Array => {
-        let container_header =
-          ContainerHeader::<O>::read_from_bytes(read_from_pointer(4)?).unwrap();
+        let container_header = read_pointee::<ContainerHeader<O>>(self.data, value)?;
+        check_container_type(&container_header, DataType::Array)?;
+        let depth = check_nesting(self.offset(), self.depth, self.max_depth, value)?;
 
         let (data_types, values) =
           BymlReaderArray::get_components(self.data, container_header.entries(), value as usize)
-            .map_err(|source| ElementReadError::Container { source })?;
+            .map_err(|source| ElementReadError::Container { source })
+            .trace_error()?;
 
         Ok(Some(BymlReaderNode::Array(BymlReaderArray {
           data: self.data,
@@ -592,12 +1151,15 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
           hash_key_table: Some(self.hash_key_table),
           data_types,
           values,
+          depth,
+          max_depth: self.max_depth,
           _p: PhantomData,
         })))
       }
       DataType::Dictionary => {
-        let container_header =
-          ContainerHeader::<O>::read_from_bytes(read_from_pointer(4)?).unwrap();
+        let container_header = read_pointee::<ContainerHeader<O>>(self.data, value)?;
+        check_container_type(&container_header, DataType::Dictionary)?;
+        let depth = check_nesting(self.offset(), self.depth, self.max_depth, value)?;
 
         let (entries, _) = BymlReaderDict::<O>::get_components(
           self.data,
@@ -605,17 +1167,22 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
           value as usize,
           Some(&self.hash_key_table),
         )
-        .map_err(|source| ElementReadError::Container { source })?;
+        .map_err(|source| ElementReadError::Container { source })
+        .trace_error()?;
 
         Ok(Some(BymlReaderNode::Dictionary(BymlReaderDict {
           data: self.data,
           string_table: self.string_table,
           hash_key_table: self.hash_key_table,
           entries,
+          depth,
+          max_depth: self.max_depth,
           _p: PhantomData,
         })))
       }
-      DataType::StringTable => Err(ElementReadError::UnexpectedStringTable),
+      DataType::StringTable => Err(ElementReadError::UnexpectedStringTable {
+        backtrace: Backtrace::generate(),
+      }),
       DataType::Bool => Ok(Some(BymlReaderNode::<O>::Bool(value > 0))),
       DataType::I32 => Ok(Some(BymlReaderNode::<O>::I32(i32::from_ne_bytes(
         value.to_ne_bytes(),
@@ -627,35 +1194,43 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
         value.to_ne_bytes(),
       )))),
       DataType::I64 => {
-        let value = read_from_pointer(8)?;
-        let value = I64::<O>::read_from_bytes(value).unwrap();
+        let value = read_pointee::<I64<O>>(self.data, value)?;
 
         Ok(Some(BymlReaderNode::<O>::I64(value.get())))
       }
       DataType::U64 => {
-        let value = read_from_pointer(8)?;
-        let value = U64::<O>::read_from_bytes(value).unwrap();
+        let value = read_pointee::<U64<O>>(self.data, value)?;
 
         Ok(Some(BymlReaderNode::<O>::U64(value.get())))
       }
       DataType::F64 => {
-        let value = read_from_pointer(8)?;
-        let value = F64::<O>::read_from_bytes(value).unwrap();
+        let value = read_pointee::<F64<O>>(self.data, value)?;
 
         Ok(Some(BymlReaderNode::<O>::F64(value.get())))
       }
+      DataType::Binary => Ok(Some(BymlReaderNode::Binary(read_binary::<O>(
+        self.data, value,
+      )?))),
       DataType::Null => Ok(Some(BymlReaderNode::Null)),
     }
   }
 
-  pub fn cstr_keys(&self) -> impl Iterator<Item = Result<&CStr, StringReadError>> {
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn cstr_keys(&self) -> impl ExactSizeIterator<Item = Result<&CStr, StringReadError>> {
     self
       .entries
       .iter()
       .map(|entry| self.hash_key_table.read_string(entry.hash_key_index()))
   }
 
-  pub fn keys(&self) -> impl Iterator<Item = Result<&str, StringReadError>> {
+  pub fn keys(&self) -> impl ExactSizeIterator<Item = Result<&str, StringReadError>> {
     self.entries.iter().map(|entry| {
       self
         .hash_key_table
@@ -663,32 +1238,42 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
         .and_then(|value| {
           value
             .to_str()
-            .map_err(|error| StringReadError::NonUtf8String { error })
+            .map_err(|error| StringReadError::NonUtf8String {
+              error,
+              backtrace: Backtrace::generate(),
+            })
         })
     })
   }
 
   pub fn cstr_entries(
     &self,
-  ) -> impl Iterator<Item = Result<(&CStr, BymlReaderNode<'_, O>), ElementReadError>> {
-    (0..self.entries.len()).map(|index| -> Result<_, ElementReadError> {
-      let string = self
-        .hash_key_table
-        .read_string(self.entries[index].hash_key_index())
-        .map_err(|source| ElementReadError::HashKeyReadError {
-          source,
-          backtrace: Backtrace::generate(),
-        })?;
-      Ok((
-        string,
-        self.get_element_by_key_bytes(string.to_bytes())?.unwrap(),
-      ))
-    })
+  ) -> impl ExactSizeIterator<Item = Result<(&CStr, BymlReaderNode<'_, O>), ElementReadError>> {
+    (0..self.entries.len()).map(|index| self.entry_at(index))
+  }
+
+  pub(crate) fn entry_at(
+    &self,
+    index: usize,
+  ) -> Result<(&'a CStr, BymlReaderNode<'a, O>), ElementReadError> {
+    let key = self
+      .hash_key_table
+      .read_string(self.entries[index].hash_key_index())
+      .map_err(|source| ElementReadError::HashKeyReadError {
+        source,
+        backtrace: Backtrace::generate(),
+      })?;
+    Ok((key, self.get_element_at(index)?))
   }
 
+  /// Lazily reads each entry in order. Like [`BymlReaderArray::values`], the length is known
+  /// before anything is decoded.
+  ///
+  /// Entries are read by position rather than looked up by key, so files that repeat a key
+  /// yield every copy with its own value.
   pub fn entries(
     &self,
-  ) -> impl Iterator<Item = Result<(&str, BymlReaderNode<'_, O>), ElementReadError>> {
+  ) -> impl ExactSizeIterator<Item = Result<(&str, BymlReaderNode<'_, O>), ElementReadError>> {
     (0..self.entries.len()).map(|index| -> Result<_, ElementReadError> {
       let string = self
         .hash_key_table
@@ -703,10 +1288,7 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
           source,
           backtrace: Backtrace::generate(),
         })?;
-      Ok((
-        string,
-        self.get_element_by_key_bytes(string.as_bytes())?.unwrap(),
-      ))
+      Ok((string, self.get_element_at(index)?))
     })
   }
 
@@ -724,11 +1306,46 @@ impl<'a, O: ByteOrder> BymlReaderDict<'a, O> {
       })
   }
 
+  /// Like [`Self::get_string`], but replaces invalid UTF-8 with U+FFFD rather than failing, for
+  /// displaying strings in other encodings such as Shift-JIS. Use [`Self::get_cstring`] to
+  /// decode them properly.
+  pub fn get_string_lossy(&'a self, key: &str) -> Result<Option<Cow<'a, str>>, ElementReadError> {
+    Ok(self.get_cstring(key)?.map(CStr::to_string_lossy))
+  }
+
+  /// Whether `key` is present, without reading its element.
+  pub fn contains_key(&self, key: &str) -> Result<bool, ElementReadError> {
+    Ok(self.get_entry_by_key_bytes(key.as_bytes())?.is_some())
+  }
+
   pub fn get_type(&self, key: &str) -> Result<Option<DataType>, ElementReadError> {
     self
       .get_entry_by_key_bytes(key.as_bytes())
       .map(|value| value.map(|(_, data_type)| data_type))
   }
+
+  /// Returns a [`Debug`] view that prints every entry beneath this dictionary.
+  /// The dictionary's own [`Debug`] only prints its length, as decoding a large document can be costly.
+  pub fn pretty_print(&self) -> PrettyPrint<'_, Self> {
+    PrettyPrint(self, None)
+  }
+
+  /// Like [`Self::pretty_print`], but only descends `max_depth` containers deep, this dictionary
+  /// included. Containers past that are printed as `...`.
+  pub fn pretty_print_limited(&self, max_depth: usize) -> PrettyPrint<'_, Self> {
+    PrettyPrint(self, Some(max_depth))
+  }
+
+  /// Like [`BymlReaderArray::visit`], walks this dictionary and everything beneath it.
+  pub fn visit(&self, visitor: &mut impl BymlVisitor<'a, O>) -> Result<(), ElementReadError> {
+    visit::walk(BymlReaderNode::Dictionary(self.clone()), visitor)
+  }
+
+  /// Where the dictionary's header starts.
+  pub(crate) fn offset(&self) -> u32 {
+    (self.entries.as_ptr() as usize - self.data.as_ptr() as usize) as u32
+      - size_of::<ContainerHeader<O>>() as u32
+  }
 }
 
 getter_impls! {
@@ -742,12 +1359,91 @@ getter_impls! {
   (get_i64, i64, I64),
   (get_u64, u64, U64),
   (get_f64, f64, F64),
-  (get_cstring, &'a CStr, String)
+  (get_cstring, &'a CStr, String),
+  (get_binary, &'a [u8], Binary)
 }
 
 impl<'a, O: ByteOrder> Debug for BymlReaderDict<'a, O> {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-    self.entries().collect::<Result<Vec<_>, _>>().fmt(f)
+    f.debug_struct("BymlReaderDict")
+      .field("len", &self.len())
+      .finish()
+  }
+}
+
+/// Compares entry by entry, keys included. Entries are sorted by key, so equal dictionaries
+/// always line up.
+impl<'a, 'b, O: ByteOrder> PartialEq<BymlReaderDict<'b, O>> for BymlReaderDict<'a, O> {
+  fn eq(&self, other: &BymlReaderDict<'b, O>) -> bool {
+    self.len() == other.len()
+      && self
+        .cstr_entries()
+        .zip(other.cstr_entries())
+        .all(|pair| match pair {
+          (Ok((key, value)), Ok((other_key, other))) => key == other_key && value == other,
+          _ => false,
+        })
+  }
+}
+
+/// Recursively prints a container and everything beneath it, see [`BymlReaderArray::pretty_print`].
+/// Elements that fail to read are printed as their error instead of aborting the whole print.
+// the second field is how many more containers deep to print, `None` for no limit
+pub struct PrettyPrint<'r, T>(&'r T, Option<usize>);
+
+impl<T> PrettyPrint<'_, T> {
+  fn children_depth(&self) -> Option<usize> {
+    self.1.map(|depth| depth - 1)
+  }
+}
+
+impl<'r, 'a, O: ByteOrder> Debug for PrettyPrint<'r, BymlReaderArray<'a, O>> {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    if self.1 == Some(0) {
+      return f.write_str("...");
+    }
+
+    let mut list = f.debug_list();
+    for value in self.0.values() {
+      match value {
+        Ok(node) => list.entry(&PrettyPrint(&node, self.children_depth())),
+        Err(error) => list.entry(&error),
+      };
+    }
+    list.finish()
+  }
+}
+
+impl<'r, 'a, O: ByteOrder> Debug for PrettyPrint<'r, BymlReaderDict<'a, O>> {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    if self.1 == Some(0) {
+      return f.write_str("...");
+    }
+
+    let mut map = f.debug_map();
+    for entry in self.0.entries() {
+      match entry {
+        Ok((key, node)) => map.entry(&key, &PrettyPrint(&node, self.children_depth())),
+        Err(error) => map.entry(&"<error>", &error),
+      };
+    }
+    map.finish()
+  }
+}
+
+impl<'r, 'a, O: ByteOrder> Debug for PrettyPrint<'r, BymlReaderNode<'a, O>> {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self.0 {
+      BymlReaderNode::Array(array) => f
+        .debug_tuple("Array")
+        .field(&PrettyPrint(array, self.1))
+        .finish(),
+      BymlReaderNode::Dictionary(dict) => f
+        .debug_tuple("Dictionary")
+        .field(&PrettyPrint(dict, self.1))
+        .finish(),
+      node => node.fmt(f),
+    }
   }
 }
 
@@ -763,6 +1459,7 @@ pub enum BymlReaderNode<'a, O: ByteOrder> {
   U64(u64),
   F64(f64),
   String(&'a CStr),
+  Binary(&'a [u8]),
   Null,
 }
 
@@ -779,7 +1476,62 @@ impl<'a, O: ByteOrder> BymlReaderNode<'a, O> {
       BymlReaderNode::U64(_) => DataType::U64,
       BymlReaderNode::F64(_) => DataType::F64,
       BymlReaderNode::String(_) => DataType::String,
+      BymlReaderNode::Binary(_) => DataType::Binary,
       BymlReaderNode::Null => DataType::Null,
     }
   }
+
+  /// Copies the node and everything under it into an owned [`BymlWriterNode`], ready to be
+  /// inserted into another document.
+  pub fn to_writer_node(&self) -> Result<BymlWriterNode, ElementReadError> {
+    Ok(match self {
+      BymlReaderNode::Array(array) => {
+        let mut writer = BymlWriterArray::new();
+        for element in array.values() {
+          writer.push(element?.to_writer_node()?);
+        }
+        BymlWriterNode::Array(Rc::new(writer))
+      }
+      BymlReaderNode::Dictionary(dict) => {
+        let mut writer = BymlWriterDict::new();
+        for entry in dict.cstr_entries() {
+          let (key, value) = entry?;
+          writer.insert(key.to_owned(), value.to_writer_node()?);
+        }
+        BymlWriterNode::Dictionary(Rc::new(writer))
+      }
+      BymlReaderNode::Bool(value) => BymlWriterNode::Bool(*value),
+      BymlReaderNode::I32(value) => BymlWriterNode::I32(*value),
+      BymlReaderNode::F32(value) => BymlWriterNode::F32(RawFloat(*value)),
+      BymlReaderNode::U32(value) => BymlWriterNode::U32(*value),
+      BymlReaderNode::I64(value) => BymlWriterNode::I64(*value),
+      BymlReaderNode::U64(value) => BymlWriterNode::U64(*value),
+      BymlReaderNode::F64(value) => BymlWriterNode::F64(RawFloat(*value)),
+      BymlReaderNode::String(value) => BymlWriterNode::String((*value).to_owned()),
+      BymlReaderNode::Binary(value) => BymlWriterNode::Binary(value.to_vec()),
+      BymlReaderNode::Null => BymlWriterNode::Null,
+    })
+  }
+}
+
+/// Compares values rather than where they're stored. Floats compare by their bits, the same way
+/// the writer deduplicates them, and an element that fails to read makes its containers unequal.
+impl<'a, 'b, O: ByteOrder> PartialEq<BymlReaderNode<'b, O>> for BymlReaderNode<'a, O> {
+  fn eq(&self, other: &BymlReaderNode<'b, O>) -> bool {
+    match (self, other) {
+      (BymlReaderNode::Array(a), BymlReaderNode::Array(b)) => a == b,
+      (BymlReaderNode::Dictionary(a), BymlReaderNode::Dictionary(b)) => a == b,
+      (BymlReaderNode::Bool(a), BymlReaderNode::Bool(b)) => a == b,
+      (BymlReaderNode::I32(a), BymlReaderNode::I32(b)) => a == b,
+      (BymlReaderNode::F32(a), BymlReaderNode::F32(b)) => a.to_bits() == b.to_bits(),
+      (BymlReaderNode::U32(a), BymlReaderNode::U32(b)) => a == b,
+      (BymlReaderNode::I64(a), BymlReaderNode::I64(b)) => a == b,
+      (BymlReaderNode::U64(a), BymlReaderNode::U64(b)) => a == b,
+      (BymlReaderNode::F64(a), BymlReaderNode::F64(b)) => a.to_bits() == b.to_bits(),
+      (BymlReaderNode::String(a), BymlReaderNode::String(b)) => a == b,
+      (BymlReaderNode::Binary(a), BymlReaderNode::Binary(b)) => a == b,
+      (BymlReaderNode::Null, BymlReaderNode::Null) => true,
+      _ => false,
+    }
+  }
 }
@@ -0,0 +1,589 @@
+use std::{ffi::CString, fmt::Display, rc::Rc};
+
+use serde::{
+  Serialize,
+  ser::{self, Impossible},
+};
+use snafu::{Backtrace, GenerateImplicitData, Snafu};
+
+use crate::byml::writer::{BymlWriter, BymlWriterArray, BymlWriterDict, BymlWriterNode};
+
+#[derive(Snafu, Debug)]
+pub enum SerializeError {
+  #[snafu(display("{message}"))]
+  Custom {
+    message: String,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("the root of a document must be a dictionary or an array, got {actual}"))]
+  InvalidRoot {
+    actual: &'static str,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("dictionary keys must be strings, got {actual}"))]
+  NonStringKey {
+    actual: &'static str,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("string {string:?} contains a nul byte"))]
+  InteriorNul {
+    string: String,
+    backtrace: Backtrace,
+  },
+}
+
+impl ser::Error for SerializeError {
+  fn custom<T: Display>(message: T) -> Self {
+    SerializeError::Custom {
+      message: message.to_string(),
+      backtrace: Backtrace::generate(),
+    }
+  }
+}
+
+/// Builds a document from `value`. Structs and maps become dictionaries and sequences become
+/// arrays, so the root has to be one of those.
+///
+/// Numbers keep the width of their Rust type: `i8` through `i32` are written as `I32`, unsigned
+/// integers up to `u32` as `U32`, and `i64`, `u64`, `f32` and `f64` as themselves.
+/// `None` and `()` are written as `Null`, byte arrays (through `serde_bytes`, for example) as
+/// `Binary`, and enums follow serde's externally tagged layout.
+pub fn to_writer<T: Serialize + ?Sized>(value: &T) -> Result<BymlWriter, SerializeError> {
+  match value.serialize(NodeSerializer)? {
+    BymlWriterNode::Array(array) => Ok(BymlWriter::from_array(array)),
+    BymlWriterNode::Dictionary(dict) => Ok(BymlWriter::from_dictionary(dict)),
+    node => Err(SerializeError::InvalidRoot {
+      actual: node_name(&node),
+      backtrace: Backtrace::generate(),
+    }),
+  }
+}
+
+fn node_name(node: &BymlWriterNode) -> &'static str {
+  match node {
+    BymlWriterNode::Array(_) => "an array",
+    BymlWriterNode::Dictionary(_) => "a dictionary",
+    BymlWriterNode::Bool(_) => "a bool",
+    BymlWriterNode::I32(_)
+    | BymlWriterNode::U32(_)
+    | BymlWriterNode::I64(_)
+    | BymlWriterNode::U64(_) => "an integer",
+    BymlWriterNode::F32(_) | BymlWriterNode::F64(_) => "a float",
+    BymlWriterNode::String(_) => "a string",
+    BymlWriterNode::Binary(_) => "a byte array",
+    BymlWriterNode::Null => "null",
+  }
+}
+
+fn cstring(string: &str) -> Result<CString, SerializeError> {
+  CString::new(string).map_err(|_| SerializeError::InteriorNul {
+    string: string.to_owned(),
+    backtrace: Backtrace::generate(),
+  })
+}
+
+/// Wraps `value` in a dictionary keyed by the variant's name.
+fn tagged(variant: &'static str, value: BymlWriterNode) -> Result<BymlWriterNode, SerializeError> {
+  let mut dict = BymlWriterDict::new();
+  dict.insert(cstring(variant)?, value);
+  Ok(BymlWriterNode::Dictionary(Rc::new(dict)))
+}
+
+struct NodeSerializer;
+
+impl ser::Serializer for NodeSerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  type SerializeSeq = ArraySerializer;
+  type SerializeTuple = ArraySerializer;
+  type SerializeTupleStruct = ArraySerializer;
+  type SerializeTupleVariant = ArraySerializer;
+  type SerializeMap = DictSerializer;
+  type SerializeStruct = DictSerializer;
+  type SerializeStructVariant = DictSerializer;
+
+  fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::Bool(v))
+  }
+
+  fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+    self.serialize_i32(v.into())
+  }
+
+  fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+    self.serialize_i32(v.into())
+  }
+
+  fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::I32(v))
+  }
+
+  fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::I64(v))
+  }
+
+  fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+    self.serialize_u32(v.into())
+  }
+
+  fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+    self.serialize_u32(v.into())
+  }
+
+  fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::U32(v))
+  }
+
+  fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::U64(v))
+  }
+
+  fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::F32(v.into()))
+  }
+
+  fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::F64(v.into()))
+  }
+
+  fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+    self.serialize_str(v.encode_utf8(&mut [0; 4]))
+  }
+
+  fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::String(cstring(v)?))
+  }
+
+  fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::Binary(v.to_vec()))
+  }
+
+  fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::Null)
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::Null)
+  }
+
+  fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+    Ok(BymlWriterNode::Null)
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+  ) -> Result<Self::Ok, Self::Error> {
+    self.serialize_str(variant)
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    value: &T,
+  ) -> Result<Self::Ok, Self::Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> Result<Self::Ok, Self::Error> {
+    tagged(variant, value.serialize(self)?)
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+    Ok(ArraySerializer::new(None, len))
+  }
+
+  fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+    Ok(ArraySerializer::new(None, Some(len)))
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    len: usize,
+  ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+    Ok(ArraySerializer::new(None, Some(len)))
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+    Ok(ArraySerializer::new(Some(variant), Some(len)))
+  }
+
+  fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+    Ok(DictSerializer::new(None))
+  }
+
+  fn serialize_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStruct, Self::Error> {
+    Ok(DictSerializer::new(None))
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStructVariant, Self::Error> {
+    Ok(DictSerializer::new(Some(variant)))
+  }
+}
+
+struct ArraySerializer {
+  variant: Option<&'static str>,
+  array: BymlWriterArray,
+}
+
+impl ArraySerializer {
+  fn new(variant: Option<&'static str>, len: Option<usize>) -> Self {
+    let mut array = BymlWriterArray::new();
+    array.reserve(len.unwrap_or_default());
+    Self { variant, array }
+  }
+
+  fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+    self.array.push(value.serialize(NodeSerializer)?);
+    Ok(())
+  }
+
+  fn finish(self) -> Result<BymlWriterNode, SerializeError> {
+    let array = BymlWriterNode::Array(Rc::new(self.array));
+    match self.variant {
+      Some(variant) => tagged(variant, array),
+      None => Ok(array),
+    }
+  }
+}
+
+impl ser::SerializeSeq for ArraySerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTuple for ArraySerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTupleStruct for ArraySerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTupleVariant for ArraySerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+struct DictSerializer {
+  variant: Option<&'static str>,
+  dict: BymlWriterDict,
+  key: Option<CString>,
+}
+
+impl DictSerializer {
+  fn new(variant: Option<&'static str>) -> Self {
+    Self {
+      variant,
+      dict: BymlWriterDict::new(),
+      key: None,
+    }
+  }
+
+  fn insert<T: Serialize + ?Sized>(
+    &mut self,
+    key: CString,
+    value: &T,
+  ) -> Result<(), SerializeError> {
+    self.dict.insert(key, value.serialize(NodeSerializer)?);
+    Ok(())
+  }
+
+  fn finish(self) -> Result<BymlWriterNode, SerializeError> {
+    let dict = BymlWriterNode::Dictionary(Rc::new(self.dict));
+    match self.variant {
+      Some(variant) => tagged(variant, dict),
+      None => Ok(dict),
+    }
+  }
+}
+
+impl ser::SerializeMap for DictSerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+    self.key = Some(key.serialize(KeySerializer)?);
+    Ok(())
+  }
+
+  fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    let key = self
+      .key
+      .take()
+      .expect("serialize_value was called before serialize_key");
+    self.insert(key, value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeStruct for DictSerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.insert(cstring(key)?, value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeStructVariant for DictSerializer {
+  type Ok = BymlWriterNode;
+  type Error = SerializeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.insert(cstring(key)?, value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+/// Accepts only strings (and unit variants, which are written as their name) as dictionary keys.
+struct KeySerializer;
+
+fn non_string_key<T>(actual: &'static str) -> Result<T, SerializeError> {
+  Err(SerializeError::NonStringKey {
+    actual,
+    backtrace: Backtrace::generate(),
+  })
+}
+
+impl ser::Serializer for KeySerializer {
+  type Ok = CString;
+  type Error = SerializeError;
+
+  type SerializeSeq = Impossible<CString, SerializeError>;
+  type SerializeTuple = Impossible<CString, SerializeError>;
+  type SerializeTupleStruct = Impossible<CString, SerializeError>;
+  type SerializeTupleVariant = Impossible<CString, SerializeError>;
+  type SerializeMap = Impossible<CString, SerializeError>;
+  type SerializeStruct = Impossible<CString, SerializeError>;
+  type SerializeStructVariant = Impossible<CString, SerializeError>;
+
+  fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+    cstring(v)
+  }
+
+  fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+    self.serialize_str(v.encode_utf8(&mut [0; 4]))
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+  ) -> Result<Self::Ok, Self::Error> {
+    self.serialize_str(variant)
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    value: &T,
+  ) -> Result<Self::Ok, Self::Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
+    non_string_key("a bool")
+  }
+
+  fn serialize_i8(self, _v: i8) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_i16(self, _v: i16) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_i32(self, _v: i32) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_i64(self, _v: i64) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_u8(self, _v: u8) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_u16(self, _v: u16) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_u32(self, _v: u32) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_u64(self, _v: u64) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an integer")
+  }
+
+  fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+    non_string_key("a float")
+  }
+
+  fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+    non_string_key("a float")
+  }
+
+  fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+    non_string_key("a byte array")
+  }
+
+  fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+    non_string_key("null")
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an option")
+  }
+
+  fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+    non_string_key("null")
+  }
+
+  fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+    non_string_key("null")
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _value: &T,
+  ) -> Result<Self::Ok, Self::Error> {
+    non_string_key("an enum variant")
+  }
+
+  fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+    non_string_key("an array")
+  }
+
+  fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+    non_string_key("an array")
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+    non_string_key("an array")
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+    non_string_key("an enum variant")
+  }
+
+  fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+    non_string_key("a dictionary")
+  }
+
+  fn serialize_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStruct, Self::Error> {
+    non_string_key("a dictionary")
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStructVariant, Self::Error> {
+    non_string_key("an enum variant")
+  }
+}
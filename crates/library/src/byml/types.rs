@@ -67,6 +67,44 @@ pub struct TryDictEntry<O> {
   pub value: U32<O>,
 }
 
+impl<O: ByteOrder> TryDictEntry<O> {
+  pub fn hash_key_index(&self) -> u32 {
+    match O::ORDER {
+      Order::BigEndian => {
+        let [a, b, c] = self.hash_key_index;
+        u32::from_be_bytes([0, a, b, c])
+      }
+      Order::LittleEndian => {
+        let [a, b, c] = self.hash_key_index;
+        u32::from_le_bytes([a, b, c, 0])
+      }
+    }
+  }
+}
+
+impl<O: ByteOrder> TryDictEntry<O> {
+  /// Reinterprets the entry as a [`DictEntry`] once its data type byte is known to be valid.
+  pub fn validate(&self) -> Result<&DictEntry<O>, InvalidDataType> {
+    DictEntry::try_ref_from_bytes(self.as_bytes()).map_err(|_| InvalidDataType(self.data_type))
+  }
+
+  /// Like [`Self::validate`] for every entry at once. Fails with the index of the first entry
+  /// whose data type is invalid.
+  pub fn validate_all(entries: &[Self]) -> Result<&[DictEntry<O>], (usize, InvalidDataType)> {
+    <[DictEntry<O>]>::try_ref_from_bytes(entries.as_bytes()).map_err(|_| {
+      entries
+        .iter()
+        .enumerate()
+        .find_map(|(index, entry)| entry.validate().err().map(|error| (index, error)))
+        .expect("dictionary entries only differ in their data type")
+    })
+  }
+}
+
+/// A data type byte that isn't a [`DataType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidDataType(pub u8);
+
 #[derive(TryFromBytes, IntoBytes, KnownLayout, Immutable)]
 #[repr(C)]
 pub struct DictEntry<O> {
@@ -112,11 +150,23 @@ impl<O: ByteOrder> DictEntry<O> {
 }
 
 #[derive(
-  Debug, FromPrimitive, TryFromBytes, IntoBytes, Unaligned, Immutable, PartialEq, Eq, Clone, Copy,
+  Debug,
+  FromPrimitive,
+  TryFromBytes,
+  IntoBytes,
+  Unaligned,
+  Immutable,
+  PartialEq,
+  Eq,
+  Hash,
+  Clone,
+  Copy,
 )]
 #[repr(u8)]
 pub enum DataType {
   String = 0xA0,
+  /// Bytes prefixed with their length, added in version 4.
+  Binary = 0xA1,
   Array = 0xC0,
   Dictionary = 0xC1,
   StringTable = 0xC2,
@@ -0,0 +1,130 @@
+use std::{collections::BTreeMap, ffi::CString, rc::Rc};
+
+use snafu::{Backtrace, GenerateImplicitData};
+use zerocopy::ByteOrder;
+
+use crate::byml::{
+  ElementReadError,
+  reader::BymlReaderNode,
+  writer::{BymlWriter, BymlWriterArray, BymlWriterDict, BymlWriterNode, RawFloat},
+};
+
+/// A node and everything beneath it, copied out of the document so that it outlives the data
+/// it was read from.
+///
+/// Keys and strings have to be valid UTF-8. Use [`BymlReaderNode::to_writer_node`] to copy
+/// documents in other encodings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BymlValue {
+  Dictionary(BTreeMap<String, BymlValue>),
+  Array(Vec<BymlValue>),
+  Bool(bool),
+  I32(i32),
+  F32(f32),
+  U32(u32),
+  I64(i64),
+  U64(u64),
+  F64(f64),
+  String(String),
+  Binary(Vec<u8>),
+  Null,
+}
+
+impl BymlValue {
+  /// Reads `node` and everything beneath it. A dictionary that repeats a key keeps the last
+  /// value for it.
+  pub fn from_reader<O: ByteOrder>(node: &BymlReaderNode<'_, O>) -> Result<Self, ElementReadError> {
+    Ok(match node {
+      BymlReaderNode::Array(array) => Self::Array(
+        array
+          .values()
+          .map(|element| Self::from_reader(&element?))
+          .collect::<Result<_, _>>()?,
+      ),
+      BymlReaderNode::Dictionary(dict) => {
+        let mut entries = BTreeMap::new();
+        for entry in dict.entries() {
+          let (key, value) = entry?;
+          entries.insert(key.to_owned(), Self::from_reader(&value)?);
+        }
+        Self::Dictionary(entries)
+      }
+      BymlReaderNode::Bool(value) => Self::Bool(*value),
+      BymlReaderNode::I32(value) => Self::I32(*value),
+      BymlReaderNode::F32(value) => Self::F32(*value),
+      BymlReaderNode::U32(value) => Self::U32(*value),
+      BymlReaderNode::I64(value) => Self::I64(*value),
+      BymlReaderNode::U64(value) => Self::U64(*value),
+      BymlReaderNode::F64(value) => Self::F64(*value),
+      BymlReaderNode::String(value) => {
+        let value = value
+          .to_str()
+          .map_err(|source| ElementReadError::NonUtf8String {
+            source,
+            backtrace: Backtrace::generate(),
+          })?;
+        Self::String(value.to_owned())
+      }
+      BymlReaderNode::Binary(value) => Self::Binary(value.to_vec()),
+      BymlReaderNode::Null => Self::Null,
+    })
+  }
+
+  /// Copies the value into a [`BymlWriterNode`], ready to be inserted into a document.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a key or string contains a nul byte, like the writer's `insert_*` methods.
+  pub fn to_writer_node(&self) -> BymlWriterNode {
+    match self {
+      Self::Array(values) => BymlWriterNode::Array(Rc::new(to_writer_array(values))),
+      Self::Dictionary(entries) => BymlWriterNode::Dictionary(Rc::new(to_writer_dict(entries))),
+      Self::Bool(value) => BymlWriterNode::Bool(*value),
+      Self::I32(value) => BymlWriterNode::I32(*value),
+      Self::F32(value) => BymlWriterNode::F32(RawFloat(*value)),
+      Self::U32(value) => BymlWriterNode::U32(*value),
+      Self::I64(value) => BymlWriterNode::I64(*value),
+      Self::U64(value) => BymlWriterNode::U64(*value),
+      Self::F64(value) => BymlWriterNode::F64(RawFloat(*value)),
+      Self::String(value) => BymlWriterNode::String(
+        CString::new(value.as_str()).expect("failed to convert value to cstring"),
+      ),
+      Self::Binary(value) => BymlWriterNode::Binary(value.clone()),
+      Self::Null => BymlWriterNode::Null,
+    }
+  }
+}
+
+fn to_writer_array(values: &[BymlValue]) -> BymlWriterArray {
+  let mut array = BymlWriterArray::new();
+  for value in values {
+    array.push(value.to_writer_node());
+  }
+  array
+}
+
+fn to_writer_dict(entries: &BTreeMap<String, BymlValue>) -> BymlWriterDict {
+  let mut dict = BymlWriterDict::new();
+  for (key, value) in entries {
+    dict.insert(
+      CString::new(key.as_str()).expect("failed to convert key to cstring"),
+      value.to_writer_node(),
+    );
+  }
+  dict
+}
+
+impl BymlWriter {
+  /// Builds a document with `value` as its root, `None` if it isn't an array or dictionary.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a key or string contains a nul byte.
+  pub fn from_value(value: &BymlValue) -> Option<Self> {
+    match value {
+      BymlValue::Array(values) => Some(Self::from_owned_array(to_writer_array(values))),
+      BymlValue::Dictionary(entries) => Some(Self::from_owned_dictionary(to_writer_dict(entries))),
+      _ => None,
+    }
+  }
+}
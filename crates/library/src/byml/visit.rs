@@ -0,0 +1,130 @@
+use std::{collections::HashSet, ffi::CStr};
+
+use snafu::{Backtrace, GenerateImplicitData};
+use zerocopy::ByteOrder;
+
+use crate::byml::{
+  ElementReadError,
+  reader::{BymlReaderArray, BymlReaderDict, BymlReaderNode},
+};
+
+/// Where a node sits in its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKey<'a> {
+  /// The container the walk started from.
+  Root,
+  Index(u32),
+  Key(&'a CStr),
+}
+
+/// Callbacks for [`BymlReaderArray::visit`] and [`BymlReaderDict::visit`], which call them in
+/// document order. Every callback does nothing by default.
+///
+/// Each `enter_*` call is matched by an `exit_*` call once the container's elements have been
+/// visited, so visitors can track the depth or path themselves.
+#[allow(unused_variables)]
+pub trait BymlVisitor<'a, O: ByteOrder> {
+  fn enter_array(&mut self, key: NodeKey<'a>, array: &BymlReaderArray<'a, O>) {}
+
+  fn exit_array(&mut self) {}
+
+  fn enter_dict(&mut self, key: NodeKey<'a>, dict: &BymlReaderDict<'a, O>) {}
+
+  fn exit_dict(&mut self) {}
+
+  /// Called for every node that isn't a container.
+  fn scalar(&mut self, key: NodeKey<'a>, node: &BymlReaderNode<'a, O>) {}
+}
+
+enum Frame<'a, O: ByteOrder> {
+  Array {
+    array: BymlReaderArray<'a, O>,
+    next: u32,
+  },
+  Dictionary {
+    dict: BymlReaderDict<'a, O>,
+    next: usize,
+  },
+}
+
+impl<O: ByteOrder> Frame<'_, O> {
+  fn offset(&self) -> u32 {
+    match self {
+      Frame::Array { array, .. } => array.offset(),
+      Frame::Dictionary { dict, .. } => dict.offset(),
+    }
+  }
+}
+
+pub(super) fn walk<'a, O: ByteOrder>(
+  root: BymlReaderNode<'a, O>,
+  visitor: &mut impl BymlVisitor<'a, O>,
+) -> Result<(), ElementReadError> {
+  let mut stack = Vec::new();
+  // the offsets of every container on the stack
+  let mut ancestors = HashSet::new();
+  enter(&mut stack, &mut ancestors, NodeKey::Root, root, visitor)?;
+
+  while let Some(frame) = stack.last_mut() {
+    let child = match frame {
+      Frame::Array { array, next } => array.get_element(*next)?.map(|node| {
+        let key = NodeKey::Index(*next);
+        *next += 1;
+        (key, node)
+      }),
+      Frame::Dictionary { dict, next } if *next < dict.len() => {
+        let (key, node) = dict.entry_at(*next)?;
+        *next += 1;
+        Some((NodeKey::Key(key), node))
+      }
+      Frame::Dictionary { .. } => None,
+    };
+
+    match child {
+      Some((key, node)) => enter(&mut stack, &mut ancestors, key, node, visitor)?,
+      None => {
+        let frame = stack.pop().unwrap();
+        ancestors.remove(&frame.offset());
+        match frame {
+          Frame::Array { .. } => visitor.exit_array(),
+          Frame::Dictionary { .. } => visitor.exit_dict(),
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+fn enter<'a, O: ByteOrder>(
+  stack: &mut Vec<Frame<'a, O>>,
+  ancestors: &mut HashSet<u32>,
+  key: NodeKey<'a>,
+  node: BymlReaderNode<'a, O>,
+  visitor: &mut impl BymlVisitor<'a, O>,
+) -> Result<(), ElementReadError> {
+  let frame = match node {
+    BymlReaderNode::Array(array) => Frame::Array { array, next: 0 },
+    BymlReaderNode::Dictionary(dict) => Frame::Dictionary { dict, next: 0 },
+    node => {
+      visitor.scalar(key, &node);
+      return Ok(());
+    }
+  };
+
+  // a container pointing back at one of its ancestors would otherwise be walked forever
+  let offset = frame.offset();
+  if !ancestors.insert(offset) {
+    return Err(ElementReadError::CyclicContainer {
+      offset,
+      backtrace: Backtrace::generate(),
+    });
+  }
+
+  match &frame {
+    Frame::Array { array, .. } => visitor.enter_array(key, array),
+    Frame::Dictionary { dict, .. } => visitor.enter_dict(key, dict),
+  }
+  stack.push(frame);
+  Ok(())
+}
@@ -1,25 +1,31 @@
 use std::{
-  collections::{BTreeMap, HashMap, HashSet},
-  ffi::CString,
-  hash::{BuildHasherDefault, DefaultHasher},
-  io::{self, Seek, SeekFrom, Write},
+  cmp::Ordering,
+  collections::{BTreeMap, HashMap, HashSet, btree_map::Entry},
+  ffi::{CString, NulError},
+  hash::{BuildHasherDefault, DefaultHasher, Hash, Hasher},
+  io::{self, Cursor, Seek, SeekFrom, Write},
   ops::{Deref, DerefMut},
   rc::Rc,
 };
 
 use either::Either;
-use ordered_float::OrderedFloat;
+use snafu::{Backtrace, GenerateImplicitData};
 use zerocopy::{ByteOrder, F64, I64, Immutable, IntoBytes, U16, U32, U64};
 
 use crate::{
   byml::{
+    InsertError, ValidationError,
     types::{ContainerHeader, DataType, DictEntry, Header},
     write_error::{Overflowed, WriteError},
   },
+  magic,
   util::align_up,
+  yaz0,
 };
 
-#[derive(Hash, PartialEq, Eq)]
+/// Elements are written in the order they were pushed in; nothing is reordered on write. Use
+/// [`Self::sort_by`] when a game expects an order derived from the elements themselves.
+#[derive(Clone, Hash, PartialEq, Eq)]
 pub struct BymlWriterArray {
   elements: Vec<BymlWriterNode>,
 }
@@ -31,22 +37,45 @@ impl BymlWriterArray {
     }
   }
 
+  /// # Panics
+  ///
+  /// Panics if `value` contains a nul byte, see [`Self::try_push_string`].
   pub fn push_string<A: AsRef<str>>(&mut self, value: A) {
-    self.elements.push(BymlWriterNode::String(
-      CString::new(value.as_ref()).expect("failed to convert value to cstring"),
-    ))
+    self
+      .try_push_string(value)
+      .expect("failed to convert value to cstring")
+  }
+
+  /// Pushes `value`, failing instead of panicking if it contains a nul byte.
+  pub fn try_push_string<A: AsRef<str>>(&mut self, value: A) -> Result<(), NulError> {
+    let value = CString::new(value.as_ref())?;
+    self.elements.push(BymlWriterNode::String(value));
+    Ok(())
   }
 
   pub fn push_null(&mut self) {
     self.elements.push(BymlWriterNode::Null);
   }
 
-  fn inline_size<O: ByteOrder>(&self) -> Option<u32> {
-    size_of::<ContainerHeader<O>>()
-      .checked_add(align_up(self.len(), 4))?
-      .checked_add(align_up(self.len() * 4, 4))?
-      .try_into()
-      .ok()
+  /// Stably sorts the elements with `compare`.
+  ///
+  /// Debug builds check that the result is actually ordered by `compare`, which catches
+  /// comparisons that aren't consistent, such as ones that treat unexpected variants as equal to
+  /// everything.
+  pub fn sort_by(&mut self, mut compare: impl FnMut(&BymlWriterNode, &BymlWriterNode) -> Ordering) {
+    self.elements.sort_by(&mut compare);
+    debug_assert!(
+      self
+        .elements
+        .windows(2)
+        .all(|pair| compare(&pair[0], &pair[1]) != Ordering::Greater),
+      "array elements aren't ordered by the comparison they were sorted with"
+    );
+  }
+
+  /// Stably sorts the elements by the key `key` extracts, see [`Self::sort_by`].
+  pub fn sort_by_key<K: Ord>(&mut self, mut key: impl FnMut(&BymlWriterNode) -> K) {
+    self.sort_by(|a, b| key(a).cmp(&key(b)));
   }
 }
 
@@ -72,7 +101,8 @@ array_push_impl! {
   (push_f32, f32, F32),
   (push_i64, i64, I64),
   (push_u64, u64, U64),
-  (push_f64, f64, F64)
+  (push_f64, f64, F64),
+  (push_binary, (impl Into<Vec<u8>>), Binary)
 }
 
 impl Deref for BymlWriterArray {
@@ -89,7 +119,12 @@ impl DerefMut for BymlWriterArray {
   }
 }
 
-#[derive(Hash, PartialEq, Eq)]
+/// Keys are unique, so inserting an existing key (through `insert_*` or the underlying map)
+/// replaces its value. Use [`Self::try_insert`] to catch collisions instead.
+///
+/// Entries are always written sorted by key, byte by byte, as readers binary search them;
+/// insertion order isn't kept.
+#[derive(Clone, Hash, PartialEq, Eq)]
 pub struct BymlWriterDict {
   entries: BTreeMap<CString, BymlWriterNode>,
 }
@@ -115,13 +150,50 @@ impl BymlWriterDict {
     }
   }
 
+  /// # Panics
+  ///
+  /// Panics if `key` or `value` contains a nul byte, see [`Self::try_insert_string`].
   pub fn insert_string(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) {
-    self.entries.insert(
-      CString::new(key.as_ref()).expect("failed to convert key to cstring"),
-      BymlWriterNode::String(
-        CString::new(value.as_ref()).expect("failed to convert value to cstring"),
-      ),
-    );
+    self
+      .try_insert_string(key, value)
+      .expect("failed to convert string to cstring")
+  }
+
+  /// Inserts `value` under `key`, failing instead of panicking if either contains a nul byte.
+  /// Like [`Self::insert_string`], an existing value for `key` is replaced.
+  pub fn try_insert_string(
+    &mut self,
+    key: impl AsRef<str>,
+    value: impl AsRef<str>,
+  ) -> Result<(), NulError> {
+    let key = CString::new(key.as_ref())?;
+    let value = CString::new(value.as_ref())?;
+    self.entries.insert(key, BymlWriterNode::String(value));
+    Ok(())
+  }
+
+  /// Inserts `value` under `key`, failing instead of overwriting if the key is already taken, or
+  /// instead of panicking if it contains a nul byte.
+  pub fn try_insert(
+    &mut self,
+    key: impl AsRef<str>,
+    value: BymlWriterNode,
+  ) -> Result<(), InsertError> {
+    let key = key.as_ref();
+    let c_key = CString::new(key).map_err(|source| InsertError::NulInKey {
+      source,
+      backtrace: Backtrace::generate(),
+    })?;
+    match self.entries.entry(c_key) {
+      Entry::Occupied(_) => Err(InsertError::DuplicateKey {
+        key: key.to_owned(),
+        backtrace: Backtrace::generate(),
+      }),
+      Entry::Vacant(entry) => {
+        entry.insert(value);
+        Ok(())
+      }
+    }
   }
 
   pub fn insert_null(&mut self, key: impl AsRef<str>) {
@@ -130,13 +202,6 @@ impl BymlWriterDict {
       BymlWriterNode::Null,
     );
   }
-
-  fn inline_size<O: ByteOrder>(&self) -> Option<u32> {
-    size_of::<ContainerHeader<O>>()
-      .checked_add(align_up(self.len() * size_of::<DictEntry<O>>(), 4))?
-      .try_into()
-      .ok()
-  }
 }
 
 macro_rules! dict_push_impl {
@@ -164,21 +229,67 @@ dict_push_impl! {
   (insert_f32, f32, F32),
   (insert_i64, i64, I64),
   (insert_u64, u64, U64),
-  (insert_f64, f64, F64)
+  (insert_f64, f64, F64),
+  (insert_binary, (impl Into<Vec<u8>>), Binary)
 }
 
-#[derive(Hash, PartialEq, Eq)]
+/// A float that is compared and hashed by its bit pattern, so NaN payloads and signed zeroes are
+/// kept distinct when containers are deduplicated and written out untouched.
+#[derive(Debug, Clone, Copy)]
+#[repr(transparent)]
+pub struct RawFloat<T>(pub T);
+
+macro_rules! raw_float_impl {
+  ($($float: ty),*) => {
+    $(
+      impl PartialEq for RawFloat<$float> {
+        fn eq(&self, other: &Self) -> bool {
+          self.0.to_bits() == other.0.to_bits()
+        }
+      }
+
+      impl Eq for RawFloat<$float> {}
+
+      impl Hash for RawFloat<$float> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+          self.0.to_bits().hash(state);
+        }
+      }
+
+      impl From<$float> for RawFloat<$float> {
+        fn from(value: $float) -> Self {
+          Self(value)
+        }
+      }
+    )*
+  };
+}
+
+raw_float_impl!(f32, f64);
+
+impl<T> Deref for RawFloat<T> {
+  type Target = T;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq)]
 pub enum BymlWriterNode {
   Array(Rc<BymlWriterArray>),
   Dictionary(Rc<BymlWriterDict>),
   Bool(bool),
   I32(i32),
-  F32(OrderedFloat<f32>),
+  F32(RawFloat<f32>),
   U32(u32),
   I64(i64),
   U64(u64),
-  F64(OrderedFloat<f64>),
+  F64(RawFloat<f64>),
   String(CString),
+  /// Only understood by version 4 readers and later, so writing it as an older [`Version`]
+  /// fails.
+  Binary(Vec<u8>),
   Null,
 }
 
@@ -195,134 +306,391 @@ impl BymlWriterNode {
       BymlWriterNode::U64(_) => DataType::U64,
       BymlWriterNode::F64(_) => DataType::F64,
       BymlWriterNode::String(_) => DataType::String,
+      BymlWriterNode::Binary(_) => DataType::Binary,
       BymlWriterNode::Null => DataType::Null,
     }
   }
 }
 
-#[derive(Clone, Hash, PartialEq, Eq)]
-enum Container {
-  Array(Rc<BymlWriterArray>),
-  Dictionary(Rc<BymlWriterDict>),
+#[derive(Hash, PartialEq, Eq)]
+enum FlatElement {
+  Array(usize),
+  Dictionary(usize),
+  Value(BymlWriterNode),
 }
 
-impl Container {
+impl FlatElement {
+  fn data_type(&self) -> DataType {
+    match self {
+      FlatElement::Array(_) => DataType::Array,
+      FlatElement::Dictionary(_) => DataType::Dictionary,
+      FlatElement::Value(value) => value.data_type(),
+    }
+  }
+}
+
+/// A container whose child containers are replaced by their index in [`BymlWriter::containers`],
+/// which keeps hashing shallow while deduplicating.
+#[derive(Hash, PartialEq, Eq)]
+enum FlatContainer {
+  Array(Vec<FlatElement>),
+  Dictionary(Vec<(CString, FlatElement)>),
+}
+
+impl FlatContainer {
+  fn len(&self) -> usize {
+    match self {
+      FlatContainer::Array(elements) => elements.len(),
+      FlatContainer::Dictionary(entries) => entries.len(),
+    }
+  }
+
   fn inline_size<O: ByteOrder>(&self) -> Option<u32> {
     match self {
-      Container::Array(byml_writer_array) => byml_writer_array.inline_size::<O>(),
-      Container::Dictionary(byml_writer_dict) => byml_writer_dict.inline_size::<O>(),
+      FlatContainer::Array(_) => size_of::<ContainerHeader<O>>()
+        .checked_add(align_up(self.len(), 4))?
+        .checked_add(align_up(self.len() * 4, 4))?
+        .try_into()
+        .ok(),
+      FlatContainer::Dictionary(_) => size_of::<ContainerHeader<O>>()
+        .checked_add(align_up(self.len() * size_of::<DictEntry<O>>(), 4))?
+        .try_into()
+        .ok(),
     }
   }
+
+  fn elements(&self) -> impl Iterator<Item = (Option<&CString>, &FlatElement)> {
+    match self {
+      FlatContainer::Array(elements) => Either::Left(elements.iter().map(|value| (None, value))),
+      FlatContainer::Dictionary(entries) => {
+        Either::Right(entries.iter().map(|(key, value)| (Some(key), value)))
+      }
+    }
+  }
+}
+
+#[derive(Default)]
+struct ContainerArena {
+  indices: HashMap<FlatContainer, usize, HashState>,
+  // shared containers are kept alive so their addresses stay unique while flattening
+  shared_arrays: HashMap<*const BymlWriterArray, (usize, Rc<BymlWriterArray>), HashState>,
+  shared_dicts: HashMap<*const BymlWriterDict, (usize, Rc<BymlWriterDict>), HashState>,
 }
 
+impl ContainerArena {
+  fn intern(&mut self, container: FlatContainer) -> usize {
+    let next_index = self.indices.len();
+    *self.indices.entry(container).or_insert(next_index)
+  }
+
+  fn flatten_node(&mut self, node: BymlWriterNode) -> FlatElement {
+    match node {
+      BymlWriterNode::Array(array) => FlatElement::Array(self.flatten_shared_array(array)),
+      BymlWriterNode::Dictionary(dict) => FlatElement::Dictionary(self.flatten_shared_dict(dict)),
+      value => FlatElement::Value(value),
+    }
+  }
+
+  fn flatten_array(&mut self, array: BymlWriterArray) -> usize {
+    let elements = array
+      .elements
+      .into_iter()
+      .map(|node| self.flatten_node(node))
+      .collect();
+    self.intern(FlatContainer::Array(elements))
+  }
+
+  fn flatten_dict(&mut self, dict: BymlWriterDict) -> usize {
+    let entries = dict
+      .entries
+      .into_iter()
+      .map(|(key, node)| (key, self.flatten_node(node)))
+      .collect();
+    self.intern(FlatContainer::Dictionary(entries))
+  }
+
+  fn flatten_shared_array(&mut self, array: Rc<BymlWriterArray>) -> usize {
+    let pointer = Rc::as_ptr(&array);
+    if let Some((index, _)) = self.shared_arrays.get(&pointer) {
+      return *index;
+    }
+
+    match Rc::try_unwrap(array) {
+      Ok(array) => self.flatten_array(array),
+      Err(array) => {
+        let index = self.flatten_array(BymlWriterArray::clone(&array));
+        self.shared_arrays.insert(pointer, (index, array));
+        index
+      }
+    }
+  }
+
+  fn flatten_shared_dict(&mut self, dict: Rc<BymlWriterDict>) -> usize {
+    let pointer = Rc::as_ptr(&dict);
+    if let Some((index, _)) = self.shared_dicts.get(&pointer) {
+      return *index;
+    }
+
+    match Rc::try_unwrap(dict) {
+      Ok(dict) => self.flatten_dict(dict),
+      Err(dict) => {
+        let index = self.flatten_dict(BymlWriterDict::clone(&dict));
+        self.shared_dicts.insert(pointer, (index, dict));
+        index
+      }
+    }
+  }
+
+  fn into_containers(self) -> Vec<FlatContainer> {
+    let mut containers = self.indices.into_iter().collect::<Vec<_>>();
+    containers.sort_unstable_by_key(|(_, index)| *index);
+    containers
+      .into_iter()
+      .map(|(container, _)| container)
+      .collect()
+  }
+}
+
+/// The version written to the header. Every version lays out the nodes the writer knows the
+/// same way, except that binary values need at least [`Version::V4`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Version {
   V2,
   V3,
+  V4,
+  V5,
+  V6,
+  V7,
+}
+
+impl Version {
+  /// The number stored in the header.
+  pub fn number(self) -> u16 {
+    match self {
+      Version::V2 => 2,
+      Version::V3 => 3,
+      Version::V4 => 4,
+      Version::V5 => 5,
+      Version::V6 => 6,
+      Version::V7 => 7,
+    }
+  }
 }
 
 type HashState = BuildHasherDefault<DefaultHasher>;
 
+/// Offsets of the 64-bit values written so far, keyed by their type and bits.
+type LongValues = HashMap<(DataType, u64), u32, HashState>;
+
+/// Where [`BymlWriter::write`] puts everything, from [`BymlWriter::layout`].
+struct Layout<'a> {
+  strings: HashSet<&'a CString, HashState>,
+  keys: HashSet<&'a CString, HashState>,
+  /// Each container's offset from `nodes_start_offset`, by index.
+  containers: Vec<u32>,
+  hash_key_offset: u32,
+  string_table_offset: u32,
+  nodes_start_offset: u32,
+  /// Where long and binary values start, right after the last container.
+  long_offset: u32,
+  /// Where the document ends.
+  end: u32,
+}
+
+/// The space a binary value takes up, including its length and the padding after it.
+fn binary_size(value: &[u8]) -> Option<u32> {
+  u32::try_from(value.len())
+    .ok()?
+    .checked_add(size_of::<u32>() as u32)?
+    .checked_next_multiple_of(4)
+}
+
+/// Containers and string tables store their entry count in 24 bits.
+const MAX_ENTRIES: usize = 1 << 24;
+
 pub struct BymlWriter {
-  container: Container,
-  containers: HashSet<Container, HashState>,
+  root: usize,
+  // children always come before their parents, so the root is last
+  containers: Vec<FlatContainer>,
 }
 
 impl BymlWriter {
   pub fn from_array(array: impl Into<Rc<BymlWriterArray>>) -> Self {
-    Self::new(Container::Array(array.into()))
+    Self::new(|arena| arena.flatten_shared_array(array.into()))
   }
 
   pub fn from_dictionary(dict: impl Into<Rc<BymlWriterDict>>) -> Self {
-    Self::new(Container::Dictionary(dict.into()))
+    Self::new(|arena| arena.flatten_shared_dict(dict.into()))
+  }
+
+  /// Takes ownership of the tree, moving its values into the writer instead of cloning them.
+  /// Any containers that are still shared elsewhere are cloned once.
+  pub fn from_owned_array(array: BymlWriterArray) -> Self {
+    Self::new(|arena| arena.flatten_array(array))
+  }
+
+  /// Takes ownership of the tree, moving its values into the writer instead of cloning them.
+  /// Any containers that are still shared elsewhere are cloned once.
+  pub fn from_owned_dictionary(dict: BymlWriterDict) -> Self {
+    Self::new(|arena| arena.flatten_dict(dict))
   }
 
-  fn new(container: Container) -> Self {
+  fn new(flatten: impl FnOnce(&mut ContainerArena) -> usize) -> Self {
     assert!(size_of::<usize>() >= 4, "cannot be executed on 16 bit platforms");
-    let mut containers = HashSet::default();
-
-    let mut stack = Vec::new();
-    stack.push(container.clone());
-
-    while let Some(container) = stack.pop() {
-      containers.insert(container.clone());
-
-      match container {
-        Container::Array(array) => stack.extend(array.iter().filter_map(|f| match f {
-          BymlWriterNode::Array(array) => Some(Container::Array(array.clone())),
-          BymlWriterNode::Dictionary(dict) => Some(Container::Dictionary(dict.clone())),
-          _ => None,
-        })),
-        Container::Dictionary(array) => stack.extend(array.iter().filter_map(|(_, f)| match f {
-          BymlWriterNode::Array(array) => Some(Container::Array(array.clone())),
-          BymlWriterNode::Dictionary(dict) => Some(Container::Dictionary(dict.clone())),
-          _ => None,
-        })),
-      }
-    }
+    let mut arena = ContainerArena::default();
+    let root = flatten(&mut arena);
 
     Self {
-      container,
-      containers,
+      root,
+      containers: arena.into_containers(),
     }
   }
 
-  fn traverse_containers<'a>(
-    &'a self,
-    mut func: impl FnMut(&'a Container) -> Result<(), WriteError>,
-  ) -> Result<(), WriteError> {
-    for ele in &self.containers {
-      func(ele)?;
+  /// Containers in the order they are laid out in the file, root first.
+  fn traverse_containers(&self) -> impl Iterator<Item = (usize, &FlatContainer)> {
+    self.containers.iter().enumerate().rev()
+  }
+
+  /// Checks every container and string against the format's limits, naming the path to the
+  /// first one that doesn't fit. [`Self::write`] only reports that something overflowed.
+  pub fn validate(&self) -> Result<(), ValidationError> {
+    fn check_string(
+      path: impl FnOnce() -> String,
+      string: &CString,
+    ) -> Result<(), ValidationError> {
+      let length = string.as_bytes_with_nul().len();
+      if u32::try_from(length).is_err() {
+        return Err(ValidationError::StringTooLong {
+          path: path(),
+          length,
+          backtrace: Backtrace::generate(),
+        });
+      }
+      Ok(())
     }
 
+    fn check_table(
+      table: &'static str,
+      strings: &HashSet<&CString, HashState>,
+    ) -> Result<(), ValidationError> {
+      if strings.len() >= MAX_ENTRIES {
+        return Err(ValidationError::TooManyStrings {
+          table,
+          entries: strings.len(),
+          backtrace: Backtrace::generate(),
+        });
+      }
+      let size = strings
+        .iter()
+        .map(|string| string.as_bytes_with_nul().len())
+        .fold(0usize, usize::saturating_add);
+      if u32::try_from(size).is_err() {
+        return Err(ValidationError::StringTableTooLarge {
+          table,
+          size,
+          backtrace: Backtrace::generate(),
+        });
+      }
+      Ok(())
+    }
+
+    let mut strings: HashSet<&CString, HashState> = HashSet::default();
+    let mut keys: HashSet<&CString, HashState> = HashSet::default();
+    let mut visited = vec![false; self.containers.len()];
+    let mut pending = vec![(self.root, String::from("root"))];
+
+    while let Some((index, path)) = pending.pop() {
+      if std::mem::replace(&mut visited[index], true) {
+        continue;
+      }
+
+      let container = &self.containers[index];
+      if container.len() >= MAX_ENTRIES {
+        return Err(ValidationError::ContainerTooLarge {
+          path,
+          entries: container.len(),
+          backtrace: Backtrace::generate(),
+        });
+      }
+
+      for (position, (key, element)) in container.elements().enumerate() {
+        let element_path = || match key {
+          Some(key) => format!("{path}/{}", key.to_string_lossy()),
+          None => format!("{path}[{position}]"),
+        };
+
+        if let Some(key) = key {
+          check_string(element_path, key)?;
+          keys.insert(key);
+        }
+        match element {
+          FlatElement::Array(child) | FlatElement::Dictionary(child) => {
+            pending.push((*child, element_path()));
+          }
+          FlatElement::Value(BymlWriterNode::String(string)) => {
+            check_string(element_path, string)?;
+            strings.insert(string);
+          }
+          FlatElement::Value(_) => {}
+        }
+      }
+    }
+
+    check_table("hash key", &keys)?;
+    check_table("string", &strings)?;
+
     Ok(())
   }
 
-  // todo: panic handling for arithmetic
-  pub fn write<O: ByteOrder>(
-    &self,
-    writer: &mut (impl Write + Seek),
-    version: Version,
-  ) -> Result<(), WriteError> {
+  /// Works out where everything goes, without writing anything.
+  fn layout<O: ByteOrder>(&self, version: Version) -> Result<Layout<'_>, WriteError> {
     let mut strings: HashSet<&CString, HashState> = HashSet::default();
     let mut keys: HashSet<&CString, HashState> = HashSet::default();
-    let mut data_size = 0u32;
+    let mut long_values: HashSet<(DataType, u64), HashState> = HashSet::default();
+    let mut binary_size_total = 0u32;
     let mut container_offset = 0u32;
-    let mut containers: HashMap<&Container, u32, HashState> = HashMap::default();
+    let mut containers = vec![0u32; self.containers.len()];
 
-    self.traverse_containers(|cont| {
-      containers.insert(cont, container_offset);
+    for (index, cont) in self.traverse_containers() {
+      containers[index] = container_offset;
       let inline_size = align_up(cont.inline_size::<O>().ok_or(Overflowed)?, 4);
       container_offset = container_offset
-        .checked_add(inline_size as u32)
+        .checked_add(inline_size)
         .ok_or(Overflowed)?;
-      data_size = data_size.checked_add(inline_size).ok_or(Overflowed)?;
-      let iter = match cont {
-        Container::Array(array) => {
-          Either::Left(array.iter().map(|value| (None::<&CString>, value)))
-        }
-        Container::Dictionary(dict) => {
-          Either::Right(dict.iter().map(|(key, value)| (Some(key), value)))
-        }
-      };
 
-      for (key, value) in iter {
+      for (key, value) in cont.elements() {
         if let Some(key) = key {
           keys.insert(key);
         }
         match value {
-          BymlWriterNode::String(string) => {
+          FlatElement::Value(BymlWriterNode::String(string)) => {
             strings.insert(string);
           }
-          BymlWriterNode::I64(_) | BymlWriterNode::U64(_) | BymlWriterNode::F64(_) => {
-            data_size = data_size.checked_add(8).ok_or(Overflowed)?;
+          // equal long values are written once, see `write_long`
+          FlatElement::Value(BymlWriterNode::I64(value)) => {
+            long_values.insert((DataType::I64, value.cast_unsigned()));
+          }
+          FlatElement::Value(BymlWriterNode::U64(value)) => {
+            long_values.insert((DataType::U64, *value));
+          }
+          FlatElement::Value(BymlWriterNode::F64(value)) => {
+            long_values.insert((DataType::F64, value.to_bits()));
+          }
+          FlatElement::Value(BymlWriterNode::Binary(value)) => {
+            if version.number() < 4 {
+              return Err(WriteError::BinaryNeedsVersion4 {
+                version: version.number(),
+                backtrace: Backtrace::generate(),
+              });
+            }
+            binary_size_total = binary_size_total
+              .checked_add(binary_size(value).ok_or(Overflowed)?)
+              .ok_or(Overflowed)?;
           }
           _ => {}
         }
       }
-
-      Ok(())
-    })?;
+    }
 
     let strings_len = align_up(
       strings
@@ -384,20 +752,69 @@ impl BymlWriter {
     .try_into()
     .map_err(|_| Overflowed)?;
 
+    let long_offset = nodes_start_offset
+      .checked_add(container_offset)
+      .ok_or(Overflowed)?;
+    let long_values_size = u32::try_from(long_values.len())
+      .map_err(|_| Overflowed)?
+      .checked_mul(8)
+      .ok_or(Overflowed)?;
+    let end = long_offset
+      .checked_add(long_values_size)
+      .and_then(|end| end.checked_add(binary_size_total))
+      .ok_or(Overflowed)?;
+
+    Ok(Layout {
+      strings,
+      keys,
+      containers,
+      hash_key_offset,
+      string_table_offset,
+      nodes_start_offset,
+      long_offset,
+      end,
+    })
+  }
+
+  /// How many bytes [`Self::write`] would write for the same byte order and version, for sizing
+  /// buffers up front. Fails in the same cases `write` does, other than I/O errors.
+  pub fn serialized_size<O: ByteOrder>(&self, version: Version) -> Result<u32, WriteError> {
+    Ok(self.layout::<O>(version)?.end)
+  }
+
+  // todo: panic handling for arithmetic
+  pub fn write<O: ByteOrder>(
+    &self,
+    writer: &mut (impl Write + Seek),
+    version: Version,
+  ) -> Result<(), WriteError> {
+    let Layout {
+      strings,
+      keys,
+      containers,
+      hash_key_offset,
+      string_table_offset,
+      nodes_start_offset,
+      mut long_offset,
+      end: _,
+    } = self.layout::<O>(version)?;
+
     let header = Header::<O> {
       magic: match O::ORDER {
-        zerocopy::Order::BigEndian => *b"BY",
-        zerocopy::Order::LittleEndian => *b"YB",
+        zerocopy::Order::BigEndian => magic::BYML_BIG_ENDIAN,
+        zerocopy::Order::LittleEndian => magic::BYML_LITTLE_ENDIAN,
       },
-      version: U16::<O>::new(match version {
-        Version::V2 => 2,
-        Version::V3 => 3,
+      version: U16::<O>::new(version.number()),
+      // empty tables are left out entirely, which readers recognize by a zero offset
+      hash_key_offset: U32::<O>::new(if keys.is_empty() { 0 } else { hash_key_offset }),
+      string_table_offset: U32::<O>::new(if strings.is_empty() {
+        0
+      } else {
+        string_table_offset
       }),
-      hash_key_offset: U32::<O>::new(hash_key_offset as _),
-      string_table_offset: U32::<O>::new(string_table_offset as _),
       root_node_offset: U32::<O>::new(
         (nodes_start_offset as u32)
-          .checked_add(*containers.get(&self.container).unwrap())
+          .checked_add(containers[self.root])
           .ok_or(Overflowed)?,
       ),
     };
@@ -408,23 +825,18 @@ impl BymlWriter {
     let strings = Self::write_string_table::<O>(strings, writer)?;
     writer.seek(SeekFrom::Start(nodes_start_offset as u64))?;
 
-    let mut long_offset = nodes_start_offset
-      .checked_add(container_offset)
-      .ok_or(Overflowed)?;
+    let mut longs = HashMap::default();
     let mut element_types: Vec<DataType> = Vec::new();
     let mut element_values: Vec<u32> = Vec::new();
-    for cont in &self.containers {
-      let container_offset = containers
-        .get(&cont)
-        .expect("missed reference during container ingest");
+    for (index, cont) in self.traverse_containers() {
       writer.seek(io::SeekFrom::Start(
         nodes_start_offset
-          .checked_add(*container_offset)
+          .checked_add(containers[index])
           .ok_or(Overflowed)? as u64,
       ))?;
 
       match cont {
-        Container::Array(array) => {
+        FlatContainer::Array(array) => {
           let header =
             ContainerHeader::<O>::new(DataType::Array, array.len() as u32).ok_or(Overflowed)?;
           element_types.clear();
@@ -437,6 +849,7 @@ impl BymlWriter {
               writer,
               nodes_start_offset as u32,
               &mut long_offset,
+              &mut longs,
               &strings,
               element,
             )?);
@@ -444,11 +857,11 @@ impl BymlWriter {
 
           writer.write_all(header.as_bytes())?;
           writer.write_all(element_types.as_bytes())?;
-          let align = 4 - (writer.stream_position()?.cast_signed() & 3);
-          writer.seek_relative(align)?;
+          let position = writer.stream_position()?;
+          writer.seek(SeekFrom::Start(align_up(position, 4)))?;
           writer.write_all(element_values.as_bytes())?;
         }
-        Container::Dictionary(dict) => {
+        FlatContainer::Dictionary(dict) => {
           let header =
             ContainerHeader::<O>::new(DataType::Dictionary, dict.len() as u32).ok_or(Overflowed)?;
           writer.write_all(header.as_bytes())?;
@@ -459,8 +872,9 @@ impl BymlWriter {
               writer,
               nodes_start_offset as u32,
               &mut long_offset,
+              &mut longs,
               &strings,
-              &element,
+              element,
             )?;
             writer.write_all(
               DictEntry::<O>::new(element.data_type(), *key, value)
@@ -477,10 +891,34 @@ impl BymlWriter {
     Ok(())
   }
 
+  /// Serializes the document into a new buffer, for when there's nothing to seek in already.
+  pub fn write_to_vec<O: ByteOrder>(&self, version: Version) -> Result<Vec<u8>, WriteError> {
+    let mut writer = Cursor::new(Vec::new());
+    self.write::<O>(&mut writer, version)?;
+    Ok(writer.into_inner())
+  }
+
+  /// Serializes the document and Yaz0-compresses it into `writer`, as it would be stored in a
+  /// `.szs`. `window` is passed to [`yaz0::compress`].
+  pub fn write_compressed<O: ByteOrder>(
+    &self,
+    writer: &mut impl Write,
+    version: Version,
+    window: u16,
+  ) -> Result<(), WriteError> {
+    let data = self.write_to_vec::<O>(version)?;
+    yaz0::compress(&mut data.as_slice(), writer, window)
+      .map_err(|source| WriteError::Compression { source })
+  }
+
   fn write_string_table<'a, O: ByteOrder>(
     table: HashSet<&'a CString, HashState>,
     writer: &mut (impl Write + Seek),
   ) -> Result<BTreeMap<&'a CString, u32>, WriteError> {
+    if table.is_empty() {
+      return Ok(BTreeMap::new());
+    }
+
     let mut offset = size_of::<ContainerHeader<O>>() + align_up((table.len() + 1) * 4, 4);
     let mut offsets = Vec::with_capacity(align_up(table.len() + 1, 4));
     let mut table = table.into_iter().collect::<Vec<_>>();
@@ -509,11 +947,20 @@ impl BymlWriter {
     Ok(table)
   }
 
+  /// Writes a 64-bit value to the long value region, or reuses the offset of an equal value of the
+  /// same type that was already written.
   fn write_long<T: IntoBytes + Immutable>(
     writer: &mut (impl Write + Seek),
     long_offset: &mut u32,
+    longs: &mut LongValues,
+    data_type: DataType,
+    bits: u64,
     value: T,
   ) -> Result<u32, WriteError> {
+    if let Some(offset) = longs.get(&(data_type, bits)) {
+      return Ok(*offset);
+    }
+
     let position = writer.stream_position()?;
     let offset = writer.seek(SeekFrom::Start(*long_offset as u64))?;
     *long_offset = long_offset
@@ -521,32 +968,57 @@ impl BymlWriter {
       .ok_or(Overflowed)?;
     writer.write_all(value.as_bytes())?;
     writer.seek(SeekFrom::Start(position))?;
+    longs.insert((data_type, bits), offset as u32);
     Ok(offset as u32)
   }
 
+  /// Writes a binary value after the long values, prefixed with its length. Unlike long values,
+  /// equal blobs aren't shared.
+  fn write_binary<O: ByteOrder>(
+    writer: &mut (impl Write + Seek),
+    long_offset: &mut u32,
+    value: &[u8],
+  ) -> Result<u32, WriteError> {
+    let length = U32::<O>::new(value.len().try_into().map_err(|_| Overflowed)?);
+    let size = binary_size(value).ok_or(Overflowed)?;
+
+    let position = writer.stream_position()?;
+    let offset = *long_offset;
+    writer.seek(SeekFrom::Start(offset as u64))?;
+    writer.write_all(length.as_bytes())?;
+    writer.write_all(value)?;
+    // padded so that whatever follows stays aligned
+    writer.write_all(&[0; 3][..size as usize - size_of::<u32>() - value.len()])?;
+    writer.seek(SeekFrom::Start(position))?;
+
+    *long_offset = offset.checked_add(size).ok_or(Overflowed)?;
+    Ok(offset)
+  }
+
   fn get_value<O: ByteOrder>(
-    containers: &HashMap<&Container, u32, HashState>,
+    containers: &[u32],
     writer: &mut (impl Write + Seek),
     nodes_start_offset: u32,
     long_offset: &mut u32,
+    longs: &mut LongValues,
     strings: &BTreeMap<&CString, u32>,
-    ele: &BymlWriterNode,
+    ele: &FlatElement,
   ) -> Result<u32, WriteError> {
+    let ele = match ele {
+      FlatElement::Array(index) | FlatElement::Dictionary(index) => {
+        return Ok(
+          nodes_start_offset
+            .checked_add(containers[*index])
+            .ok_or(Overflowed)?,
+        );
+      }
+      FlatElement::Value(ele) => ele,
+    };
+
     let value = match ele {
-      BymlWriterNode::Array(array) => nodes_start_offset
-        .checked_add(
-          *containers
-            .get(&Container::Array(array.clone()))
-            .expect("missed reference during container ingest"),
-        )
-        .ok_or(Overflowed)?,
-      BymlWriterNode::Dictionary(dict) => nodes_start_offset
-        .checked_add(
-          *containers
-            .get(&Container::Dictionary(dict.clone()))
-            .expect("missed reference during container ingest"),
-        )
-        .ok_or(Overflowed)?,
+      BymlWriterNode::Array(_) | BymlWriterNode::Dictionary(_) => {
+        unreachable!("containers are flattened before writing")
+      }
       BymlWriterNode::Bool(value) => {
         if *value {
           1
@@ -555,20 +1027,36 @@ impl BymlWriter {
         }
       }
       BymlWriterNode::I32(value) => value.cast_unsigned(),
-      BymlWriterNode::F32(value) => u32::from_ne_bytes(value.to_ne_bytes()),
+      BymlWriterNode::F32(value) => value.to_bits(),
       BymlWriterNode::U32(value) => *value,
-      BymlWriterNode::I64(value) => {
-        Self::write_long::<I64<O>>(writer, long_offset, I64::new(*value))?
-      }
-      BymlWriterNode::U64(value) => {
-        Self::write_long::<U64<O>>(writer, long_offset, U64::new(*value))?
-      }
-      BymlWriterNode::F64(value) => {
-        Self::write_long::<F64<O>>(writer, long_offset, F64::new(**value))?
-      }
+      BymlWriterNode::I64(value) => Self::write_long::<I64<O>>(
+        writer,
+        long_offset,
+        longs,
+        DataType::I64,
+        value.cast_unsigned(),
+        I64::new(*value),
+      )?,
+      BymlWriterNode::U64(value) => Self::write_long::<U64<O>>(
+        writer,
+        long_offset,
+        longs,
+        DataType::U64,
+        *value,
+        U64::new(*value),
+      )?,
+      BymlWriterNode::F64(value) => Self::write_long::<F64<O>>(
+        writer,
+        long_offset,
+        longs,
+        DataType::F64,
+        value.to_bits(),
+        F64::new(**value),
+      )?,
       BymlWriterNode::String(cstring) => *strings
         .get(cstring)
         .expect("missed string during string ingest"),
+      BymlWriterNode::Binary(value) => Self::write_binary::<O>(writer, long_offset, value)?,
       BymlWriterNode::Null => 0,
     };
 
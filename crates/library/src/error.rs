@@ -0,0 +1,113 @@
+use std::{error::Error as StdError, io};
+
+use snafu::{Backtrace, Snafu};
+
+use crate::{
+  byml::{
+    ElementReadError, OpenError, ValidationError, raw::RawContainerError, write_error::WriteError,
+  },
+  magic::MagicMismatch,
+  nw::{bfres::reader::BfresError, bntx::reader::BntxError},
+  sarc::{
+    reader::{ReadError as SarcReadError, VerifyError as SarcVerifyError},
+    writer::WriteError as SarcWriteError,
+  },
+  yaz0::{CompressionError, DecompressionError},
+};
+
+/// A type-erased error that can cross threads, for mixing this crate's errors with other crates'.
+///
+/// Every error here is `Send + Sync + 'static`, so `?` boxes any of them into this through std's
+/// blanket `From` impl, and they can be handed to `anyhow` or `eyre` as-is.
+pub type BoxError = Box<dyn StdError + Send + Sync + 'static>;
+
+// keeps the guarantee above from silently breaking when an error gains a new field
+const _: () = {
+  const fn assert_thread_safe<T: StdError + Send + Sync + 'static>() {}
+
+  assert_thread_safe::<Error>();
+  assert_thread_safe::<DecompressionError>();
+  assert_thread_safe::<CompressionError>();
+  assert_thread_safe::<SarcReadError>();
+  assert_thread_safe::<SarcVerifyError>();
+  assert_thread_safe::<SarcWriteError>();
+  assert_thread_safe::<OpenError>();
+  assert_thread_safe::<ElementReadError>();
+  assert_thread_safe::<RawContainerError>();
+  assert_thread_safe::<ValidationError>();
+  assert_thread_safe::<WriteError>();
+  assert_thread_safe::<BntxError>();
+  assert_thread_safe::<BfresError>();
+  assert_thread_safe::<MagicMismatch>();
+};
+
+/// Any error this crate can produce, for functions that chain several formats together
+/// (for example yaz0 → sarc → byml). Match on the variant to get the module's own error back.
+#[derive(Snafu, Debug)]
+pub enum Error {
+  #[snafu(context(false), display("io error: {source}"))]
+  Io {
+    source: io::Error,
+    backtrace: Backtrace,
+  },
+  #[snafu(context(false), display("failed to decompress yaz0 data: {source}"))]
+  Decompression {
+    #[snafu(backtrace)]
+    source: DecompressionError,
+  },
+  #[snafu(context(false), display("failed to compress yaz0 data: {source}"))]
+  Compression {
+    #[snafu(backtrace)]
+    source: CompressionError,
+  },
+  #[snafu(context(false), display("failed to read sarc: {source}"))]
+  SarcRead {
+    #[snafu(backtrace)]
+    source: SarcReadError,
+  },
+  #[snafu(context(false), display("failed to verify sarc: {source}"))]
+  SarcVerify {
+    #[snafu(backtrace)]
+    source: SarcVerifyError,
+  },
+  #[snafu(context(false), display("failed to write sarc: {source}"))]
+  SarcWrite {
+    #[snafu(backtrace)]
+    source: SarcWriteError,
+  },
+  #[snafu(context(false), display("failed to open byml: {source}"))]
+  BymlOpen {
+    #[snafu(backtrace)]
+    source: OpenError,
+  },
+  #[snafu(context(false), display("failed to read byml element: {source}"))]
+  BymlElement {
+    #[snafu(backtrace)]
+    source: ElementReadError,
+  },
+  #[snafu(context(false), display("failed to read raw byml container: {source}"))]
+  BymlRawContainer {
+    #[snafu(backtrace)]
+    source: RawContainerError,
+  },
+  #[snafu(context(false), display("byml document can't be written: {source}"))]
+  BymlValidation {
+    #[snafu(backtrace)]
+    source: ValidationError,
+  },
+  #[snafu(context(false), display("failed to write byml: {source}"))]
+  BymlWrite {
+    #[snafu(backtrace)]
+    source: WriteError,
+  },
+  #[snafu(context(false), display("failed to read bntx: {source}"))]
+  Bntx {
+    #[snafu(backtrace)]
+    source: BntxError,
+  },
+  #[snafu(context(false), display("failed to read bfres: {source}"))]
+  Bfres {
+    #[snafu(backtrace)]
+    source: BfresError,
+  },
+}
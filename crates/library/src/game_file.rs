@@ -0,0 +1,129 @@
+//! [`GameFile`], a single entry point that loads a file from disk or memory, decompresses it if
+//! needed and opens it with the right reader.
+
+use std::{fs, io::Cursor, path::Path};
+
+use zerocopy::LittleEndian;
+
+use crate::{
+  Error,
+  byml::reader::BymlDocument,
+  magic,
+  nw::{bfres::reader::BfresReaderV8, bntx::reader::BntxReader},
+  sarc::reader::SarcArchive,
+  yaz0,
+};
+
+/// How many bytes [`GameFile::from_bytes`] lets Yaz0 data decompress to. See
+/// [`GameFile::from_bytes_with_limit`].
+pub const DEFAULT_SIZE_LIMIT: u32 = 512 * 1024 * 1024;
+
+/// The formats [`GameFile`] recognizes by their magic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+  Sarc,
+  Byml,
+  Bntx,
+  Bfres,
+}
+
+impl FileKind {
+  /// Picks the format from the start of `data`, `None` if it isn't one of the above.
+  /// Yaz0 compressed data has to be decompressed first.
+  pub fn detect(data: &[u8]) -> Option<Self> {
+    if data.starts_with(&magic::SARC) {
+      Some(Self::Sarc)
+    } else if data.starts_with(&magic::BNTX) {
+      Some(Self::Bntx)
+    } else if data.starts_with(&magic::FRES) {
+      Some(Self::Bfres)
+    } else if data.starts_with(&magic::BYML_LITTLE_ENDIAN)
+      || data.starts_with(&magic::BYML_BIG_ENDIAN)
+    {
+      Some(Self::Byml)
+    } else {
+      None
+    }
+  }
+}
+
+/// A file's decompressed contents, which the readers borrow from.
+///
+/// The readers hold on to slices of their data, so this owns the buffer and opens a reader on
+/// every call to one of the `as_*` accessors. Opening is cheap since nothing is copied.
+///
+/// ```no_run
+/// # use senobi_library::game_file::GameFile;
+/// let file = GameFile::open("Bed.szs")?;
+/// let byml = file.as_sarc()?.get("Bed.byml");
+/// # Ok::<(), senobi_library::Error>(())
+/// ```
+pub struct GameFile {
+  data: Box<[u8]>,
+  kind: Option<FileKind>,
+  compressed: bool,
+}
+
+impl GameFile {
+  /// Reads the file at `path`, see [`Self::from_bytes`].
+  pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+    Self::from_bytes(fs::read(path)?)
+  }
+
+  /// Takes ownership of `data`, decompressing it first if it's Yaz0 compressed.
+  ///
+  /// Data in an unrecognized format is kept as is, with a [`Self::kind`] of `None`. Compressed
+  /// data that claims to be larger than [`DEFAULT_SIZE_LIMIT`] is rejected.
+  pub fn from_bytes(data: impl Into<Box<[u8]>>) -> Result<Self, Error> {
+    Self::from_bytes_with_limit(data, DEFAULT_SIZE_LIMIT)
+  }
+
+  /// Like [`Self::from_bytes`], but lets compressed data decompress to at most `limit` bytes
+  /// instead of [`DEFAULT_SIZE_LIMIT`].
+  pub fn from_bytes_with_limit(data: impl Into<Box<[u8]>>, limit: u32) -> Result<Self, Error> {
+    let data = data.into();
+    let compressed = data.starts_with(&magic::YAZ0);
+    let data = if compressed {
+      yaz0::decompress_with_limit(&mut Cursor::new(&data), limit)?
+    } else {
+      data
+    };
+
+    Ok(Self {
+      kind: FileKind::detect(&data),
+      data,
+      compressed,
+    })
+  }
+
+  /// The decompressed data.
+  pub fn data(&self) -> &[u8] {
+    &self.data
+  }
+
+  pub fn kind(&self) -> Option<FileKind> {
+    self.kind
+  }
+
+  /// Whether the data was Yaz0 compressed when it was loaded.
+  pub fn was_compressed(&self) -> bool {
+    self.compressed
+  }
+
+  pub fn as_sarc(&self) -> Result<SarcArchive<'_>, Error> {
+    Ok(SarcArchive::new(&self.data)?)
+  }
+
+  pub fn as_byml(&self) -> Result<BymlDocument<'_>, Error> {
+    Ok(BymlDocument::new(&self.data)?)
+  }
+
+  /// Switch textures are always little endian.
+  pub fn as_bntx(&self) -> Result<BntxReader<'_, LittleEndian>, Error> {
+    Ok(BntxReader::new(&self.data)?)
+  }
+
+  pub fn as_bfres(&self) -> Result<BfresReaderV8<'_>, Error> {
+    Ok(BfresReaderV8::new(&self.data)?)
+  }
+}
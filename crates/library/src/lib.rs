@@ -1,5 +1,15 @@
 pub mod byml;
+mod error;
+pub mod game_file;
+pub mod magic;
 pub mod sarc;
 mod util;
 pub mod yaz0;
 pub mod nw;
+pub mod prelude;
+mod report;
+mod trace;
+
+pub use error::{BoxError, Error};
+pub use report::{format_error, report_error};
+pub use util::{Order, align_up, round_up_to_multiple};
@@ -0,0 +1,44 @@
+//! The magic numbers that start each format and section, and [`check_magic`], which every
+//! reader checks them with.
+
+use snafu::{Backtrace, Snafu, ensure};
+
+pub const YAZ0: [u8; 4] = *b"Yaz0";
+
+pub const SARC: [u8; 4] = *b"SARC";
+pub const SFAT: [u8; 4] = *b"SFAT";
+pub const SFNT: [u8; 4] = *b"SFNT";
+
+/// The magic of little endian documents, see [`BYML_BIG_ENDIAN`].
+pub const BYML_LITTLE_ENDIAN: [u8; 2] = *b"YB";
+pub const BYML_BIG_ENDIAN: [u8; 2] = *b"BY";
+
+/// Padded to eight bytes with nuls.
+pub const BNTX: [u8; 8] = *b"BNTX\0\0\0\0";
+/// Only the first half of the file magic, the rest is padded with spaces rather than nuls.
+pub const FRES: [u8; 4] = *b"FRES";
+/// The magic of the texture dictionary in BNTX files. BFRES dictionaries leave theirs zeroed.
+pub const RES_DICT: [u8; 4] = *b"_DIC";
+
+#[derive(Snafu, Debug)]
+#[snafu(display("expected {expected:02X?}, got {actual:02X?}"))]
+pub struct MagicMismatch {
+  pub expected: Box<[u8]>,
+  pub actual: Box<[u8]>,
+  backtrace: Backtrace,
+}
+
+pub fn check_magic<const N: usize>(
+  actual: [u8; N],
+  expected: [u8; N],
+) -> Result<(), MagicMismatch> {
+  ensure!(
+    actual == expected,
+    MagicMismatchSnafu {
+      expected: Box::<[u8]>::from(expected),
+      actual: Box::<[u8]>::from(actual),
+    }
+  );
+
+  Ok(())
+}
@@ -1 +1,4 @@
+mod obj;
 pub mod reader;
+
+pub use obj::export_obj;
@@ -0,0 +1,112 @@
+use std::io::{self, Write};
+
+use crate::nw::{
+  bfres::reader::{Model, VertexBuffer},
+  gfx::{ChannelFormat, PrimitiveType, TypeFormat},
+};
+
+/// Writes the model's shapes as Wavefront OBJ objects, one per shape in name order.
+///
+/// Only positions are exported, and only each shape's most detailed LOD. Positions must be
+/// stored as three 32-bit floats and meshes must be triangle lists; anything else fails with
+/// [`io::ErrorKind::InvalidData`].
+pub fn export_obj(model: &Model, w: &mut impl Write) -> io::Result<()> {
+  let mut shapes = model.shapes.iter().collect::<Vec<_>>();
+  shapes.sort_by_key(|(name, _)| *name);
+
+  // obj indices are 1-based and count every vertex written before them, across objects
+  let mut vertex_base = 1;
+  for (name, shape) in shapes {
+    let vertex_buffer = model
+      .vertex_buffers
+      .get(shape.vertex_buffer_index as usize)
+      .ok_or_else(|| {
+        invalid_data(format!(
+          "shape {name:?} uses vertex buffer {}, which doesn't exist",
+          shape.vertex_buffer_index
+        ))
+      })?;
+
+    writeln!(w, "o {name}")?;
+    let positions = positions(name, vertex_buffer)?;
+    for [x, y, z] in &positions {
+      writeln!(w, "v {x} {y} {z}")?;
+    }
+
+    if let Some(mesh) = shape.meshes().next() {
+      if mesh.primitive_type != PrimitiveType::Triangles {
+        return Err(invalid_data(format!(
+          "shape {name:?} uses {:?} primitives, only triangles can be exported",
+          mesh.primitive_type
+        )));
+      }
+
+      let indices = mesh
+        .indices()
+        .map(|index| {
+          let index = index.checked_add(mesh.first_vertex).ok_or_else(|| {
+            invalid_data(format!(
+              "shape {name:?}'s index {index} overflows when offset by its first vertex {}",
+              mesh.first_vertex
+            ))
+          })?;
+          if (index as usize) < positions.len() {
+            Ok(vertex_base + index as usize)
+          } else {
+            Err(invalid_data(format!(
+              "shape {name:?} refers to vertex {index}, but only has {}",
+              positions.len()
+            )))
+          }
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+      for face in indices.chunks_exact(3) {
+        writeln!(w, "f {} {} {}", face[0], face[1], face[2])?;
+      }
+    }
+
+    vertex_base += positions.len();
+  }
+
+  Ok(())
+}
+
+fn positions(name: &str, vertex_buffer: &VertexBuffer) -> io::Result<Vec<[f32; 3]>> {
+  let attribute = vertex_buffer
+    .attributes
+    .get("_p0")
+    .ok_or_else(|| invalid_data(format!("shape {name:?} has no positions")))?;
+
+  if (attribute.channel_format, attribute.type_format)
+    != (ChannelFormat::R32G32B32, TypeFormat::Float)
+  {
+    return Err(invalid_data(format!(
+      "shape {name:?}'s positions are stored as {:?} {:?}, which isn't supported",
+      attribute.channel_format, attribute.type_format
+    )));
+  }
+
+  let positions = vertex_buffer
+    .attribute_data(attribute)
+    .ok_or_else(|| invalid_data(format!("shape {name:?}'s position buffer doesn't exist")))?
+    .filter_map(|vertex| vertex.get(..12))
+    .map(|vertex| {
+      [0, 4, 8].map(|offset| f32::from_le_bytes(vertex[offset..offset + 4].try_into().unwrap()))
+    })
+    .collect::<Vec<_>>();
+
+  if positions.len() != vertex_buffer.vertex_count as usize {
+    return Err(invalid_data(format!(
+      "shape {name:?}'s position buffer only holds {} of its {} vertices",
+      positions.len(),
+      vertex_buffer.vertex_count
+    )));
+  }
+
+  Ok(positions)
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -1,16 +1,125 @@
 use std::{collections::HashMap, marker::PhantomData};
 
-use snafu::{Backtrace, Snafu};
+use num_traits::FromPrimitive;
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
 use zerocopy::{
   little_endian::{U16, U32, U64}, ByteOrder, FromBytes, Immutable, IntoBytes, KnownLayout, LittleEndian
 };
 
-use crate::nw::util::{res_dict::DictRef, BinaryBlockHeader, BinaryFileHeader};
+use crate::{
+  magic::{self, MagicMismatch, check_magic},
+  nw::{
+    gfx::{ChannelFormat, IndexFormat, PrimitiveType, TypeFormat, decode_image_format},
+    util::{
+      BinaryBlockHeader, BinaryFileHeader,
+      res_dict::{DictRef, ResDictError},
+    },
+  },
+  trace::{self, TraceError},
+  util::align_up,
+};
 
 #[derive(Snafu, Debug)]
 pub enum BfresError {
   #[snafu(display("the header is out of bounds"))]
   HeaderOutOfBounds { backtrace: Backtrace },
+  #[snafu(display("the file's magic is wrong: {source}"))]
+  IncorrectMagic { source: MagicMismatch },
+  #[snafu(display("the memory pool info is out of bounds: offset is 0x{offset:X}"))]
+  MemoryPoolInfoOutOfBounds { offset: usize, backtrace: Backtrace },
+  #[snafu(display("failed to read model"))]
+  Models {
+    #[snafu(backtrace)]
+    source: Box<ResDictError<BfresError>>,
+  },
+  #[snafu(display("failed to read model {model:?}'s shapes"))]
+  Shapes {
+    model: String,
+    #[snafu(backtrace)]
+    source: Box<ResDictError<BfresError>>,
+  },
+  #[snafu(display("shape {shape:?}'s {count} meshes are out of bounds: offset is 0x{offset:X}"))]
+  MeshesOutOfBounds {
+    shape: String,
+    offset: usize,
+    count: u8,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("shape {shape:?}'s mesh {mesh} has an invalid primitive type 0x{actual:X}"))]
+  InvalidPrimitiveType {
+    shape: String,
+    mesh: usize,
+    actual: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("shape {shape:?}'s mesh {mesh} has an invalid index format 0x{actual:X}"))]
+  InvalidIndexFormat {
+    shape: String,
+    mesh: usize,
+    actual: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display(
+    "shape {shape:?}'s mesh {mesh} has its index buffer out of bounds: offset is 0x{offset:X}"
+  ))]
+  IndexBufferOutOfBounds {
+    shape: String,
+    mesh: usize,
+    offset: usize,
+    backtrace: Backtrace,
+  },
+  #[snafu(display(
+    "model {model:?}'s {count} vertex buffers are out of bounds: offset is 0x{offset:X}"
+  ))]
+  VertexBuffersOutOfBounds {
+    model: String,
+    offset: usize,
+    count: u16,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("failed to read vertex buffer {vertex_buffer}'s attributes"))]
+  Attributes {
+    vertex_buffer: usize,
+    #[snafu(backtrace)]
+    source: Box<ResDictError<BfresError>>,
+  },
+  #[snafu(display("attribute {attribute:?} has an invalid format 0x{actual:04X}"))]
+  InvalidAttributeFormat {
+    attribute: String,
+    actual: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display(
+    "vertex buffer {vertex_buffer}'s buffer sizes and strides are out of bounds: offset is 0x{offset:X}"
+  ))]
+  BufferInfoOutOfBounds {
+    vertex_buffer: usize,
+    offset: usize,
+    backtrace: Backtrace,
+  },
+  #[snafu(display(
+    "vertex buffer {vertex_buffer}'s buffer {buffer} is out of bounds: offset is 0x{offset:X}"
+  ))]
+  VertexDataOutOfBounds {
+    vertex_buffer: usize,
+    buffer: usize,
+    offset: usize,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("failed to read embedded files"))]
+  EmbeddedFiles {
+    #[snafu(backtrace)]
+    source: Box<ResDictError<BfresError>>,
+  },
+  #[snafu(display(
+    "embedded file {name:?} is out of bounds: offset is 0x{offset:X}, size is 0x{size:X}"
+  ))]
+  EmbeddedFileOutOfBounds {
+    name: String,
+    offset: u64,
+    size: u32,
+    backtrace: Backtrace,
+  },
 }
 
 #[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
@@ -25,11 +134,11 @@ struct ResFileV8 {
   pub shape_anims: DictRef<LittleEndian>,
   pub scene_anims: DictRef<LittleEndian>,
   _runtime_memory_pool: U64,
-  _runtime_memory_pool_info: U64,
+  pub memory_pool_info_offset: U64,
   pub embedded_files: DictRef<LittleEndian>,
   pub _runtime_user_pointer: U64,
   some_string: U64,
-  _padding: u32,
+  _padding: U32,
   pub model_count: U16,
   pub skeletal_anim_count: U16,
   pub material_anim_count: U16,
@@ -61,6 +170,110 @@ struct ResModelV8 {
   _unused_2: [u8; 4],
 }
 
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResMemoryPoolInfo {
+  _flags: U32,
+  pub size: U32,
+  pub offset: U64,
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResExternalFile {
+  pub offset: U64,
+  pub size: U32,
+  _padding: [u8; 4],
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResShapeV8 {
+  pub header: BinaryBlockHeader<LittleEndian>,
+  pub name: U64,
+  pub vertex_buffer_offset: U64,
+  pub mesh_array_offset: U64,
+  pub skin_bone_index_array_offset: U64,
+  pub key_shapes: DictRef<LittleEndian>,
+  pub bounding_box_array_offset: U64,
+  pub bounding_radius_array_offset: U64,
+  _runtime_user_pointer: U64,
+  pub flags: U32,
+  pub index: U16,
+  pub material_index: U16,
+  pub bone_index: U16,
+  pub vertex_buffer_index: U16,
+  pub skin_bone_index_count: U16,
+  pub vertex_skin_count: u8,
+  pub mesh_count: u8,
+  pub key_shape_count: u8,
+  pub target_attribute_count: u8,
+  _padding: [u8; 6],
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResMeshV8 {
+  pub sub_mesh_array_offset: U64,
+  _runtime_memory_pool: U64,
+  _runtime_buffer: U64,
+  pub buffer_size_offset: U64,
+  /// Relative to the file's memory pool.
+  pub index_buffer_offset: U32,
+  pub primitive_type: U32,
+  pub index_format: U32,
+  pub index_count: U32,
+  pub first_vertex: U32,
+  pub sub_mesh_count: U16,
+  _padding: [u8; 2],
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResVertexV8 {
+  pub header: BinaryBlockHeader<LittleEndian>,
+  pub attributes: DictRef<LittleEndian>,
+  _runtime_memory_pool: U64,
+  _runtime_buffer_array: U64,
+  _user_buffer_array: U64,
+  pub buffer_size_array_offset: U64,
+  pub buffer_stride_array_offset: U64,
+  _padding: U64,
+  /// Relative to the file's memory pool.
+  pub buffer_offset: U32,
+  pub attribute_count: u8,
+  pub buffer_count: u8,
+  pub index: U16,
+  pub vertex_count: U32,
+  pub vertex_skin_count: u8,
+  _padding_2: [u8; 3],
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResVertexAttributeV8 {
+  pub name: U64,
+  pub format: U32,
+  pub offset: U16,
+  pub buffer_index: U16,
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResBufferSize {
+  pub size: U32,
+  _flags: U32,
+  _padding: [u8; 8],
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct ResBufferStride {
+  pub stride: U32,
+  _divisor: U32,
+  _padding: [u8; 8],
+}
+
 #[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
 #[repr(C)]
 struct ResSkeletonV8 {
@@ -93,31 +306,369 @@ struct ResUserData {
   data_type: U32
 }
 
-pub struct Model {
+pub struct Model<'a> {
   // pub skeleton:
+  pub shapes: HashMap<&'a str, Shape<'a>>,
+  pub vertex_buffers: Vec<VertexBuffer<'a>>,
+}
+
+pub struct VertexBuffer<'a> {
+  pub vertex_count: u32,
+  /// Keyed by the attribute's name, such as `_p0` for positions or `_u0` for the first UV set.
+  pub attributes: HashMap<&'a str, VertexAttribute>,
+  pub buffers: Vec<Buffer<'a>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VertexAttribute {
+  pub channel_format: ChannelFormat,
+  pub type_format: TypeFormat,
+  /// The attribute's offset into each of its buffer's vertices.
+  pub offset: u16,
+  pub buffer_index: u16,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Buffer<'a> {
+  pub stride: u32,
+  pub data: &'a [u8],
+}
+
+impl<'a> VertexBuffer<'a> {
+  /// Returns the bytes of `attribute` for every vertex, `None` if the attribute's buffer doesn't
+  /// exist. Each slice runs to the end of its vertex's stride.
+  pub fn attribute_data(
+    &self,
+    attribute: &VertexAttribute,
+  ) -> Option<impl Iterator<Item = &'a [u8]> + 'a> {
+    let buffer = *self.buffers.get(attribute.buffer_index as usize)?;
+    let offset = attribute.offset as usize;
+
+    Some(
+      buffer
+        .data
+        .chunks(buffer.stride.max(1) as usize)
+        .take(self.vertex_count as usize)
+        .filter_map(move |vertex| vertex.get(offset..)),
+    )
+  }
+}
+
+pub struct Shape<'a> {
+  pub material_index: u16,
+  pub vertex_buffer_index: u16,
+  meshes: Vec<Mesh<'a>>,
+}
+
+impl<'a> Shape<'a> {
+  /// Iterates over the shape's meshes, from the most detailed LOD to the least.
+  pub fn meshes(&self) -> impl ExactSizeIterator<Item = Mesh<'a>> + '_ {
+    self.meshes.iter().copied()
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mesh<'a> {
+  pub primitive_type: PrimitiveType,
+  pub index_format: IndexFormat,
+  pub index_count: u32,
+  /// Added to every index to get the vertex it refers to.
+  pub first_vertex: u32,
+  /// The raw, little endian index buffer.
+  pub index_buffer: &'a [u8],
+}
+
+impl Mesh<'_> {
+  /// Decodes the index buffer, widening every index to a `u32`.
+  pub fn indices(&self) -> impl ExactSizeIterator<Item = u32> + '_ {
+    self
+      .index_buffer
+      .chunks_exact(self.index_format.size())
+      .map(|index| match *index {
+        [index] => index as u32,
+        [a, b] => u16::from_le_bytes([a, b]) as u32,
+        [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+        _ => unreachable!(),
+      })
+  }
 }
 
 pub struct BfresReaderV8<'a> {
   file_data: &'a [u8],
-  pub models: HashMap<&'a str, Model>,
+  pub models: HashMap<&'a str, Model<'a>>,
+  /// Files stored alongside the models, such as `textures.bntx`, keyed by name.
+  pub embedded_files: HashMap<&'a str, &'a [u8]>,
 }
 
 const DICT_SIGNATURE: &'static [u8; 4] = b"\0\0\0\0";
 
 impl<'a> BfresReaderV8<'a> {
+  pub fn new(file_data: &'a [u8]) -> Result<BfresReaderV8<'a>, BfresError> {
+    trace::enter_span!("bfres::open", size = file_data.len());
+    Self::open(file_data).trace_error()
+  }
+
+  fn open(file_data: &'a [u8]) -> Result<BfresReaderV8<'a>, BfresError> {
+    let file = file_data
+      .get(..size_of::<ResFileV8>())
+      .map(|data| ResFileV8::ref_from_bytes(data).unwrap())
+      .context(HeaderOutOfBoundsSnafu)?;
+
+    // the rest of the magic is padded with spaces rather than nuls
+    check_magic(*file.binary_file.magic.first_chunk().unwrap(), magic::FRES)
+      .context(IncorrectMagicSnafu)?;
+
+    let memory_pool_offset = file.memory_pool_info_offset.get() as usize;
+    let memory_pool = memory_pool_offset
+      .checked_add(size_of::<ResMemoryPoolInfo>())
+      .and_then(|end_offset| file_data.get(memory_pool_offset..end_offset))
+      .map(|data| ResMemoryPoolInfo::ref_from_bytes(data).unwrap())
+      .context(MemoryPoolInfoOutOfBoundsSnafu {
+        offset: memory_pool_offset,
+      })?;
+    let buffer_data = file_data
+      .get(memory_pool.offset.get() as usize..)
+      .and_then(|data| data.get(..memory_pool.size.get() as usize))
+      .unwrap_or_default();
+
+    let models = file
+      .models
+      .read::<ResModelV8, Model, BfresError>(file_data, DICT_SIGNATURE, |key, model| {
+        let shapes = model
+          .shapes
+          .read::<ResShapeV8, Shape, BfresError>(file_data, DICT_SIGNATURE, |key, shape| {
+            Self::read_shape(file_data, buffer_data, key, shape)
+          })
+          .map_err(Box::new)
+          .context(ShapesSnafu {
+            model: key.to_owned(),
+          })?;
+
+        let vertex_buffers = Self::read_vertex_buffers(file_data, buffer_data, key, model)?;
+
+        Ok(Model {
+          shapes,
+          vertex_buffers,
+        })
+      })
+      .map_err(Box::new)
+      .context(ModelsSnafu)?;
+
+    // files without any embedded files leave the dictionary offset null
+    let embedded_files = match file.embedded_file_count.get() {
+      0 => HashMap::new(),
+      _ => file
+        .embedded_files
+        .read::<ResExternalFile, &[u8], BfresError>(file_data, DICT_SIGNATURE, |key, file| {
+          let offset = file.offset.get();
+          let size = file.size.get();
+          usize::try_from(offset)
+            .ok()
+            .and_then(|offset| file_data.get(offset..)?.get(..size as usize))
+            .context(EmbeddedFileOutOfBoundsSnafu {
+              name: key.to_owned(),
+              offset,
+              size,
+            })
+        })
+        .map_err(Box::new)
+        .context(EmbeddedFilesSnafu)?,
+    };
+
+    Ok(Self {
+      file_data,
+      models,
+      embedded_files,
+    })
+  }
+
+  #[deprecated(note = "renamed to `new` to match the other readers")]
   pub fn read(file_data: &'a [u8]) -> Result<BfresReaderV8<'a>, BfresError> {
-    let file = ResFileV8::read_from_bytes(&file_data[..0xcc]).unwrap();
+    Self::new(file_data)
+  }
+
+  fn read_vertex_buffers(
+    file_data: &'a [u8],
+    buffer_data: &'a [u8],
+    key: &str,
+    model: &ResModelV8,
+  ) -> Result<Vec<VertexBuffer<'a>>, BfresError> {
+    let count = model.vertex_buffer_count.get();
+    let vertices_offset = model.vertex_buffer_array_offset.get() as usize;
+    let vertices = vertices_offset
+      .checked_add(size_of::<ResVertexV8>() * count as usize)
+      .and_then(|end_offset| file_data.get(vertices_offset..end_offset))
+      .map(|data| <[ResVertexV8]>::ref_from_bytes(data).unwrap())
+      .context(VertexBuffersOutOfBoundsSnafu {
+        model: key.to_owned(),
+        offset: vertices_offset,
+        count,
+      })?;
+
+    vertices
+      .iter()
+      .enumerate()
+      .map(|(index, vertex)| Self::read_vertex_buffer(file_data, buffer_data, index, vertex))
+      .collect()
+  }
+
+  fn read_vertex_buffer(
+    file_data: &'a [u8],
+    buffer_data: &'a [u8],
+    index: usize,
+    vertex: &ResVertexV8,
+  ) -> Result<VertexBuffer<'a>, BfresError> {
+    let attributes = vertex
+      .attributes
+      .read::<ResVertexAttributeV8, VertexAttribute, BfresError>(
+        file_data,
+        DICT_SIGNATURE,
+        |key, attribute| {
+          let (channel_format, type_format) =
+            decode_image_format(attribute.format.get()).context(InvalidAttributeFormatSnafu {
+              attribute: key.to_owned(),
+              actual: attribute.format.get(),
+            })?;
 
-    let models = file.models.read::<ResModelV8, Model, BfresError>(
+          Ok(VertexAttribute {
+            channel_format,
+            type_format,
+            offset: attribute.offset.get(),
+            buffer_index: attribute.buffer_index.get(),
+          })
+        },
+      )
+      .map_err(Box::new)
+      .context(AttributesSnafu {
+        vertex_buffer: index,
+      })?;
+
+    let buffer_count = vertex.buffer_count as usize;
+    let sizes = Self::read_buffer_info::<ResBufferSize>(
+      file_data,
+      index,
+      vertex.buffer_size_array_offset.get() as usize,
+      buffer_count,
+    )?;
+    let strides = Self::read_buffer_info::<ResBufferStride>(
       file_data,
-      DICT_SIGNATURE,
-      |key, model: &ResModelV8| {
-        println!("{model:#?}");
-        todo!()
-        // Ok(())
-      },
-    ).unwrap();
-
-    Ok(Self { file_data, models })
+      index,
+      vertex.buffer_stride_array_offset.get() as usize,
+      buffer_count,
+    )?;
+
+    // buffers are laid out back to back, each aligned to 8 bytes
+    let mut offset = vertex.buffer_offset.get() as usize;
+    let mut buffers = Vec::with_capacity(buffer_count);
+    for (buffer, (size, stride)) in sizes.iter().zip(strides).enumerate() {
+      let data = offset
+        .checked_add(size.size.get() as usize)
+        .and_then(|end_offset| buffer_data.get(offset..end_offset))
+        .context(VertexDataOutOfBoundsSnafu {
+          vertex_buffer: index,
+          buffer,
+          offset,
+        })?;
+
+      buffers.push(Buffer {
+        stride: stride.stride.get(),
+        data,
+      });
+      offset = align_up(offset + data.len(), 8);
+    }
+
+    Ok(VertexBuffer {
+      vertex_count: vertex.vertex_count.get(),
+      attributes,
+      buffers,
+    })
+  }
+
+  fn read_buffer_info<T: FromBytes + Immutable + KnownLayout>(
+    file_data: &'a [u8],
+    index: usize,
+    offset: usize,
+    count: usize,
+  ) -> Result<&'a [T], BfresError> {
+    offset
+      .checked_add(size_of::<T>() * count)
+      .and_then(|end_offset| file_data.get(offset..end_offset))
+      .map(|data| <[T]>::ref_from_bytes(data).unwrap())
+      .context(BufferInfoOutOfBoundsSnafu {
+        vertex_buffer: index,
+        offset,
+      })
+  }
+
+  fn read_shape(
+    file_data: &'a [u8],
+    buffer_data: &'a [u8],
+    key: &str,
+    shape: &ResShapeV8,
+  ) -> Result<Shape<'a>, BfresError> {
+    let meshes_offset = shape.mesh_array_offset.get() as usize;
+    let meshes = meshes_offset
+      .checked_add(size_of::<ResMeshV8>() * shape.mesh_count as usize)
+      .and_then(|end_offset| file_data.get(meshes_offset..end_offset))
+      .map(|data| <[ResMeshV8]>::ref_from_bytes(data).unwrap())
+      .context(MeshesOutOfBoundsSnafu {
+        shape: key.to_owned(),
+        offset: meshes_offset,
+        count: shape.mesh_count,
+      })?;
+
+    let meshes = meshes
+      .iter()
+      .enumerate()
+      .map(|(index, mesh)| {
+        let primitive_type = PrimitiveType::from_u32(mesh.primitive_type.get()).context(
+          InvalidPrimitiveTypeSnafu {
+            shape: key.to_owned(),
+            mesh: index,
+            actual: mesh.primitive_type.get(),
+          },
+        )?;
+        let index_format =
+          IndexFormat::from_u32(mesh.index_format.get()).context(InvalidIndexFormatSnafu {
+            shape: key.to_owned(),
+            mesh: index,
+            actual: mesh.index_format.get(),
+          })?;
+
+        let index_count = mesh.index_count.get();
+        let buffer_offset = mesh.index_buffer_offset.get() as usize;
+        let index_buffer = (index_count as usize)
+          .checked_mul(index_format.size())
+          .and_then(|size| buffer_offset.checked_add(size))
+          .and_then(|end_offset| buffer_data.get(buffer_offset..end_offset))
+          .context(IndexBufferOutOfBoundsSnafu {
+            shape: key.to_owned(),
+            mesh: index,
+            offset: buffer_offset,
+          })?;
+
+        Ok(Mesh {
+          primitive_type,
+          index_format,
+          index_count,
+          first_vertex: mesh.first_vertex.get(),
+          index_buffer,
+        })
+      })
+      .collect::<Result<_, BfresError>>()?;
+
+    Ok(Shape {
+      material_index: shape.material_index.get(),
+      vertex_buffer_index: shape.vertex_buffer_index.get(),
+      meshes,
+    })
+  }
+}
+
+impl<'a> TryFrom<&'a [u8]> for BfresReaderV8<'a> {
+  type Error = BfresError;
+
+  fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+    Self::new(data)
   }
 }
@@ -1,26 +1,36 @@
-use std::{collections::HashMap, ffi::CStr};
+use std::collections::HashMap;
 
+use num_traits::FromPrimitive;
+use senobi_textures::{
+  DynTextureReader,
+  formats::{Srgb, bc1::reader::Bc1},
+};
 use snafu::{Backtrace, OptionExt, ResultExt, Snafu, ensure};
-use tegra_swizzle::{surface::deswizzle_surface, SwizzleError};
+use tegra_swizzle::SwizzleError;
 use zerocopy::{ByteOrder, FromBytes, I32, Immutable, IntoBytes, KnownLayout, U32, U64};
 
-use crate::nw::{
-  gfx::{decode_image_format, ChannelFormat, FormatInfo, TextureInfo, TypeFormat},
-  util::{
-    res_dict::{read_res_dict, ResDictError}, BinaryBlockHeader, BinaryFileHeader
+use crate::{
+  magic::{self, MagicMismatch, check_magic},
+  nw::{
+    bfres::reader::BfresReaderV8,
+    gfx::{
+      AccessFlags, ChannelFormat, FormatInfo, ImageDimension, TextureInfo, TypeFormat,
+      decode_image_format, deswizzle,
+    },
+    util::{
+      BinaryBlockHeader, BinaryFileHeader,
+      res_dict::{ResDictError, read_res_dict, read_res_dict_lenient},
+    },
   },
+  trace::{self, TraceError},
 };
 
 #[derive(Snafu, Debug)]
 pub enum BntxError {
   #[snafu(display("the header is out of bounds"))]
   HeaderOutOfBounds { backtrace: Backtrace },
-  #[snafu(display("expected magic to be {expected:02X?}, got {actual:02X?}"))]
-  IncorrectMagic {
-    expected: [u8; 8],
-    actual: [u8; 8],
-    backtrace: Backtrace,
-  },
+  #[snafu(display("the file's magic is wrong: {source}"))]
+  IncorrectMagic { source: MagicMismatch },
   #[snafu(display("the texture container header is out of bounds"))]
   ResTextureContainerHeaderOutOfBounds { backtrace: Backtrace },
   #[snafu(display("failed to read texture"))]
@@ -38,12 +48,41 @@ pub enum BntxError {
     actual: u32,
     backtrace: Backtrace,
   },
+  #[snafu(display("texture {key:?} has an invalid image dimension 0x{actual:02X}"))]
+  InvalidImageDimension {
+    key: String,
+    actual: u8,
+    backtrace: Backtrace,
+  },
   MipmapPointersOutOfBounds {
     key: String,
     offset: usize,
     levels: u16,
     backtrace: Backtrace,
   },
+  #[snafu(display(
+    "texture {key:?} has {levels} mip levels, but a {width}x{height} texture can have at most {maximum}"
+  ))]
+  MipLevelCountInvalid {
+    key: String,
+    levels: u16,
+    maximum: u16,
+    width: u32,
+    height: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("layer {layer} is out of bounds, the texture has {layers} layers"))]
+  LayerOutOfBounds {
+    layer: u32,
+    layers: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("failed to deswizzle layer {layer}: {source}"))]
+  Deswizzle {
+    layer: u32,
+    source: SwizzleError,
+    backtrace: Backtrace,
+  },
   #[snafu(display("texture {key:?}'s mipmap {level} is out of bounds: offset is 0x{offset:X}"))]
   MipmapOutOfBounds {
     key: String,
@@ -51,6 +90,34 @@ pub enum BntxError {
     offset: usize,
     backtrace: Backtrace,
   },
+  #[snafu(display(
+    "texture {key:?}'s image data is out of bounds: offset is 0x{offset:X}, size is 0x{size:X}"
+  ))]
+  ImageDataOutOfBounds {
+    key: String,
+    offset: usize,
+    size: usize,
+    backtrace: Backtrace,
+  },
+  #[snafu(display(
+    "the texture container lists {header} textures, but the texture dictionary has {dictionary}"
+  ))]
+  TextureCountMismatch {
+    header: u32,
+    dictionary: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("texture {key:?} was read without its image data"))]
+  NoImageData { key: String, backtrace: Backtrace },
+  #[snafu(display("failed to deswizzle the image: {source}"))]
+  DeswizzleImage {
+    source: SwizzleError,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("the BFRES file has no embedded file named {name:?}"))]
+  EmbeddedFileNotFound { name: String, backtrace: Backtrace },
+  #[snafu(display("embedded file {name:?} isn't a BNTX file: {source}"))]
+  EmbeddedFileNotBntx { name: String, source: MagicMismatch },
 }
 #[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
 #[repr(C)]
@@ -88,26 +155,24 @@ pub struct ResTextureInfo<O: ByteOrder> {
   pub user_data_dictionary: U64<O>,
 }
 
+/// A texture's name and the result of reading it, see [`BntxReader::read_lenient`].
+pub type LenientTexture<'a, O> = (&'a str, Result<BntxTextureReader<'a, O>, BntxError>);
+
 pub struct BntxReader<'a, O: ByteOrder + 'static> {
   file_data: &'a [u8],
   pub textures: HashMap<&'a str, BntxTextureReader<'a, O>>,
+  texture_names: Vec<&'a str>,
 }
 
 impl<'a, O: ByteOrder> BntxReader<'a, O> {
-  pub fn read(file_data: &'a [u8]) -> Result<Self, BntxError> {
+  fn read_container(file_data: &'a [u8]) -> Result<ResTextureContainer<O>, BntxError> {
     let header_offset_end = size_of::<BinaryFileHeader<O>>();
     let header = file_data
       .get(..header_offset_end)
       .map(|data| BinaryFileHeader::<O>::ref_from_bytes(data).unwrap())
       .context(HeaderOutOfBoundsSnafu)?;
 
-    ensure!(
-      header.magic == *b"BNTX\0\0\0\0",
-      IncorrectMagicSnafu {
-        expected: *b"BNTX\0\0\0\0",
-        actual: header.magic
-      }
-    );
+    check_magic(header.magic, magic::BNTX).context(IncorrectMagicSnafu)?;
 
     let container_offset_end = header_offset_end + size_of::<ResTextureContainer<O>>();
     let container = file_data
@@ -115,117 +180,260 @@ impl<'a, O: ByteOrder> BntxReader<'a, O> {
       .map(|data| ResTextureContainer::<O>::read_from_bytes(data).unwrap())
       .context(ResTextureContainerHeaderOutOfBoundsSnafu)?;
 
-    println!(
-      "name: {:?}",
-      CStr::from_bytes_until_nul(&file_data[header.file_name_offset.get() as usize..])
+    trace::debug!(
+      name = ?file_data
+        .get(header.file_name_offset.get() as usize..)
+        .and_then(|name| std::ffi::CStr::from_bytes_until_nul(name).ok()),
+      "read texture container"
     );
 
+    Ok(container)
+  }
+
+  pub fn new(file_data: &'a [u8]) -> Result<Self, BntxError> {
+    trace::enter_span!("bntx::open", size = file_data.len());
+    Self::open(file_data, true).trace_error()
+  }
+
+  /// Reads the BNTX file embedded in `bfres` as `name`, usually `textures.bntx`.
+  pub fn from_bfres(bfres: &BfresReaderV8<'a>, name: &str) -> Result<Self, BntxError> {
+    let file_data = *bfres
+      .embedded_files
+      .get(name)
+      .context(EmbeddedFileNotFoundSnafu { name })?;
+
+    Self::new(file_data).map_err(|error| match error {
+      BntxError::IncorrectMagic { source } => BntxError::EmbeddedFileNotBntx {
+        name: name.to_owned(),
+        source,
+      },
+      error => error,
+    })
+  }
+
+  /// Like [`Self::new`], but only reads the textures' metadata, so that names, formats and
+  /// dimensions can be listed from files whose texture data is truncated or stripped.
+  ///
+  /// The textures' image data accessors return [`BntxError::NoImageData`].
+  pub fn read_metadata_only(file_data: &'a [u8]) -> Result<Self, BntxError> {
+    trace::enter_span!("bntx::open", size = file_data.len());
+    Self::open(file_data, false).trace_error()
+  }
+
+  fn open(file_data: &'a [u8], with_data: bool) -> Result<Self, BntxError> {
+    let container = Self::read_container(file_data)?;
+
+    let mut texture_names = Vec::new();
     let textures = read_res_dict::<U64<O>, BntxTextureReader<'a, O>, O, BntxError>(
       file_data,
-      b"DIC_",
+      &magic::RES_DICT,
       container.texture_info_dictionary_offset.get() as _,
       container.texture_info_values_offset.get() as _,
       |key, texture| {
-        let offset = texture.get() as usize;
-        let info = offset
-          .checked_add(size_of::<ResTextureInfo<O>>())
-          .and_then(|end_offset| file_data.get(offset..end_offset))
-          .map(|data| ResTextureInfo::<O>::ref_from_bytes(data).unwrap())
-          .context(TextureInfoOutOfBoundsSnafu {
-            offset,
-            key: key.to_owned(),
-          })?;
-        println!("{key}");
-
-        let decoded = decode_image_format(info.info.image_format.get()).expect("fuck");
-        println!("{decoded:?}");
-        let array_layer_count = info.info.array_layers.get();
-        let mip_level_count = info.info.mip_levels.get() as usize;
-
-        let mipmap_ptrs_offset = info.mipmap_array.get() as usize;
-        let mipmap_ptr_array = mipmap_ptrs_offset
-          .checked_add(size_of::<u64>() * mip_level_count)
-          .and_then(|end_offset| file_data.get(mipmap_ptrs_offset..end_offset))
-          .map(|data| <[U64<O>]>::ref_from_bytes_with_elems(data, mip_level_count).unwrap())
-          .context(MipmapPointersOutOfBoundsSnafu {
-            offset: mipmap_ptrs_offset,
-            key: key.to_owned(),
-            levels: mip_level_count as u16,
-          })?;
-
-        let mut array_layers = Vec::with_capacity(array_layer_count as usize);
-        let mut array_offset = 0;
-        for array_layer in 0..array_layer_count {
-          let mut next_array_offset = 0;
-          let mut mipmaps = Vec::with_capacity(array_layer_count as usize);
-          for mip_level in 0..mip_level_count {
-            let start_offset = mipmap_ptr_array[0].get() as usize;
-            let index = (array_layer as usize * mip_level_count) + mip_level;
-            let offset = mipmap_ptr_array[index].get() as usize;
-            // .checked_mul(array_offset)
-            // .context(MipmapOutOfBoundsSnafu {
-            //   key: key.to_owned(),
-            //   level: mip_level as u16,
-            //   offset: mipmap_ptr_array[index].get() as usize,
-            // })?;
-
-            println!("{index} {} {}", info.info.width, info.info.height);
-            println!(
-              "mip_offset {offset} {array_offset} {start_offset} {}",
-              info.total_texture_size.get()
-            );
-            let size = start_offset + info.total_texture_size.get() as usize - offset;
-            println!("mip_size {size} {}", offset + size);
-
-            let mipmap = start_offset
-              .checked_add(info.total_texture_size.get() as _)
-              .and_then(|size| size.checked_sub(offset))
-              .and_then(|size| size.checked_div(array_layer_count as usize))
-              .and_then(|size| file_data.get(offset..offset + size))
-              .context(MipmapOutOfBoundsSnafu {
-                key: key.to_owned(),
-                level: mip_level as u16,
-                offset,
-              })?;
-
-            if mip_level == 0 {
-              next_array_offset += mipmap.len();
-            }
-
-            mipmaps.push(mipmap);
-          }
-
-          // panic!();
-
-          array_layers.push(mipmaps);
-          array_offset += next_array_offset;
-        }
-        println!("henlo {key:?}");
-
-        Ok(BntxTextureReader {
-          file_data,
-          info,
-          array_levels: array_layers,
-        })
+        texture_names.push(key);
+        BntxTextureReader::read(file_data, key, texture.get() as usize, with_data)
       },
     )
     .map_err(Box::new)
     .context(TextureInfoSnafu)?;
+    Self::check_texture_count(&container, texture_names.len())?;
 
     Ok(Self {
       file_data,
       textures,
+      texture_names,
     })
   }
+
+  #[deprecated(note = "renamed to `new` to match the other readers")]
+  pub fn read(file_data: &'a [u8]) -> Result<Self, BntxError> {
+    Self::new(file_data)
+  }
+
+  /// Like [`Self::new`], but a texture that fails to read is kept as its error instead of failing
+  /// the whole archive. Errors in the file header or the texture dictionary are still returned.
+  ///
+  /// The textures are returned in the order they are declared in the texture dictionary.
+  pub fn read_lenient(file_data: &'a [u8]) -> Result<Vec<LenientTexture<'a, O>>, BntxError> {
+    trace::enter_span!("bntx::open", size = file_data.len());
+    let container = Self::read_container(file_data).trace_error()?;
+
+    let mut texture_names = Vec::new();
+    let mut textures = read_res_dict_lenient::<U64<O>, BntxTextureReader<'a, O>, O, BntxError>(
+      file_data,
+      &magic::RES_DICT,
+      container.texture_info_dictionary_offset.get() as _,
+      container.texture_info_values_offset.get() as _,
+      |key, texture| {
+        texture_names.push(key);
+        BntxTextureReader::read(file_data, key, texture.get() as usize, true).trace_error()
+      },
+    )
+    .map_err(Box::new)
+    .context(TextureInfoSnafu)
+    .trace_error()?;
+    Self::check_texture_count(&container, textures.len()).trace_error()?;
+
+    Ok(
+      texture_names
+        .into_iter()
+        .filter_map(|name| Some((name, textures.remove(name)?)))
+        .collect(),
+    )
+  }
+
+  fn check_texture_count(
+    container: &ResTextureContainer<O>,
+    dictionary: usize,
+  ) -> Result<(), BntxError> {
+    ensure!(
+      container.texture_count.get() as usize == dictionary,
+      TextureCountMismatchSnafu {
+        header: container.texture_count.get(),
+        dictionary: dictionary as u32,
+      }
+    );
+
+    Ok(())
+  }
+
+  /// Iterates over the textures in the order they are declared in the texture dictionary.
+  pub fn textures_ordered(&self) -> impl Iterator<Item = (&'a str, &BntxTextureReader<'a, O>)> {
+    self
+      .texture_names
+      .iter()
+      .map(|name| (*name, &self.textures[name]))
+  }
+}
+
+impl<'a, O: ByteOrder> TryFrom<&'a [u8]> for BntxReader<'a, O> {
+  type Error = BntxError;
+
+  fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+    Self::new(data)
+  }
 }
 
 pub struct BntxTextureReader<'a, O: ByteOrder + 'static> {
   file_data: &'a [u8],
-  array_levels: Vec<Vec<&'a [u8]>>,
+  key: &'a str,
+  dimension: ImageDimension,
+  /// `None` when the texture was read with [`BntxReader::read_metadata_only`].
+  array_levels: Option<Vec<Vec<&'a [u8]>>>,
   pub info: &'a ResTextureInfo<O>,
 }
 
 impl<'a, O: ByteOrder> BntxTextureReader<'a, O> {
+  fn read(
+    file_data: &'a [u8],
+    key: &'a str,
+    offset: usize,
+    with_data: bool,
+  ) -> Result<Self, BntxError> {
+    trace::enter_span!("texture", key);
+    let info = offset
+      .checked_add(size_of::<ResTextureInfo<O>>())
+      .and_then(|end_offset| file_data.get(offset..end_offset))
+      .map(|data| ResTextureInfo::<O>::ref_from_bytes(data).unwrap())
+      .context(TextureInfoOutOfBoundsSnafu {
+        offset,
+        key: key.to_owned(),
+      })?;
+
+    let dimension = ImageDimension::from_u8(info.image_dimension).context(
+      InvalidImageDimensionSnafu {
+        key: key.to_owned(),
+        actual: info.image_dimension,
+      },
+    )?;
+
+    decode_image_format(info.info.image_format.get()).context(InvalidImageFormatSnafu {
+      key: key.to_owned(),
+      actual: info.info.image_format.get(),
+    })?;
+    let array_layer_count = info.info.array_layers.get();
+    let mip_level_count = info.info.mip_levels.get() as usize;
+    trace::debug!(
+      width = info.info.width.get(),
+      height = info.info.height.get(),
+      format = info.info.image_format.get(),
+      array_layers = array_layer_count,
+      mip_levels = mip_level_count,
+      "read texture info"
+    );
+
+    // every level halves the larger dimension until it reaches 1
+    let width = info.info.width.get();
+    let height = info.info.height.get();
+    let maximum = (u32::BITS - width.max(height).max(1).leading_zeros()) as u16;
+    ensure!(
+      mip_level_count <= maximum as usize,
+      MipLevelCountInvalidSnafu {
+        key: key.to_owned(),
+        levels: mip_level_count as u16,
+        maximum,
+        width,
+        height,
+      }
+    );
+
+    if !with_data {
+      return Ok(Self {
+        file_data,
+        key,
+        dimension,
+        info,
+        array_levels: None,
+      });
+    }
+
+    let mipmap_ptrs_offset = info.mipmap_array.get() as usize;
+    let mipmap_ptr_array = mipmap_ptrs_offset
+      .checked_add(size_of::<u64>() * mip_level_count)
+      .and_then(|end_offset| file_data.get(mipmap_ptrs_offset..end_offset))
+      .map(|data| <[U64<O>]>::ref_from_bytes_with_elems(data, mip_level_count).unwrap())
+      .context(MipmapPointersOutOfBoundsSnafu {
+        offset: mipmap_ptrs_offset,
+        key: key.to_owned(),
+        levels: mip_level_count as u16,
+      })?;
+
+    // each layer holds its own mip chain, and the mipmap pointers only cover the first one,
+    // so later layers are found by stepping whole layers past them
+    let layer_size = info.total_texture_size.get() as usize / array_layer_count.max(1) as usize;
+    let mut array_layers = Vec::with_capacity(array_layer_count as usize);
+    for array_layer in 0..array_layer_count as usize {
+      let layer_start = array_layer * layer_size;
+      let mut mipmaps = Vec::with_capacity(mip_level_count);
+      for mip_level in 0..mip_level_count {
+        let start_offset = mipmap_ptr_array[0].get() as usize;
+        let offset = (mipmap_ptr_array[mip_level].get() as usize).saturating_add(layer_start);
+        let layer_end = start_offset.saturating_add(layer_start + layer_size);
+
+        // a mip runs on until the end of its layer, as the swizzled size isn't known here
+        let mipmap = file_data
+          .get(offset..layer_end)
+          .context(MipmapOutOfBoundsSnafu {
+            key: key.to_owned(),
+            level: mip_level as u16,
+            offset,
+          })?;
+
+        mipmaps.push(mipmap);
+      }
+
+      array_layers.push(mipmaps);
+    }
+
+    Ok(Self {
+      file_data,
+      key,
+      dimension,
+      info,
+      array_levels: Some(array_layers),
+    })
+  }
+
   pub fn width(&self) -> u32 {
     self.info.info.width.get()
   }
@@ -241,39 +449,124 @@ impl<'a, O: ByteOrder> BntxTextureReader<'a, O> {
   pub fn mip_levels(&self) -> u32 {
     self.info.info.mip_levels.get() as u32
   }
+  pub fn sample_count(&self) -> u32 {
+    self.info.info.sample_count.get() as u32
+  }
+  pub fn access_flags(&self) -> AccessFlags {
+    AccessFlags::from_u32(self.info.info.access_flags.get())
+  }
+
+  pub fn dimension(&self) -> ImageDimension {
+    self.dimension
+  }
+
+  /// The undecoded image format, with the channel format in bits 8..16 and the type in 0..8.
+  pub fn raw_format_word(&self) -> u32 {
+    self.info.info.image_format.get()
+  }
+
+  /// Decodes the image format, `None` if either half isn't one [`decode_image_format`] knows.
+  pub fn try_image_format(&self) -> Option<(ChannelFormat, TypeFormat)> {
+    decode_image_format(self.raw_format_word())
+  }
 
+  /// Panics if the format can't be decoded, see [`Self::try_image_format`].
   pub fn image_format(&self) -> (ChannelFormat, TypeFormat) {
-    decode_image_format(self.info.info.image_format.get()).unwrap()
+    self.try_image_format().unwrap()
   }
 
-  pub fn image_data(&self) -> &'a [u8] {
-    let start_offset = self.info.mipmap_array.get() as usize;
-    let data_start_ptr = U64::<O>::read_from_bytes(
-      self
-        .file_data
-        .get(start_offset..start_offset + size_of::<u64>())
-        .unwrap(),
-    )
-    .unwrap()
-    .get() as usize;
-    let data_end_ptr = data_start_ptr + self.info.total_texture_size.get() as usize;
+  /// The swizzled data of every layer and mip.
+  pub fn image_data(&self) -> Result<&'a [u8], BntxError> {
+    ensure!(
+      self.array_levels.is_some(),
+      NoImageDataSnafu { key: self.key }
+    );
 
-    &self.file_data[data_start_ptr..data_end_ptr]
+    let pointer_offset = self.info.mipmap_array.get() as usize;
+    let offset = pointer_offset
+      .checked_add(size_of::<u64>())
+      .and_then(|end_offset| self.file_data.get(pointer_offset..end_offset))
+      .map(|data| U64::<O>::read_from_bytes(data).unwrap().get() as usize)
+      .context(MipmapPointersOutOfBoundsSnafu {
+        offset: pointer_offset,
+        key: self.key,
+        levels: 1u16,
+      })?;
+    let size = self.info.total_texture_size.get() as usize;
+
+    offset
+      .checked_add(size)
+      .and_then(|end_offset| self.file_data.get(offset..end_offset))
+      .context(ImageDataOutOfBoundsSnafu {
+        key: self.key,
+        offset,
+        size,
+      })
   }
 
-  pub fn deswizzled_image_data(&self) -> Result<Vec<u8>, SwizzleError> {
+  /// The swizzled data starting at mip `level` of array `layer`, running to the end of the layer.
+  /// `None` if either is out of range, or the texture was read without its data.
+  pub fn mip_data(&self, layer: u32, level: u32) -> Option<&'a [u8]> {
+    self
+      .array_levels
+      .as_ref()?
+      .get(layer as usize)?
+      .get(level as usize)
+      .copied()
+  }
+
+  pub fn deswizzled_image_data(&self) -> Result<Vec<u8>, BntxError> {
+    let data = self.image_data()?;
     let (chan_fmt, type_fmt) = self.image_format();
     let format_info = FormatInfo::from_image_format(chan_fmt, type_fmt).unwrap();
-    deswizzle_surface(
+    deswizzle(
+      &format_info,
       self.width(),
       self.height(),
       self.depth(),
-      self.image_data(),
-      format_info.block_dim,
-      None,
-      format_info.bytes_per_pixel,
       self.mip_levels(),
       self.array_layers(),
+      data,
     )
+    .context(DeswizzleImageSnafu)
+  }
+
+  /// Deswizzles the mip chain of a single array layer, such as one face of a cubemap.
+  pub fn deswizzled_layer(&self, layer: u32) -> Result<Vec<u8>, BntxError> {
+    ensure!(
+      self.array_levels.is_some(),
+      NoImageDataSnafu { key: self.key }
+    );
+    let data = self.mip_data(layer, 0).context(LayerOutOfBoundsSnafu {
+      layer,
+      layers: self.array_layers(),
+    })?;
+    let (chan_fmt, type_fmt) = self.image_format();
+    let format_info = FormatInfo::from_image_format(chan_fmt, type_fmt).unwrap();
+    deswizzle(
+      &format_info,
+      self.width(),
+      self.height(),
+      self.depth(),
+      self.mip_levels(),
+      1,
+      data,
+    )
+    .context(DeswizzleSnafu { layer })
+  }
+
+  /// Deswizzles the image data and wraps it in the matching decoder from `senobi_textures`.
+  /// Returns `None` if the image format has no decoder yet or the data fails to deswizzle.
+  pub fn as_texture_reader(&self) -> Option<Box<dyn DynTextureReader>> {
+    let reader: Box<dyn DynTextureReader> = match self.try_image_format()? {
+      (ChannelFormat::BC1, TypeFormat::SRGB) => Box::new(Bc1::<Srgb>::new(
+        self.width(),
+        self.height(),
+        self.deswizzled_image_data().ok()?,
+      )),
+      _ => return None,
+    };
+
+    Some(reader)
   }
 }
@@ -3,7 +3,10 @@ use std::num::NonZeroU32;
 use modular_bitfield::prelude::*;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use tegra_swizzle::surface::BlockDim;
+use tegra_swizzle::{
+  SwizzleError,
+  surface::{BlockDim, deswizzle_surface},
+};
 use zerocopy::{ByteOrder, FromBytes, Immutable, IntoBytes, KnownLayout, U16, U32, Unaligned};
 
 #[bitfield(bytes = 1)]
@@ -17,6 +20,36 @@ pub struct TextureInfoFlags {
   padding: B4,
 }
 
+/// `nn::gfx::GpuAccess`, the ways the GPU is allowed to access a texture.
+/// Decode [`TextureInfo::access_flags`] with [`AccessFlags::from_u32`].
+#[bitfield(bytes = 4)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AccessFlags {
+  pub read: bool,
+  pub write: bool,
+  pub vertex_buffer: bool,
+  pub index_buffer: bool,
+  pub constant_buffer: bool,
+  pub texture: bool,
+  pub unordered_access_buffer: bool,
+  pub color_buffer: bool,
+  pub depth_stencil: bool,
+  pub indirect_buffer: bool,
+  pub scan_buffer: bool,
+  pub query_buffer: bool,
+  pub descriptor: bool,
+  pub shader_code: bool,
+  pub image: bool,
+  #[skip]
+  __: B17,
+}
+
+impl AccessFlags {
+  pub fn from_u32(value: u32) -> Self {
+    Self::from_bytes(value.to_le_bytes())
+  }
+}
+
 #[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
 #[repr(C)]
 pub struct TextureInfo<O: ByteOrder> {
@@ -38,7 +71,20 @@ pub struct TextureInfo<O: ByteOrder> {
   pub packaged_texture_layout: U32<O>,
 }
 
-#[derive(Debug, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum ImageDimension {
+  Tex1d = 0x0,
+  Tex2d = 0x1,
+  Tex3d = 0x2,
+  Cube = 0x3,
+  Tex1dArray = 0x4,
+  Tex2dArray = 0x5,
+  Tex2dMultisample = 0x6,
+  Tex2dMultisampleArray = 0x7,
+  CubeArray = 0x8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 pub enum ChannelFormat {
   None = 0x1,
   R8 = 0x2,
@@ -86,7 +132,7 @@ pub enum ChannelFormat {
   B5G5R5A1 = 0x3b,
 }
 
-#[derive(Debug, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 pub enum TypeFormat {
   Unorm = 0x1,
   Snorm = 0x2,
@@ -100,6 +146,38 @@ pub enum TypeFormat {
   UFloat = 0xa,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum PrimitiveType {
+  Points = 0x0,
+  Lines = 0x1,
+  LineStrip = 0x2,
+  Triangles = 0x3,
+  TriangleStrip = 0x4,
+  LinesAdjacency = 0x5,
+  LineStripAdjacency = 0x6,
+  TrianglesAdjacency = 0x7,
+  TriangleStripAdjacency = 0x8,
+  Patches = 0x9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum IndexFormat {
+  U8 = 0x0,
+  U16 = 0x1,
+  U32 = 0x2,
+}
+
+impl IndexFormat {
+  /// The size of a single index in bytes.
+  pub fn size(self) -> usize {
+    match self {
+      IndexFormat::U8 => 1,
+      IndexFormat::U16 => 2,
+      IndexFormat::U32 => 4,
+    }
+  }
+}
+
 pub fn decode_image_format(value: u32) -> Option<(ChannelFormat, TypeFormat)> {
   ChannelFormat::from_u32((value & 0xff00) >> 8).zip(TypeFormat::from_u32(value & 0xff))
 }
@@ -144,3 +222,30 @@ impl FormatInfo {
     Some(format)
   }
 }
+
+/// Deswizzles a Tegra block linear surface of `format_info`'s format into a linear one, with
+/// every layer's mip chain one after the other.
+///
+/// The block height of the first mip is inferred from its size, as the Switch's texture formats
+/// leave it out.
+pub fn deswizzle(
+  format_info: &FormatInfo,
+  width: u32,
+  height: u32,
+  depth: u32,
+  mip_levels: u32,
+  array_layers: u32,
+  data: &[u8],
+) -> Result<Vec<u8>, SwizzleError> {
+  deswizzle_surface(
+    width,
+    height,
+    depth,
+    data,
+    format_info.block_dim,
+    None,
+    format_info.bytes_per_pixel,
+    mip_levels,
+    array_layers,
+  )
+}
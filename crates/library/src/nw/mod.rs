@@ -2,3 +2,5 @@ mod util;
 pub mod bntx;
 pub mod gfx;
 pub mod bfres;
+
+pub use util::res_dict;
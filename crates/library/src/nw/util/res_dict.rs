@@ -4,19 +4,17 @@ use std::{
   str::Utf8Error,
 };
 
-use snafu::{Backtrace, OptionExt, ResultExt, Snafu, ensure};
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
 use zerocopy::{ByteOrder, FromBytes, Immutable, IntoBytes, KnownLayout, U16, U32, U64};
 
+use crate::magic::{MagicMismatch, check_magic};
+
 #[derive(Snafu, Debug)]
 pub enum ResDictError<ReadError: snafu::Error + snafu::ErrorCompat + 'static> {
   #[snafu(display("the dictionary's header is out of bounds: offset is 0x{offset:X}"))]
   HeaderOutOfBounds { offset: usize, backtrace: Backtrace },
-  #[snafu(display("expected magic to be {expected:02X?}, got {actual:02X?}"))]
-  IncorrectMagic {
-    expected: [u8; 4],
-    actual: [u8; 4],
-    backtrace: Backtrace,
-  },
+  #[snafu(display("the dictionary's magic is wrong: {source}"))]
+  IncorrectMagic { source: MagicMismatch },
   #[snafu(display("the dictionary's {count} nodes are out of bounds: offset is 0x{offset:X}"))]
   NodesOutOfBounds {
     offset: usize,
@@ -36,6 +34,12 @@ pub enum ResDictError<ReadError: snafu::Error + snafu::ErrorCompat + 'static> {
     #[snafu(backtrace)]
     source: ReadError,
   },
+  #[snafu(display("failed to read node {index}'s value"))]
+  NodeReadFailed {
+    index: usize,
+    #[snafu(backtrace)]
+    source: ReadError,
+  },
   #[snafu(display("key string {index} is out of bounds: offset is 0x{offset:X}"))]
   KeyStringOutOfBounds {
     index: usize,
@@ -82,20 +86,11 @@ struct Node<O: ByteOrder> {
   key_offset: U64<O>,
 }
 
-pub fn read_res_dict<
-  'a,
-  T: FromBytes + Immutable + KnownLayout + 'a,
-  E: 'a,
-  O: ByteOrder,
-  ReadError: snafu::Error + snafu::ErrorCompat + 'static,
->(
+fn read_nodes<'a, O: ByteOrder, ReadError: snafu::Error + snafu::ErrorCompat + 'static>(
   file_data: &'a [u8],
   expected_signature: &'static [u8; 4],
   dict_offset: usize,
-  values_offset: usize,
-  // FnMut(key: &str, element: &mut T)
-  mut node_validator: impl FnMut(&'a str, &'a T) -> Result<E, ReadError>,
-) -> Result<HashMap<&'a str, E>, ResDictError<ReadError>> {
+) -> Result<&'a [Node<O>], ResDictError<ReadError>> {
   let header_end_offset =
     dict_offset
       .checked_add(size_of::<Header<O>>())
@@ -111,13 +106,7 @@ pub fn read_res_dict<
   let header =
     Header::<O>::read_from_bytes(header).expect("failed to validate header slice's size");
 
-  ensure!(
-    header.magic == *expected_signature,
-    IncorrectMagicSnafu {
-      expected: *expected_signature,
-      actual: header.magic
-    }
-  );
+  check_magic(header.magic, *expected_signature).context(IncorrectMagicSnafu)?;
 
   let node_count = header.node_count.get();
 
@@ -137,8 +126,53 @@ pub fn read_res_dict<
         count: node_count,
       })?;
 
-  let nodes = <[Node<O>]>::ref_from_bytes_with_elems(nodes, node_count as _)
-    .expect("failed to validate nodes slice's size");
+  Ok(
+    <[Node<O>]>::ref_from_bytes_with_elems(nodes, node_count as _)
+      .expect("failed to validate nodes slice's size"),
+  )
+}
+
+fn read_key<'a, O: ByteOrder, ReadError: snafu::Error + snafu::ErrorCompat + 'static>(
+  file_data: &'a [u8],
+  index: usize,
+  node: &Node<O>,
+) -> Result<&'a str, ResDictError<ReadError>> {
+  let key_offset = node.key_offset.get() as usize + size_of::<u16>();
+
+  let key = file_data
+    .get(key_offset..)
+    .context(KeyStringOutOfBoundsSnafu {
+      offset: key_offset,
+      index,
+    })?;
+
+  let key = CStr::from_bytes_until_nul(key).context(KeyStringUnterminatedSnafu {
+    offset: key_offset,
+    index,
+  })?;
+
+  key.to_str().context(KeyStringNotUTF8Snafu {
+    offset: key_offset,
+    index,
+  })
+}
+
+pub fn read_res_dict<
+  'a,
+  T: FromBytes + Immutable + KnownLayout + 'a,
+  E: 'a,
+  O: ByteOrder,
+  ReadError: snafu::Error + snafu::ErrorCompat + 'static,
+>(
+  file_data: &'a [u8],
+  expected_signature: &'static [u8; 4],
+  dict_offset: usize,
+  values_offset: usize,
+  // FnMut(key: &str, element: &mut T)
+  mut node_validator: impl FnMut(&'a str, &'a T) -> Result<E, ReadError>,
+) -> Result<HashMap<&'a str, E>, ResDictError<ReadError>> {
+  let nodes = read_nodes::<O, ReadError>(file_data, expected_signature, dict_offset)?;
+  let node_count = nodes.len() as u32;
 
   let values_end_offset = size_of::<T>()
     .checked_mul(node_count as _)
@@ -161,24 +195,7 @@ pub fn read_res_dict<
   let mut dictionary = HashMap::new();
 
   for (index, (node, value_data)) in nodes.iter().zip(values).enumerate() {
-    let key_offset = node.key_offset.get() as usize + size_of::<u16>();
-
-    let key = file_data
-      .get(key_offset..)
-      .context(KeyStringOutOfBoundsSnafu {
-        offset: key_offset,
-        index,
-      })?;
-
-    let key = CStr::from_bytes_until_nul(key).context(KeyStringUnterminatedSnafu {
-      offset: key_offset,
-      index,
-    })?;
-
-    let key = key.to_str().context(KeyStringNotUTF8Snafu {
-      offset: key_offset,
-      index,
-    })?;
+    let key = read_key::<O, ReadError>(file_data, index, node)?;
 
     let value = T::ref_from_bytes(value_data).unwrap();
     let value = node_validator(key, value).context(NodeValueReadFailedSnafu {
@@ -192,6 +209,57 @@ pub fn read_res_dict<
   Ok(dictionary)
 }
 
+/// Like [`read_res_dict`], but for dictionaries whose values aren't one contiguous array.
+/// The validator is given each node's index alongside its key and locates the value itself.
+pub fn read_res_dict_indexed<
+  'a,
+  E: 'a,
+  O: ByteOrder,
+  ReadError: snafu::Error + snafu::ErrorCompat + 'static,
+>(
+  file_data: &'a [u8],
+  expected_signature: &'static [u8; 4],
+  dict_offset: usize,
+  mut node_validator: impl FnMut(usize, &'a str) -> Result<E, ReadError>,
+) -> Result<HashMap<&'a str, E>, ResDictError<ReadError>> {
+  let nodes = read_nodes::<O, ReadError>(file_data, expected_signature, dict_offset)?;
+
+  let mut dictionary = HashMap::new();
+
+  for (index, node) in nodes.iter().enumerate() {
+    let key = read_key::<O, ReadError>(file_data, index, node)?;
+    let value = node_validator(index, key).context(NodeReadFailedSnafu { index })?;
+
+    dictionary.insert(key, value);
+  }
+
+  Ok(dictionary)
+}
+
+/// Like [`read_res_dict`], but a node whose validator fails is stored as its error instead of
+/// failing the whole dictionary.
+pub fn read_res_dict_lenient<
+  'a,
+  T: FromBytes + Immutable + KnownLayout + 'a,
+  E: 'a,
+  O: ByteOrder,
+  ReadError: snafu::Error + snafu::ErrorCompat + 'static,
+>(
+  file_data: &'a [u8],
+  expected_signature: &'static [u8; 4],
+  dict_offset: usize,
+  values_offset: usize,
+  mut node_validator: impl FnMut(&'a str, &'a T) -> Result<E, ReadError>,
+) -> Result<HashMap<&'a str, Result<E, ReadError>>, ResDictError<ReadError>> {
+  read_res_dict::<T, Result<E, ReadError>, O, ReadError>(
+    file_data,
+    expected_signature,
+    dict_offset,
+    values_offset,
+    |key, value| Ok(node_validator(key, value)),
+  )
+}
+
 #[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
 #[repr(C)]
 pub struct DictRef<O: ByteOrder> {
@@ -220,4 +288,19 @@ impl<O: ByteOrder> DictRef<O> {
       node_validator,
     )
   }
+
+  /// Reads the dictionary with [`read_res_dict_indexed`], ignoring `array_offset`.
+  pub fn read_indexed<'a, E: 'a, ReadError: snafu::Error + snafu::ErrorCompat + 'static>(
+    &self,
+    file_data: &'a [u8],
+    expected_signature: &'static [u8; 4],
+    node_validator: impl FnMut(usize, &'a str) -> Result<E, ReadError>,
+  ) -> Result<HashMap<&'a str, E>, ResDictError<ReadError>> {
+    read_res_dict_indexed::<E, O, ReadError>(
+      file_data,
+      expected_signature,
+      self.dict_offset.get() as usize,
+      node_validator,
+    )
+  }
 }
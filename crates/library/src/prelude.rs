@@ -0,0 +1,13 @@
+pub use zerocopy::{BigEndian, ByteOrder, LittleEndian};
+
+pub use crate::{
+  byml::{
+    BymlValue,
+    reader::{BymlDocument, BymlReader, BymlReaderArray, BymlReaderDict, BymlReaderNode},
+    writer::{BymlWriter, BymlWriterArray, BymlWriterDict, BymlWriterNode, Version},
+  },
+  game_file::GameFile,
+  nw::bntx::reader::{BntxReader, BntxTextureReader},
+  sarc::reader::{SarcArchive, SarcReader},
+  yaz0::{decompress, decompressed_size},
+};
@@ -0,0 +1,36 @@
+use std::{env, error::Error, fmt::Write};
+
+use snafu::ErrorCompat;
+
+/// Formats `error` followed by every error in its source chain, one per line.
+///
+/// The error's backtrace is appended when `RUST_LIB_BACKTRACE` (or failing that,
+/// `RUST_BACKTRACE`) is set to something other than `0`.
+pub fn format_error<E: Error + ErrorCompat + 'static>(error: &E) -> String {
+  let mut report = String::new();
+  let mut chain = error.iter_chain();
+  if let Some(error) = chain.next() {
+    writeln!(report, "error: {error}").unwrap();
+  }
+  for source in chain {
+    writeln!(report, "caused by: {source}").unwrap();
+  }
+
+  let backtrace = ErrorCompat::backtrace(error).filter(|_| backtraces_enabled());
+  if let Some(backtrace) = backtrace {
+    writeln!(report, "backtrace:\n{backtrace:?}").unwrap();
+  }
+
+  report
+}
+
+/// Prints [`format_error`]'s report to stderr.
+pub fn report_error<E: Error + ErrorCompat + 'static>(error: &E) {
+  eprint!("{}", format_error(error));
+}
+
+fn backtraces_enabled() -> bool {
+  env::var("RUST_LIB_BACKTRACE")
+    .or_else(|_| env::var("RUST_BACKTRACE"))
+    .is_ok_and(|value| value != "0")
+}
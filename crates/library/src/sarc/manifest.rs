@@ -0,0 +1,35 @@
+use std::io::{self, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// Everything about an archive's layout except its files' contents, so that it can be rebuilt
+/// byte for byte with [`SarcWriter::from_manifest`](crate::sarc::writer::SarcWriter::from_manifest)
+/// after the files have been extracted and edited.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SarcManifest {
+  pub hash_key: u32,
+  /// The archive's files, in the order their data is stored. This can differ from the node
+  /// order, which is always sorted by hash.
+  pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+  /// `None` for nodes that are only identified by their hash.
+  pub name: Option<String>,
+  pub hash: u32,
+  /// The smallest power of two that reproduces the padding before the file's data.
+  pub alignment: u32,
+}
+
+impl SarcManifest {
+  /// Reads a manifest written by [`Self::write`].
+  pub fn read(reader: impl Read) -> io::Result<Self> {
+    Ok(serde_json::from_reader(reader)?)
+  }
+
+  /// Writes the manifest as pretty printed JSON.
+  pub fn write(&self, writer: impl Write) -> io::Result<()> {
+    Ok(serde_json::to_writer_pretty(writer, self)?)
+  }
+}
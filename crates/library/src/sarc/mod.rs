@@ -1,2 +1,23 @@
+#[cfg(feature = "manifest")]
+pub mod manifest;
 pub mod reader;
-mod types;
+pub mod types;
+pub mod writer;
+
+/// The hash key every known archive uses.
+pub const DEFAULT_HASH_KEY: u32 = 0x65;
+
+/// Hashes a node name the way SFAT does, treating each byte as a signed char.
+pub fn name_hash(name: &[u8], hash_key: u32) -> u32 {
+  name.iter().fold(0u32, |hash, &byte| {
+    hash
+      .wrapping_mul(hash_key)
+      .wrapping_add(byte as i8 as u32)
+  })
+}
+
+/// Hashes `name` the way SFAT does, so that lookups can be prepared ahead of time. `key` is the
+/// archive's [`hash_key`](reader::SarcReader::hash_key), usually [`DEFAULT_HASH_KEY`].
+pub fn compute_hash(name: &str, key: u32) -> u32 {
+  name_hash(name.as_bytes(), key)
+}
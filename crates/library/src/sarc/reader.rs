@@ -1,9 +1,21 @@
-use std::{ffi::CStr, marker::PhantomData, ops::Range};
+#[cfg(feature = "manifest")]
+use std::io::{self, Write};
+use std::{ffi::CStr, marker::PhantomData, ops::Range, slice};
 
-use snafu::{ensure, Backtrace, Snafu};
-use zerocopy::{ByteOrder, FromBytes};
+use either::Either;
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+use zerocopy::{BigEndian, ByteOrder, FromBytes, LittleEndian};
 
-use crate::sarc::types::{SarcHeader, SfatHeader, SfatNode, SfntHeader};
+#[cfg(feature = "manifest")]
+use crate::sarc::manifest::{ManifestEntry, SarcManifest};
+use crate::{
+  magic::{self, MagicMismatch, check_magic},
+  sarc::{
+    compute_hash, name_hash,
+    types::{SarcHeader, SfatHeader, SfatNode, SfntHeader},
+  },
+  trace::{self, TraceError},
+};
 
 #[derive(Snafu, Debug)]
 pub enum ReadError {
@@ -11,10 +23,9 @@ pub enum ReadError {
   HeaderOutOfBounds {
     backtrace: Backtrace,
   },
+  #[snafu(display("the file header's magic is wrong: {source}"))]
   IncorrectHeaderMagic {
-    expected: [u8; 4],
-    actual: [u8; 4],
-    backtrace: Backtrace,
+    source: MagicMismatch,
   },
   InvalidByteOrderMark {
     actual: [u8; 2],
@@ -41,10 +52,9 @@ pub enum ReadError {
   NodeTableHeaderOutOfBounds {
     backtrace: Backtrace,
   },
+  #[snafu(display("the node table header's magic is wrong: {source}"))]
   IncorrectNodeTableHeaderMagic {
-    expected: [u8; 4],
-    actual: [u8; 4],
-    backtrace: Backtrace,
+    source: MagicMismatch,
   },
   IncorrectNodeTableHeaderLength {
     expected: u16,
@@ -65,10 +75,9 @@ pub enum ReadError {
     range: Range<u32>,
     backtrace: Backtrace,
   },
+  #[snafu(display("the name table header's magic is wrong: {source}"))]
   IncorrectNameTableHeaderMagic {
-    expected: [u8; 4],
-    actual: [u8; 4],
-    backtrace: Backtrace,
+    source: MagicMismatch,
   },
   IncorrectNameTableHeaderLength {
     expected: u16,
@@ -85,36 +94,130 @@ pub enum ReadError {
     offset: u32,
     backtrace: Backtrace,
   },
+  /// Only returned by [`SarcReader::new_validated`].
+  #[snafu(display(
+    "the node at 0x{offset:X} stores hash 0x{actual:08X}, but its name hashes to 0x{expected:08X}"
+  ))]
+  HashMismatch {
+    offset: u32,
+    expected: u32,
+    actual: u32,
+    backtrace: Backtrace,
+  },
+}
+
+#[derive(Snafu, Debug)]
+#[snafu(module)]
+pub enum VerifyError {
+  #[snafu(display("{} node name(s) did not match their stored hash", mismatches.len()))]
+  HashMismatch {
+    mismatches: Vec<HashMismatch>,
+    backtrace: Backtrace,
+  },
+}
+
+#[derive(Debug)]
+pub struct HashMismatch {
+  pub index: usize,
+  pub name: String,
+  pub stored: u32,
+  pub computed: u32,
+}
+
+/// A file and the padding before it, see [`SarcReader::stored_files`].
+pub(crate) struct StoredFile<'a> {
+  pub name: Option<&'a CStr>,
+  pub hash: u32,
+  /// The smallest power of two that reproduces the padding before the file's data.
+  pub alignment: u32,
+  pub data: &'a [u8],
 }
 
 pub struct SarcReader<'a, O: ByteOrder> {
+  hash_key: u32,
+  data_start: u32,
+  name_table_end: u32,
   file_data: &'a [u8],
   name_data: &'a [u8],
   nodes: &'a [SfatNode<O>],
+  /// Whether the nodes are sorted by hash, as archives should be, so they can be binary searched.
+  sorted: bool,
   phantom: PhantomData<O>,
 }
 
 impl<'a, O: ByteOrder> SarcReader<'a, O> {
   pub fn new(data: &'a [u8]) -> Result<Self, ReadError> {
+    trace::enter_span!("sarc::open", size = data.len());
+    Self::open(data).trace_error()
+  }
+
+  /// Like [`Self::new`], but also checks each named node's hash against its name, failing on the
+  /// first mismatch. [`Self::verify`] lists every mismatch in an archive that's already open.
+  pub fn new_validated(data: &'a [u8]) -> Result<Self, ReadError> {
+    trace::enter_span!("sarc::open", size = data.len());
+    Self::open(data)
+      .and_then(|reader| reader.check_hashes().map(|()| reader))
+      .trace_error()
+  }
+
+  fn check_hashes(&self) -> Result<(), ReadError> {
+    if let Some(mismatch) = self.hash_mismatches().next() {
+      let nodes_start = size_of::<SarcHeader<O>>() + size_of::<SfatHeader<O>>();
+      return HashMismatchSnafu {
+        offset: (nodes_start + mismatch.index * size_of::<SfatNode<O>>()) as u32,
+        expected: mismatch.computed,
+        actual: mismatch.stored,
+      }
+      .fail();
+    }
+
+    Ok(())
+  }
+
+  /// Every named node whose stored hash doesn't match the hash of its name, in node order.
+  fn hash_mismatches(&self) -> impl Iterator<Item = HashMismatch> + '_ {
+    self.nodes.iter().enumerate().filter_map(|(index, node)| {
+      // nameless nodes can only be looked up by their hash, so there's nothing to check
+      let name = node_name(self.name_data, node)?;
+      let stored = node.file_name_hash.get();
+      let computed = name_hash(name.to_bytes(), self.hash_key);
+      (stored != computed).then(|| HashMismatch {
+        index,
+        name: name.to_string_lossy().into_owned(),
+        stored,
+        computed,
+      })
+    })
+  }
+
+  fn open(data: &'a [u8]) -> Result<Self, ReadError> {
     assert!(
       size_of::<usize>() >= 4,
       "cannot be executed on 16 bit platforms"
     );
     ensure!(
-      data.len() > size_of::<SarcHeader<O>>(),
+      data.len() >= size_of::<SarcHeader<O>>(),
       HeaderOutOfBoundsSnafu
     );
     let sarc_header = SarcHeader::<O>::ref_from_bytes(&data[..size_of::<SarcHeader<O>>()]).unwrap();
+    check_magic(sarc_header.magic, magic::SARC).context(IncorrectHeaderMagicSnafu)?;
+
     ensure!(
-      sarc_header.magic == *b"SARC",
-      IncorrectHeaderMagicSnafu {
-        expected: *b"SARC",
-        actual: sarc_header.magic
+      sarc_header.header_length.get() == 0x14,
+      IncorrectHeaderLengthSnafu {
+        expected: 0x14u16,
+        actual: sarc_header.header_length.get()
+      }
+    );
+    ensure!(
+      sarc_header.version.get() == 0x0100,
+      UnsupportedVersionSnafu {
+        actual: sarc_header.version.get()
       }
     );
 
     ensure!(
-      (sarc_header.data_start.get() as usize) < data.len()
+      (sarc_header.data_start.get() as usize) <= data.len()
         && sarc_header
           .data_start
           .get()
@@ -130,18 +233,12 @@ impl<'a, O: ByteOrder> SarcReader<'a, O> {
 
     let mut offset = size_of::<SarcHeader<O>>();
     ensure!(
-      data.len() > offset + size_of::<SfatHeader<O>>(),
+      data.len() >= offset + size_of::<SfatHeader<O>>(),
       NodeTableHeaderOutOfBoundsSnafu
     );
     let sfat_header =
       SfatHeader::<O>::ref_from_bytes(&data[offset..offset + size_of::<SfatHeader<O>>()]).unwrap();
-    ensure!(
-      sfat_header.magic == *b"SFAT",
-      IncorrectNodeTableHeaderMagicSnafu {
-        expected: *b"SFAT",
-        actual: sfat_header.magic
-      }
-    );
+    check_magic(sfat_header.magic, magic::SFAT).context(IncorrectNodeTableHeaderMagicSnafu)?;
     ensure!(
       sfat_header.header_length.get() == 0xC,
       IncorrectNodeTableHeaderLengthSnafu {
@@ -153,7 +250,7 @@ impl<'a, O: ByteOrder> SarcReader<'a, O> {
 
     let node_count = sfat_header.node_count.get() as usize;
     ensure!(
-      data.len() > offset + size_of::<SfatNode::<O>>() * node_count as usize,
+      data.len() >= offset + size_of::<SfatNode::<O>>() * node_count as usize,
       NodeOutOfBoundsSnafu {
         range: offset as u32..(offset + size_of::<SfatNode::<O>>()) as u32 * node_count as u32
       }
@@ -166,7 +263,7 @@ impl<'a, O: ByteOrder> SarcReader<'a, O> {
     offset += size_of::<SfatNode<O>>() * node_count as usize;
 
     ensure!(
-      data.len() > offset + size_of::<SfntHeader<O>>(),
+      data.len() >= offset + size_of::<SfntHeader<O>>(),
       NameTableHeaderOutOfBoundsSnafu {
         range: offset as u32..(offset + size_of::<SfntHeader<O>>()) as u32
       }
@@ -174,13 +271,7 @@ impl<'a, O: ByteOrder> SarcReader<'a, O> {
 
     let sfnt_header =
       SfntHeader::<O>::ref_from_bytes(&data[offset..offset + size_of::<SfntHeader<O>>()]).unwrap();
-    ensure!(
-      sfnt_header.magic == *b"SFNT",
-      IncorrectNodeTableHeaderMagicSnafu {
-        expected: *b"SFNT",
-        actual: sfnt_header.magic
-      }
-    );
+    check_magic(sfnt_header.magic, magic::SFNT).context(IncorrectNameTableHeaderMagicSnafu)?;
     ensure!(
       sfnt_header.header_length.get() == 0x8,
       IncorrectNameTableHeaderLengthSnafu {
@@ -191,6 +282,7 @@ impl<'a, O: ByteOrder> SarcReader<'a, O> {
     offset += size_of::<SfntHeader<O>>();
     let name_data = &data[offset..];
 
+    let mut name_table_end = offset;
     for node in nodes {
       if let Some(name_offset) = node.name_offset() {
         let name_start = offset + name_offset as usize;
@@ -207,7 +299,8 @@ impl<'a, O: ByteOrder> SarcReader<'a, O> {
           NameOutOfBoundsSnafu {
             offset: name_offset
           }
-        )
+        );
+        name_table_end = name_table_end.max(name_start + cstr.unwrap().count_bytes() + 1);
       }
 
       ensure!(
@@ -221,29 +314,331 @@ impl<'a, O: ByteOrder> SarcReader<'a, O> {
     }
 
     Ok(Self {
+      hash_key: sfat_header.hash_key.get(),
+      data_start: sarc_header.data_start.get(),
+      name_table_end: name_table_end as u32,
       file_data,
       name_data,
       nodes,
+      sorted: nodes.is_sorted_by_key(|node| node.file_name_hash.get()),
       phantom: PhantomData,
     })
   }
 
+  /// The multiplier the archive's name hashes were computed with.
+  pub fn hash_key(&self) -> u32 {
+    self.hash_key
+  }
+
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// The end of the last byte the archive references, either the end of the name table or of
+  /// the last file. Anything in the buffer past this is trailing data.
+  pub fn consumed_len(&self) -> usize {
+    let files_end = self
+      .nodes
+      .iter()
+      .map(|node| self.data_start as usize + node.relative_file_end.get() as usize)
+      .max()
+      .unwrap_or_default();
+
+    (self.name_table_end as usize).max(files_end)
+  }
+
+  /// Finds a file by name, binary searching the nodes for the name's hash and only comparing
+  /// names on collisions. Archives whose nodes aren't sorted by hash are searched linearly.
   pub fn get(&self, search_name: &str) -> Option<&'a [u8]> {
-    self.entries().find_map(|(name, data)| {
-      name.and_then(|name| name.to_bytes().eq(search_name.as_bytes()).then_some(data))
-    })
+    let is_match = |node: &SfatNode<O>| {
+      node_name(self.name_data, node)
+        .is_some_and(|name| name.to_bytes() == search_name.as_bytes())
+        .then(|| node_data(self.file_data, node))
+    };
+    if !self.sorted {
+      return self.nodes.iter().find_map(is_match);
+    }
+
+    let hash = compute_hash(search_name, self.hash_key);
+    let start = self
+      .nodes
+      .partition_point(|node| node.file_name_hash.get() < hash);
+    self.nodes[start..]
+      .iter()
+      .take_while(|node| node.file_name_hash.get() == hash)
+      .find_map(is_match)
+  }
+
+  /// The name and data of the file at `index` in node order, the same order as
+  /// [`Self::entries`]. `None` if `index` is out of range.
+  pub fn get_by_index(&self, index: usize) -> Option<(Option<&'a CStr>, &'a [u8])> {
+    let node = self.nodes.get(index)?;
+    Some((
+      node_name(self.name_data, node),
+      node_data(self.file_data, node),
+    ))
+  }
+
+  /// Recomputes the hash of every named node and checks it against the stored one.
+  pub fn verify(&self) -> Result<(), VerifyError> {
+    let mismatches = self.hash_mismatches().collect::<Vec<_>>();
+    ensure!(
+      mismatches.is_empty(),
+      verify_error::HashMismatchSnafu { mismatches }
+    );
+
+    Ok(())
+  }
+
+  /// Infers the alignment the archive was built with from the padding before each file.
+  ///
+  /// This is the smallest power of two that reproduces the padding after the name table and
+  /// between consecutive files, falling back to the largest alignment every file start shares.
+  pub fn data_alignment(&self) -> u32 {
+    let mut files = self
+      .nodes
+      .iter()
+      .map(|node| {
+        (
+          self.data_start + node.relative_file_start.get(),
+          self.data_start + node.relative_file_end.get(),
+        )
+      })
+      .collect::<Vec<_>>();
+    files.sort_unstable();
+
+    let shared = files
+      .iter()
+      .fold(self.data_start, |shared, (start, _)| shared | start);
+    let largest = match shared {
+      0 => 1u32 << 31,
+      shared => 1u32 << shared.trailing_zeros(),
+    };
+
+    let mut gaps = vec![(self.name_table_end, self.data_start)];
+    gaps.extend(files.windows(2).map(|pair| (pair[0].1, pair[1].0)));
+
+    (0..=largest.trailing_zeros())
+      .map(|shift| 1u32 << shift)
+      .find(|&alignment| {
+        gaps
+          .iter()
+          .all(|&(end, start)| end.next_multiple_of(alignment) == start)
+      })
+      .unwrap_or(largest)
+  }
+
+  /// The archive's files in the order their data is stored, which can differ from the node order.
+  pub(crate) fn stored_files(&self) -> Vec<StoredFile<'a>> {
+    let mut nodes = self.nodes.iter().zip(self.entries()).collect::<Vec<_>>();
+    nodes.sort_by_key(|(node, _)| (node.relative_file_start.get(), node.relative_file_end.get()));
+
+    let mut previous_end = self.name_table_end;
+    nodes
+      .into_iter()
+      .map(|(node, (name, data))| {
+        let start = self.data_start + node.relative_file_start.get();
+        let alignment = (0..32)
+          .map(|shift| 1u32 << shift)
+          .find(|&alignment| previous_end.checked_next_multiple_of(alignment) == Some(start))
+          .unwrap_or(1 << start.trailing_zeros().min(31));
+        previous_end = self.data_start + node.relative_file_end.get();
+
+        StoredFile {
+          name,
+          hash: node.file_name_hash.get(),
+          alignment,
+          data,
+        }
+      })
+      .collect()
+  }
+
+  /// Describes the archive's layout, with its files in the order their data is stored.
+  #[cfg(feature = "manifest")]
+  pub fn manifest(&self) -> SarcManifest {
+    let entries = self
+      .stored_files()
+      .into_iter()
+      .map(|file| ManifestEntry {
+        name: file.name.map(|name| name.to_string_lossy().into_owned()),
+        hash: file.hash,
+        alignment: file.alignment,
+      })
+      .collect();
+
+    SarcManifest {
+      hash_key: self.hash_key,
+      entries,
+    }
+  }
+
+  /// Writes [`Self::manifest`] as JSON, see [`SarcManifest::write`].
+  #[cfg(feature = "manifest")]
+  pub fn write_manifest(&self, w: &mut impl Write) -> io::Result<()> {
+    self.manifest().write(w)
+  }
+
+  pub fn entries(&self) -> Entries<'a, O> {
+    Entries {
+      nodes: self.nodes.iter(),
+      name_data: self.name_data,
+      file_data: self.file_data,
+    }
+  }
+}
+
+impl<'a, O: ByteOrder> IntoIterator for &SarcReader<'a, O> {
+  type Item = (Option<&'a CStr>, &'a [u8]);
+  type IntoIter = Entries<'a, O>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.entries()
+  }
+}
+
+fn node_name<'a, O: ByteOrder>(name_data: &'a [u8], node: &SfatNode<O>) -> Option<&'a CStr> {
+  node.name_offset().map(|name_offset| {
+    CStr::from_bytes_until_nul(&name_data[name_offset as usize..])
+      .expect("poorly asserted name during parsing and validation")
+  })
+}
+
+fn node_data<'a, O: ByteOrder>(file_data: &'a [u8], node: &SfatNode<O>) -> &'a [u8] {
+  &file_data[node.relative_file_start.get() as usize..node.relative_file_end.get() as usize]
+}
+
+/// The names and data of an archive's files in node order, see [`SarcReader::entries`].
+pub struct Entries<'a, O: ByteOrder> {
+  nodes: slice::Iter<'a, SfatNode<O>>,
+  name_data: &'a [u8],
+  file_data: &'a [u8],
+}
+
+impl<'a, O: ByteOrder> Iterator for Entries<'a, O> {
+  type Item = (Option<&'a CStr>, &'a [u8]);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let node = self.nodes.next()?;
+    Some((
+      node_name(self.name_data, node),
+      node_data(self.file_data, node),
+    ))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.nodes.size_hint()
+  }
+}
+
+impl<O: ByteOrder> ExactSizeIterator for Entries<'_, O> {}
+
+impl<'a, O: ByteOrder> TryFrom<&'a [u8]> for SarcReader<'a, O> {
+  type Error = ReadError;
+
+  fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+    Self::new(data)
+  }
+}
+
+/// A [`SarcReader`] whose byte order is picked from the archive's byte order mark,
+/// for code that shouldn't have to be generic over it.
+pub enum SarcArchive<'a> {
+  LittleEndian(SarcReader<'a, LittleEndian>),
+  BigEndian(SarcReader<'a, BigEndian>),
+}
+
+impl<'a> SarcArchive<'a> {
+  pub fn new(data: &'a [u8]) -> Result<Self, ReadError> {
+    match *data.get(6..8).context(HeaderOutOfBoundsSnafu)? {
+      [0xFF, 0xFE] => SarcReader::new(data).map(Self::LittleEndian),
+      [0xFE, 0xFF] => SarcReader::new(data).map(Self::BigEndian),
+      [a, b] => InvalidByteOrderMarkSnafu { actual: [a, b] }.fail(),
+      _ => unreachable!(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    match self {
+      Self::LittleEndian(reader) => reader.len(),
+      Self::BigEndian(reader) => reader.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    match self {
+      Self::LittleEndian(reader) => reader.is_empty(),
+      Self::BigEndian(reader) => reader.is_empty(),
+    }
+  }
+
+  pub fn get(&self, search_name: &str) -> Option<&'a [u8]> {
+    match self {
+      Self::LittleEndian(reader) => reader.get(search_name),
+      Self::BigEndian(reader) => reader.get(search_name),
+    }
+  }
+
+  pub fn get_by_index(&self, index: usize) -> Option<(Option<&'a CStr>, &'a [u8])> {
+    match self {
+      Self::LittleEndian(reader) => reader.get_by_index(index),
+      Self::BigEndian(reader) => reader.get_by_index(index),
+    }
+  }
+
+  pub fn verify(&self) -> Result<(), VerifyError> {
+    match self {
+      Self::LittleEndian(reader) => reader.verify(),
+      Self::BigEndian(reader) => reader.verify(),
+    }
+  }
+
+  pub fn consumed_len(&self) -> usize {
+    match self {
+      Self::LittleEndian(reader) => reader.consumed_len(),
+      Self::BigEndian(reader) => reader.consumed_len(),
+    }
+  }
+
+  pub fn data_alignment(&self) -> u32 {
+    match self {
+      Self::LittleEndian(reader) => reader.data_alignment(),
+      Self::BigEndian(reader) => reader.data_alignment(),
+    }
+  }
+
+  #[cfg(feature = "manifest")]
+  pub fn manifest(&self) -> SarcManifest {
+    match self {
+      Self::LittleEndian(reader) => reader.manifest(),
+      Self::BigEndian(reader) => reader.manifest(),
+    }
+  }
+
+  #[cfg(feature = "manifest")]
+  pub fn write_manifest(&self, w: &mut impl Write) -> io::Result<()> {
+    match self {
+      Self::LittleEndian(reader) => reader.write_manifest(w),
+      Self::BigEndian(reader) => reader.write_manifest(w),
+    }
   }
 
   pub fn entries(&self) -> impl Iterator<Item = (Option<&'a CStr>, &'a [u8])> {
-    self.nodes.iter().map(|node| {
-      (
-        node.name_offset().map(|name_offset| {
-          CStr::from_bytes_until_nul(&self.name_data[name_offset as usize..])
-            .expect("poorly asserted name during parsing and validation")
-        }),
-        &self.file_data
-          [node.relative_file_start.get() as usize..node.relative_file_end.get() as usize],
-      )
-    })
+    match self {
+      Self::LittleEndian(reader) => Either::Left(reader.entries()),
+      Self::BigEndian(reader) => Either::Right(reader.entries()),
+    }
+  }
+}
+
+impl<'a> TryFrom<&'a [u8]> for SarcArchive<'a> {
+  type Error = ReadError;
+
+  fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+    Self::new(data)
   }
 }
@@ -30,10 +30,42 @@ pub struct SfatNode<O: ByteOrder> {
   pub relative_file_end: U32<O>,
 }
 
+/// Set in [`SfatNode::file_attributes`] when the node has a name in the name table.
+pub const NAME_PRESENT_FLAG: u32 = 0x01000000;
+/// The low bits of [`SfatNode::file_attributes`] hold the name's offset divided by 4.
+pub const NAME_OFFSET_MASK: u32 = 0xFFFF;
+
 impl<O: ByteOrder> SfatNode<O> {
+  /// Builds a node whose name starts `name_offset` bytes into the name table.
+  ///
+  /// Names are 4 byte aligned, so this returns `None` if the offset is misaligned or too large
+  /// to be stored.
+  pub fn named(hash: u32, name_offset: u32, start: u32, end: u32) -> Option<Self> {
+    if !name_offset.is_multiple_of(4) || name_offset / 4 > NAME_OFFSET_MASK {
+      return None;
+    }
+
+    Some(Self {
+      file_name_hash: U32::new(hash),
+      file_attributes: U32::new(NAME_PRESENT_FLAG | (name_offset / 4)),
+      relative_file_start: U32::new(start),
+      relative_file_end: U32::new(end),
+    })
+  }
+
+  /// Builds a node that is only identified by its hash.
+  pub fn nameless(hash: u32, start: u32, end: u32) -> Self {
+    Self {
+      file_name_hash: U32::new(hash),
+      file_attributes: U32::new(0),
+      relative_file_start: U32::new(start),
+      relative_file_end: U32::new(end),
+    }
+  }
+
   pub fn name_offset(&self) -> Option<u32> {
-    if self.file_attributes.get() & 0x01000000 != 0 {
-      Some((self.file_attributes.get() & 0xFFFF) * 4)
+    if self.file_attributes.get() & NAME_PRESENT_FLAG != 0 {
+      Some((self.file_attributes.get() & NAME_OFFSET_MASK) * 4)
     } else {
       None
     }
@@ -1,3 +1,261 @@
-pub struct SarcWriter {
+use std::{io::Write, marker::PhantomData};
 
-}
\ No newline at end of file
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu, ensure};
+use zerocopy::{ByteOrder, FromZeros, IntoBytes, U16, U32};
+
+#[cfg(feature = "manifest")]
+use crate::sarc::manifest::{ManifestEntry, SarcManifest};
+use crate::{
+  magic,
+  sarc::{
+    DEFAULT_HASH_KEY, name_hash,
+    reader::SarcReader,
+    types::{SarcHeader, SfatHeader, SfatNode, SfntHeader},
+  },
+  util::align_up,
+  yaz0::{self, CompressionError},
+};
+
+#[derive(Snafu, Debug)]
+pub enum WriteError {
+  #[snafu(display("error while writing: {source}"))]
+  Io {
+    source: std::io::Error,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("archive has {count} files, more than a node table can hold"))]
+  TooManyFiles { count: usize, backtrace: Backtrace },
+  #[snafu(display("file {index} has an alignment of {alignment}, which isn't a power of two"))]
+  InvalidAlignment {
+    index: usize,
+    alignment: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display("name {name:?} starts too far into the name table to be stored"))]
+  NameTableTooLarge { name: String, backtrace: Backtrace },
+  #[snafu(display("archive is too large, files can't be addressed past 4 GiB"))]
+  ArchiveTooLarge { backtrace: Backtrace },
+  #[snafu(display("error while compressing: {source}"))]
+  Compression {
+    #[snafu(backtrace)]
+    source: CompressionError,
+  },
+}
+
+struct File {
+  name: Option<String>,
+  hash: u32,
+  alignment: u32,
+  data: Vec<u8>,
+}
+
+pub struct SarcWriter<O: ByteOrder> {
+  hash_key: u32,
+  files: Vec<File>,
+  phantom: PhantomData<O>,
+}
+
+/// The alignment [`SarcWriter::add_file`] and [`SarcWriter::add_unnamed`] give file data.
+pub const DEFAULT_ALIGNMENT: u32 = 4;
+
+impl<O: ByteOrder> SarcWriter<O> {
+  /// An empty archive whose names are hashed with [`DEFAULT_HASH_KEY`].
+  pub fn new() -> Self {
+    Self::with_hash_key(DEFAULT_HASH_KEY)
+  }
+
+  pub fn with_hash_key(hash_key: u32) -> Self {
+    Self {
+      hash_key,
+      files: Vec::new(),
+      phantom: PhantomData,
+    }
+  }
+
+  /// Adds a file under `name`, aligned to [`DEFAULT_ALIGNMENT`]. Names should be unique, as
+  /// readers look files up by their hash.
+  pub fn add_file(&mut self, name: &str, data: &[u8]) {
+    self.add_file_aligned(name, data, DEFAULT_ALIGNMENT);
+  }
+
+  /// Like [`Self::add_file`], for formats that have to be aligned further in memory, such as
+  /// BFRES. `alignment` has to be a power of two, or writing fails.
+  pub fn add_file_aligned(&mut self, name: &str, data: &[u8], alignment: u32) {
+    self.files.push(File {
+      name: Some(name.to_owned()),
+      hash: name_hash(name.as_bytes(), self.hash_key),
+      alignment,
+      data: data.to_vec(),
+    });
+  }
+
+  /// Adds a file without a name, found only by `hash`.
+  pub fn add_unnamed(&mut self, hash: u32, data: &[u8]) {
+    self.files.push(File {
+      name: None,
+      hash,
+      alignment: DEFAULT_ALIGNMENT,
+      data: data.to_vec(),
+    });
+  }
+
+  /// Copies every file out of an archive, keeping the order their data is stored in and the
+  /// padding before each one, so that writing it reproduces the archive byte for byte.
+  ///
+  /// Names that aren't valid UTF-8 are converted lossily, which is the one case where the
+  /// rebuilt archive differs.
+  pub fn from_reader(reader: &SarcReader<'_, O>) -> Self {
+    let files = reader
+      .stored_files()
+      .into_iter()
+      .map(|file| File {
+        name: file.name.map(|name| name.to_string_lossy().into_owned()),
+        hash: file.hash,
+        alignment: file.alignment,
+        data: file.data.to_vec(),
+      })
+      .collect();
+
+    Self {
+      hash_key: reader.hash_key(),
+      files,
+      phantom: PhantomData,
+    }
+  }
+
+  /// Rebuilds the archive a manifest was taken from, asking `read_file` for each entry's data.
+  #[cfg(feature = "manifest")]
+  pub fn from_manifest<E>(
+    manifest: &SarcManifest,
+    mut read_file: impl FnMut(&ManifestEntry) -> Result<Vec<u8>, E>,
+  ) -> Result<Self, E> {
+    let files = manifest
+      .entries
+      .iter()
+      .map(|entry| {
+        Ok(File {
+          name: entry.name.clone(),
+          hash: entry.hash,
+          alignment: entry.alignment,
+          data: read_file(entry)?,
+        })
+      })
+      .collect::<Result<_, E>>()?;
+
+    Ok(Self {
+      hash_key: manifest.hash_key,
+      files,
+      phantom: PhantomData,
+    })
+  }
+
+  /// Writes the archive. Nodes are sorted by hash, while file data keeps the order the files
+  /// were added in, each aligned to its own alignment.
+  pub fn write(&self, w: &mut impl Write) -> Result<(), WriteError> {
+    ensure!(
+      self.files.len() <= u16::MAX as usize,
+      TooManyFilesSnafu {
+        count: self.files.len()
+      }
+    );
+    for (index, file) in self.files.iter().enumerate() {
+      ensure!(
+        file.alignment.is_power_of_two(),
+        InvalidAlignmentSnafu {
+          index,
+          alignment: file.alignment
+        }
+      );
+    }
+
+    let mut node_order = (0..self.files.len()).collect::<Vec<_>>();
+    node_order.sort_by_key(|&index| self.files[index].hash);
+
+    let mut names = Vec::new();
+    let mut name_offsets = vec![None; self.files.len()];
+    for &index in &node_order {
+      if let Some(name) = &self.files[index].name {
+        name_offsets[index] = Some(names.len());
+        names.extend_from_slice(name.as_bytes());
+        names.push(0);
+        names.resize(align_up(names.len(), 4), 0);
+      }
+    }
+
+    let name_table_end = size_of::<SarcHeader<O>>()
+      + size_of::<SfatHeader<O>>()
+      + size_of::<SfatNode<O>>() * self.files.len()
+      + size_of::<SfntHeader<O>>()
+      + names.len();
+
+    let mut end = name_table_end;
+    let mut starts = Vec::with_capacity(self.files.len());
+    for file in &self.files {
+      let start = align_up(end, file.alignment as usize);
+      starts.push(start);
+      end = start + file.data.len();
+    }
+    let data_start = starts.first().copied().unwrap_or(name_table_end);
+    let file_size = u32::try_from(end).ok().context(ArchiveTooLargeSnafu)?;
+
+    let mut data = Vec::with_capacity(end);
+
+    let mut header = SarcHeader::<O>::new_zeroed();
+    header.magic = magic::SARC;
+    header.header_length = U16::new(0x14);
+    header.byte_order_mark = U16::<O>::new(0xFEFF).to_bytes();
+    header.file_size = U32::new(file_size);
+    header.data_start = U32::new(data_start as u32);
+    header.version = U16::new(0x0100);
+    data.extend_from_slice(header.as_bytes());
+
+    let sfat_header = SfatHeader::<O> {
+      magic: magic::SFAT,
+      header_length: U16::new(0xC),
+      node_count: U16::new(self.files.len() as u16),
+      hash_key: U32::new(self.hash_key),
+    };
+    data.extend_from_slice(sfat_header.as_bytes());
+
+    for &index in &node_order {
+      let file = &self.files[index];
+      let start = (starts[index] - data_start) as u32;
+      let end = start + file.data.len() as u32;
+      let node = match (&file.name, name_offsets[index]) {
+        (Some(name), Some(name_offset)) => u32::try_from(name_offset)
+          .ok()
+          .and_then(|name_offset| SfatNode::<O>::named(file.hash, name_offset, start, end))
+          .context(NameTableTooLargeSnafu { name })?,
+        _ => SfatNode::nameless(file.hash, start, end),
+      };
+      data.extend_from_slice(node.as_bytes());
+    }
+
+    let mut sfnt_header = SfntHeader::<O>::new_zeroed();
+    sfnt_header.magic = magic::SFNT;
+    sfnt_header.header_length = U16::new(0x8);
+    data.extend_from_slice(sfnt_header.as_bytes());
+    data.extend_from_slice(&names);
+
+    for (file, start) in self.files.iter().zip(starts) {
+      data.resize(start, 0);
+      data.extend_from_slice(&file.data);
+    }
+
+    w.write_all(&data).context(IoSnafu)
+  }
+
+  /// Writes the archive and Yaz0-compresses it into `w`, producing a `.szs`. `window` is passed
+  /// to [`yaz0::compress`].
+  pub fn write_compressed(&self, w: &mut impl Write, window: u16) -> Result<(), WriteError> {
+    let mut data = Vec::new();
+    self.write(&mut data)?;
+    yaz0::compress(&mut data.as_slice(), w, window).context(CompressionSnafu)
+  }
+}
+
+impl<O: ByteOrder> Default for SarcWriter<O> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,46 @@
+//! Diagnostics that go through `tracing` when the `tracing` feature is enabled, and compile to
+//! nothing otherwise. The `trace` feature also forwards them to the `log` crate, for programs
+//! that set up a `log` logger rather than a `tracing` subscriber.
+
+use std::fmt::Display;
+
+/// Enters a debug level span that lasts until the end of the enclosing block.
+macro_rules! enter_span {
+  ($($arg:tt)*) => {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!($($arg)*).entered();
+  };
+}
+
+macro_rules! debug {
+  ($($arg:tt)*) => {
+    #[cfg(feature = "tracing")]
+    tracing::debug!($($arg)*);
+  };
+}
+
+macro_rules! trace {
+  ($($arg:tt)*) => {
+    #[cfg(feature = "tracing")]
+    tracing::trace!($($arg)*);
+  };
+}
+
+pub(crate) use {debug, enter_span, trace};
+
+pub(crate) trait TraceError {
+  /// Emits a debug event for the error, if there is one.
+  fn trace_error(self) -> Self;
+}
+
+impl<T, E: Display> TraceError for Result<T, E> {
+  #[inline]
+  fn trace_error(self) -> Self {
+    #[cfg(feature = "tracing")]
+    if let Err(error) = &self {
+      tracing::debug!(%error, "failed to decode");
+    }
+
+    self
+  }
+}
@@ -1,44 +1,26 @@
 use num_traits::PrimInt;
 
-use crate::byml::reader::BymlReader;
-
+/// Rounds `value` up to the next multiple of `alignment`, which must be a power of two.
+/// Use [`round_up_to_multiple`] for anything else.
 pub fn align_up<T: PrimInt>(value: T, alignment: T) -> T {
+  debug_assert!(
+    alignment.count_ones() == 1,
+    "alignment must be a power of two"
+  );
   (value + alignment - T::one()) & !(alignment - T::one())
 }
 
-#[derive(Debug)]
-pub enum Order {
-  LittleEndian,
-  BigEndian,
-}
-
-pub trait DetectEndianness {
-  type Error;
-  fn detect() -> Result<Order, Self::Error>;
-}
-
-struct DetectEndian;
-
-impl<'a> DetectEndianness for BymlReader<'a, DetectEndian> {
-  type Error = ();
-
-  fn detect() -> Result<Order, Self::Error> {
-    todo!()
+/// Rounds `value` up to the next multiple of `multiple`, which can be any non-zero number.
+pub fn round_up_to_multiple<T: PrimInt>(value: T, multiple: T) -> T {
+  match value % multiple {
+    remainder if remainder.is_zero() => value,
+    remainder => value + (multiple - remainder),
   }
 }
 
-macro_rules! read_endian_agnostic {
-  () => {
-    
-  };
+/// A byte order picked at runtime, where [`zerocopy::ByteOrder`] would pick it at compile time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+  LittleEndian,
+  BigEndian,
 }
-
-// fn testing() {
-//   let byml: ;
-//   match BymlReader::<DetectEndian>::detect().unwrap() {
-//     Order::LittleEndian => {
-//       |reader| -> Result<(), BymlReader<>
-//     },
-//     Order::BigEndian => todo!(),
-//   }
-// }
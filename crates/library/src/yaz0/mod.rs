@@ -1,20 +1,26 @@
 #![allow(dead_code)]
 
 use std::{
-  io::{self, Read, Seek},
-  iter,
+  io::{self, Read, Seek, Write},
+  slice,
 };
 
 use modular_bitfield::bitfield;
-use snafu::{ensure, Backtrace, GenerateImplicitData, OptionExt, Snafu};
+use snafu::{ensure, Backtrace, GenerateImplicitData, OptionExt, ResultExt, Snafu};
 use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout, big_endian::U32};
 
+use crate::{
+  magic::{self, MagicMismatch, check_magic},
+  trace::{self, TraceError},
+};
+
 #[derive(FromBytes, IntoBytes, Immutable, KnownLayout)]
 #[repr(C)]
 struct Header {
   magic: [u8; 4],
   uncompressed_size: U32,
-  _unused: [u8; 8],
+  alignment: U32,
+  _unused: [u8; 4],
 }
 
 #[derive(Snafu, Debug)]
@@ -24,12 +30,8 @@ pub enum DecompressionError {
     source: io::Error,
     backtrace: Backtrace,
   },
-  #[snafu(display("incorrect magic, expected {expected:02X?}, got {actual:02X?}"))]
-  IncorrectMagic {
-    expected: [u8; 4],
-    actual: [u8; 4],
-    backtrace: Backtrace,
-  },
+  #[snafu(display("incorrect magic: {source}"))]
+  IncorrectMagic { source: MagicMismatch },
   #[snafu(display(
     "attempted to copy {copy_count} bytes which would add data past the decompressed buffer's end {decompressed_size}, currently at {current_size}"
   ))]
@@ -47,6 +49,23 @@ pub enum DecompressionError {
     lookback_distance: u16,
     backtrace: Backtrace,
   },
+  #[snafu(display(
+    "the header claims {claimed} decompressed bytes, more than the limit of {limit}"
+  ))]
+  SizeLimitExceeded {
+    claimed: u32,
+    limit: u32,
+    backtrace: Backtrace,
+  },
+  #[snafu(display(
+    "{size} bytes of data follow the end of the stream at 0x{offset:X}, starting with a nonzero byte at 0x{first_nonzero:X}"
+  ))]
+  TrailingData {
+    offset: u64,
+    size: usize,
+    first_nonzero: u64,
+    backtrace: Backtrace,
+  },
 }
 
 impl From<io::Error> for DecompressionError {
@@ -59,19 +78,64 @@ impl From<io::Error> for DecompressionError {
   }
 }
 
-pub fn decompressed_size(reader: &mut impl Read) -> Result<u32, DecompressionError> {
+pub use compression_error::CompressionError;
+mod compression_error {
+  use std::io;
+
+  use snafu::{Backtrace, GenerateImplicitData, Snafu};
+
+  #[derive(Snafu, Debug)]
+  pub enum CompressionError {
+    #[snafu(display("error while compressing file: {source}"))]
+    Io {
+      source: io::Error,
+      backtrace: Backtrace,
+    },
+    #[snafu(display("input of {size} bytes is too large to be compressed"))]
+    InputTooLarge { size: usize, backtrace: Backtrace },
+    #[snafu(display("encoder expected {expected} bytes but only {written} were written"))]
+    IncompleteInput {
+      expected: u32,
+      written: u32,
+      backtrace: Backtrace,
+    },
+  }
+
+  impl From<io::Error> for CompressionError {
+    #[track_caller]
+    fn from(value: io::Error) -> Self {
+      Self::Io {
+        source: value,
+        backtrace: Backtrace::generate(),
+      }
+    }
+  }
+}
+
+/// What a Yaz0 header says about the data it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Yaz0Info {
+  pub uncompressed_size: u32,
+  /// The alignment the decompressed data needs in memory, such as a SARC's largest file
+  /// alignment. 0 if the encoder didn't specify one.
+  pub alignment: u32,
+}
+
+/// Reads the header, leaving `reader` positioned at the start of the compressed data.
+pub fn read_info(reader: &mut impl Read) -> Result<Yaz0Info, DecompressionError> {
   let mut header = Header::new_zeroed();
   reader.read_exact(header.as_mut_bytes())?;
 
-  ensure!(
-    header.magic == *b"Yaz0",
-    IncorrectMagicSnafu {
-      expected: *b"Yaz0",
-      actual: header.magic,
-    }
-  );
+  check_magic(header.magic, magic::YAZ0).context(IncorrectMagicSnafu)?;
 
-  Ok(header.uncompressed_size.get())
+  Ok(Yaz0Info {
+    uncompressed_size: header.uncompressed_size.get(),
+    alignment: header.alignment.get(),
+  })
+}
+
+pub fn decompressed_size(reader: &mut impl Read) -> Result<u32, DecompressionError> {
+  read_info(reader).map(|info| info.uncompressed_size)
 }
 
 use modular_bitfield::prelude::*;
@@ -138,13 +202,103 @@ impl Groups {
   }
 }
 
+/// Decompresses the whole stream, trusting the size in its header. This is
+/// [`decompress_with_limit`] without a limit.
 pub fn decompress(reader: &mut (impl Read + Seek)) -> Result<Box<[u8]>, DecompressionError> {
-  let decomp_size = decompressed_size(reader)?;
+  decompress_with_limit(reader, u32::MAX)
+}
+
+/// How many bytes are decoded between calls to a progress callback.
+pub const PROGRESS_INTERVAL: u32 = 0x10000;
+
+/// Like [`decompress`], but calls `on_progress` with how many bytes have been decoded and the
+/// total roughly every [`PROGRESS_INTERVAL`] bytes, and once more when decoding finishes.
+pub fn decompress_with_progress(
+  reader: &mut (impl Read + Seek),
+  on_progress: impl FnMut(u32, u32),
+) -> Result<Box<[u8]>, DecompressionError> {
+  let decomp_size = decompressed_size(reader).trace_error()?;
+  trace::enter_span!("yaz0::decompress", size = decomp_size);
+  decode(reader, decomp_size, on_progress).trace_error()
+}
+
+/// Like [`decompress`], but fails with [`DecompressionError::SizeLimitExceeded`] before allocating
+/// anything if the header claims more than `limit` bytes. [`decompress`] trusts the header, so a
+/// few bytes of untrusted input can make it reserve up to 4 GiB.
+pub fn decompress_with_limit(
+  reader: &mut (impl Read + Seek),
+  limit: u32,
+) -> Result<Box<[u8]>, DecompressionError> {
+  let decomp_size = decompressed_size(reader).trace_error()?;
+  trace::enter_span!("yaz0::decompress", size = decomp_size);
+  ensure!(
+    decomp_size <= limit,
+    SizeLimitExceededSnafu {
+      claimed: decomp_size,
+      limit
+    }
+  );
+  decode(reader, decomp_size, |_, _| {}).trace_error()
+}
+
+/// Decompresses the stream like [`decompress`], then checks that nothing but zero padding follows
+/// the last group it needed, returning [`DecompressionError::TrailingData`] otherwise.
+///
+/// Data after the claimed size is ignored when decompressing, so this catches files that decode
+/// correctly but would be rejected by stricter readers.
+pub fn validate(reader: &mut (impl Read + Seek)) -> Result<(), DecompressionError> {
+  let decomp_size = decompressed_size(reader).trace_error()?;
+  trace::enter_span!("yaz0::validate", size = decomp_size);
+  check_trailing_data(reader, decomp_size).trace_error()
+}
+
+fn check_trailing_data(
+  reader: &mut (impl Read + Seek),
+  decomp_size: u32,
+) -> Result<(), DecompressionError> {
+  decode(reader, decomp_size, |_, _| {})?;
+
+  let offset = reader.stream_position()?;
+  let mut trailing = Vec::new();
+  reader.read_to_end(&mut trailing)?;
+
+  // encoders commonly pad their output to an alignment with zeros
+  match trailing.iter().position(|&byte| byte != 0) {
+    Some(index) => TrailingDataSnafu {
+      offset,
+      size: trailing.len(),
+      first_nonzero: offset + index as u64,
+    }
+    .fail(),
+    None => Ok(()),
+  }
+}
+
+/// Decodes `decomp_size` bytes from a reader that is already positioned past the header.
+pub fn decompress_with_size(
+  reader: &mut impl Read,
+  decomp_size: u32,
+) -> Result<Box<[u8]>, DecompressionError> {
+  trace::enter_span!("yaz0::decompress", size = decomp_size);
+  decode(reader, decomp_size, |_, _| {}).trace_error()
+}
+
+fn decode(
+  reader: &mut impl Read,
+  decomp_size: u32,
+  mut on_progress: impl FnMut(u32, u32),
+) -> Result<Box<[u8]>, DecompressionError> {
   let mut decomp_data = Vec::with_capacity(decomp_size as _);
 
-  let mut read_buffer = [0u8; 3];
+  let mut read_buffer = [0u8; 1];
   let mut groups = Groups::empty();
+  let mut next_progress = PROGRESS_INTERVAL as usize;
   while decomp_data.len() < decomp_data.capacity() {
+    if decomp_data.len() >= next_progress {
+      on_progress(decomp_data.len() as u32, decomp_size);
+      next_progress = decomp_data.len() + PROGRESS_INTERVAL as usize;
+    }
+
     let current_group = if let Some(current_group) = groups.pop() {
       current_group
     } else {
@@ -152,35 +306,19 @@ pub fn decompress(reader: &mut (impl Read + Seek)) -> Result<Box<[u8]>, Decompre
       groups.refill_and_pop(read_buffer[0])
     };
 
-    //    println!("got {current_group:?}");
-
     match current_group {
       Group::Uncompressed => {
         reader.read_exact(&mut read_buffer[0..=0])?;
         decomp_data.push(read_buffer[0]);
       }
       Group::Copy => {
-        reader.read_exact(&mut read_buffer[0..=1])?;
-
-        //        println!(
-        //          "{:02X} {:02X} {:02X}",
-        //          read_buffer[0],
-        //          read_buffer[1],
-        //          read_buffer[0] & 0xF0
-        //        );
-        let (copy_count, lookback_distance) = if read_buffer[0] & 0xF0 == 0 {
-          reader.read_exact(&mut read_buffer[2..=2])?;
-          let long_copy = LongCopy::from_bytes(read_buffer);
-          let lookback_distance =
-            (long_copy.lookback_upper() as u16) << 8 | (long_copy.lookback_lower() as u16);
-          (long_copy.copy_count() as u16 + 0x12, lookback_distance + 1)
-        } else {
-          let short_copy = ShortCopy::from_bytes([read_buffer[0], read_buffer[1]]);
-          let lookback_distance =
-            (short_copy.lookback_upper() as u16) << 8 | (short_copy.lookback_lower() as u16);
-
-          (short_copy.copy_count() as u16 + 0x02, lookback_distance + 1)
-        };
+        let (copy_count, lookback_distance) = read_copy(reader)?;
+        trace::trace!(
+          offset = decomp_data.len(),
+          copy_count,
+          lookback_distance,
+          "copy"
+        );
 
         ensure!(
           decomp_data.len().saturating_add(copy_count as _) <= decomp_data.capacity(),
@@ -199,12 +337,395 @@ pub fn decompress(reader: &mut (impl Read + Seek)) -> Result<Box<[u8]>, Decompre
             lookback_distance,
           })?;
 
-        let dest_start = decomp_data.len();
-        decomp_data.extend(iter::repeat_n(0, copy_count as _));
-        decomp_data.copy_within(start..start + copy_count as usize, dest_start);
+        // copies can overlap the bytes they produce, so they have to be done one byte at a time
+        for index in start..start + copy_count as usize {
+          let byte = decomp_data[index];
+          decomp_data.push(byte);
+        }
       }
     }
   }
 
+  on_progress(decomp_size, decomp_size);
   Ok(decomp_data.into_boxed_slice())
 }
+
+/// Reads a copy's `(copy_count, lookback_distance)`.
+fn read_copy(reader: &mut impl Read) -> io::Result<(u16, u16)> {
+  let mut read_buffer = [0u8; 3];
+  reader.read_exact(&mut read_buffer[0..=1])?;
+
+  if read_buffer[0] & 0xF0 == 0 {
+    reader.read_exact(&mut read_buffer[2..=2])?;
+    let long_copy = LongCopy::from_bytes(read_buffer);
+    let lookback_distance =
+      (long_copy.lookback_upper() as u16) << 8 | (long_copy.lookback_lower() as u16);
+    Ok((long_copy.copy_count() as u16 + 0x12, lookback_distance + 1))
+  } else {
+    let short_copy = ShortCopy::from_bytes([read_buffer[0], read_buffer[1]]);
+    let lookback_distance =
+      (short_copy.lookback_upper() as u16) << 8 | (short_copy.lookback_lower() as u16);
+
+    Ok((short_copy.copy_count() as u16 + 0x02, lookback_distance + 1))
+  }
+}
+
+/// A streaming Yaz0 decompressor, so large `.szs` files can be piped with [`io::copy`] without
+/// holding the whole decompressed image in memory.
+///
+/// Only the last [`MAX_WINDOW`] decoded bytes are kept for copies to read from, and the source
+/// is never seeked. Malformed streams fail with [`io::ErrorKind::InvalidData`], wrapping the
+/// [`DecompressionError`] [`decompress`] would have returned.
+pub struct Yaz0Decoder<R: Read> {
+  reader: R,
+  decomp_size: u32,
+  decoded: u32,
+  // a ring buffer holding the last `MAX_WINDOW` bytes, the next byte goes at `decoded`
+  window: Box<[u8]>,
+  groups: Groups,
+  // the part of the current copy that hasn't been read out yet
+  copy_remaining: u16,
+  lookback_distance: u16,
+}
+
+impl<R: Read> Yaz0Decoder<R> {
+  /// Reads the header, leaving the rest of the stream to be decoded as it's read.
+  pub fn new(mut reader: R) -> Result<Self, DecompressionError> {
+    let decomp_size = decompressed_size(&mut reader).trace_error()?;
+
+    Ok(Self {
+      reader,
+      decomp_size,
+      decoded: 0,
+      window: vec![0; MAX_WINDOW as usize].into_boxed_slice(),
+      groups: Groups::empty(),
+      copy_remaining: 0,
+      lookback_distance: 0,
+    })
+  }
+
+  /// The size the header claims, which is how many bytes the decoder reads out in total.
+  pub fn decompressed_size(&self) -> u32 {
+    self.decomp_size
+  }
+
+  pub fn into_inner(self) -> R {
+    self.reader
+  }
+
+  fn push(&mut self, byte: u8) -> u8 {
+    self.window[self.decoded as usize % MAX_WINDOW as usize] = byte;
+    self.decoded += 1;
+    byte
+  }
+
+  /// Decodes the next byte, reading a new chunk from the source if the current copy is done.
+  fn next_byte(&mut self) -> Result<u8, DecompressionError> {
+    if self.copy_remaining == 0 {
+      let current_group = match self.groups.pop() {
+        Some(current_group) => current_group,
+        None => {
+          let mut value = 0;
+          self.reader.read_exact(slice::from_mut(&mut value))?;
+          self.groups.refill_and_pop(value)
+        }
+      };
+
+      match current_group {
+        Group::Uncompressed => {
+          let mut byte = 0;
+          self.reader.read_exact(slice::from_mut(&mut byte))?;
+          return Ok(self.push(byte));
+        }
+        Group::Copy => {
+          let (copy_count, lookback_distance) = read_copy(&mut self.reader)?;
+          ensure!(
+            self.decoded.saturating_add(copy_count as _) <= self.decomp_size,
+            CopyingPastEndSnafu {
+              copy_count,
+              current_size: self.decoded,
+              decompressed_size: self.decomp_size
+            }
+          );
+          ensure!(
+            lookback_distance as u32 <= self.decoded,
+            CopyingFromBeforeStartSnafu {
+              copy_count,
+              lookback_distance,
+            }
+          );
+          self.copy_remaining = copy_count;
+          self.lookback_distance = lookback_distance;
+        }
+      }
+    }
+
+    // the byte `lookback_distance` back hasn't been overwritten yet, as the distance is at most
+    // the window's size
+    let start = self.decoded - self.lookback_distance as u32;
+    self.copy_remaining -= 1;
+    Ok(self.push(self.window[start as usize % MAX_WINDOW as usize]))
+  }
+}
+
+impl<R: Read> Read for Yaz0Decoder<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let mut written = 0;
+    while written < buf.len() && self.decoded < self.decomp_size {
+      buf[written] = self.next_byte().map_err(|error| match error {
+        DecompressionError::Io { source, .. } => source,
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+      })?;
+      written += 1;
+    }
+
+    Ok(written)
+  }
+}
+
+/// The largest lookback distance a copy can encode.
+pub const MAX_WINDOW: u16 = 0x1000;
+const MIN_COPY: usize = 0x3;
+const MAX_SHORT_COPY: usize = 0x11;
+const MAX_LONG_COPY: usize = 0x111;
+
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 256;
+const NO_POSITION: u32 = u32::MAX;
+
+struct MatchFinder<'a> {
+  data: &'a [u8],
+  window: usize,
+  head: Vec<u32>,
+  previous: Vec<u32>,
+}
+
+impl<'a> MatchFinder<'a> {
+  fn new(data: &'a [u8], window: usize) -> Self {
+    Self {
+      data,
+      window,
+      head: vec![NO_POSITION; 1 << HASH_BITS],
+      previous: vec![NO_POSITION; data.len()],
+    }
+  }
+
+  fn hash(&self, position: usize) -> Option<usize> {
+    let [a, b, c] = *self.data.get(position..position + MIN_COPY)?.first_chunk()?;
+    let value = u32::from_be_bytes([0, a, b, c]);
+    Some((value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize)
+  }
+
+  fn insert(&mut self, position: usize) {
+    if let Some(hash) = self.hash(position) {
+      self.previous[position] = self.head[hash];
+      self.head[hash] = position as u32;
+    }
+  }
+
+  /// Returns the longest `(copy_count, lookback_distance)` for the data at `position`.
+  fn find(&self, position: usize) -> Option<(usize, usize)> {
+    let hash = self.hash(position)?;
+    let max_count = (self.data.len() - position).min(MAX_LONG_COPY);
+    let mut best: Option<(usize, usize)> = None;
+    let mut candidate = self.head[hash];
+
+    for _ in 0..MAX_CHAIN {
+      if candidate == NO_POSITION {
+        break;
+      }
+      let distance = position - candidate as usize;
+      if distance > self.window {
+        break;
+      }
+
+      // the source may run into the bytes being produced, which the decoder handles
+      let count = (0..max_count)
+        .take_while(|&index| {
+          self.data[candidate as usize + index] == self.data[position + index]
+        })
+        .count();
+      if count >= MIN_COPY && best.is_none_or(|(best_count, _)| count > best_count) {
+        best = Some((count, distance));
+        if count == max_count {
+          break;
+        }
+      }
+
+      candidate = self.previous[candidate as usize];
+    }
+
+    best
+  }
+}
+
+/// Compresses all of `reader` into `writer`, looking back at most `window` bytes for copies.
+///
+/// `window` is capped to [`MAX_WINDOW`]; a window of 0 only emits uncompressed bytes.
+pub fn compress(
+  reader: &mut impl Read,
+  writer: &mut impl Write,
+  window: u16,
+) -> Result<(), CompressionError> {
+  let mut data = Vec::new();
+  reader.read_to_end(&mut data)?;
+  let uncompressed_size =
+    u32::try_from(data.len()).map_err(|_| CompressionError::InputTooLarge {
+      size: data.len(),
+      backtrace: Backtrace::generate(),
+    })?;
+
+  let mut encoder = Yaz0Encoder::new(writer, uncompressed_size, window)?;
+  encoder.write_all(&data)?;
+  encoder.finish()?;
+
+  Ok(())
+}
+
+/// How many unencoded bytes [`Yaz0Encoder`] buffers before compressing them.
+const ENCODE_CHUNK: usize = 0x10000;
+
+/// A streaming Yaz0 compressor, so data can be piped into a `.szs` with [`io::copy`].
+///
+/// The header is written up front, so the uncompressed size has to be known when the encoder is
+/// created. Exactly that many bytes must be written before calling [`Yaz0Encoder::finish`].
+pub struct Yaz0Encoder<W: Write> {
+  writer: W,
+  window: usize,
+  uncompressed_size: u32,
+  remaining: u32,
+  // up to `window` bytes of history followed by the bytes that haven't been encoded yet
+  buffer: Vec<u8>,
+  position: usize,
+  // a group is one control byte followed by up to 8 chunks of at most 3 bytes
+  group: Vec<u8>,
+  group_count: usize,
+}
+
+impl<W: Write> Yaz0Encoder<W> {
+  /// Writes the header for `uncompressed_size` bytes, looking back at most `window` bytes for
+  /// copies. `window` is capped to [`MAX_WINDOW`]; a window of 0 only emits uncompressed bytes.
+  pub fn new(mut writer: W, uncompressed_size: u32, window: u16) -> Result<Self, CompressionError> {
+    let header = Header {
+      magic: magic::YAZ0,
+      uncompressed_size: U32::new(uncompressed_size),
+      alignment: U32::new(0),
+      _unused: [0; 4],
+    };
+    writer.write_all(header.as_bytes())?;
+
+    Ok(Self {
+      writer,
+      window: window.min(MAX_WINDOW) as usize,
+      uncompressed_size,
+      remaining: uncompressed_size,
+      buffer: Vec::new(),
+      position: 0,
+      group: Vec::with_capacity(1 + 8 * 3),
+      group_count: 0,
+    })
+  }
+
+  /// Encodes everything still buffered and returns the inner writer.
+  pub fn finish(mut self) -> Result<W, CompressionError> {
+    if self.remaining != 0 {
+      return Err(CompressionError::IncompleteInput {
+        expected: self.uncompressed_size,
+        written: self.uncompressed_size - self.remaining,
+        backtrace: Backtrace::generate(),
+      });
+    }
+
+    self.encode_until(self.buffer.len())?;
+    if self.group_count != 0 {
+      self.writer.write_all(&self.group)?;
+    }
+    self.writer.flush()?;
+
+    Ok(self.writer)
+  }
+
+  /// Encodes buffered bytes up to at least `end`, then drops history the window can't reach.
+  fn encode_until(&mut self, end: usize) -> io::Result<()> {
+    let mut matches = MatchFinder::new(&self.buffer, self.window);
+    for position in 0..self.position {
+      matches.insert(position);
+    }
+
+    while self.position < end {
+      if self.group_count == 0 {
+        self.group.clear();
+        self.group.push(0);
+      }
+
+      let length = match matches.find(self.position) {
+        Some((copy_count, lookback_distance)) => {
+          let lookback = (lookback_distance - 1) as u16;
+          if copy_count <= MAX_SHORT_COPY {
+            let short_copy = ShortCopy::new()
+              .with_lookback_upper((lookback >> 8) as u8)
+              .with_copy_count((copy_count - 0x02) as u8)
+              .with_lookback_lower(lookback as u8);
+            self.group.extend(short_copy.into_bytes());
+          } else {
+            let long_copy = LongCopy::new()
+              .with_lookback_upper((lookback >> 8) as u8)
+              .with_lookback_lower(lookback as u8)
+              .with_copy_count((copy_count - 0x12) as u8);
+            self.group.extend(long_copy.into_bytes());
+          }
+          copy_count
+        }
+        None => {
+          self.group[0] |= 0x80 >> self.group_count;
+          self.group.push(self.buffer[self.position]);
+          1
+        }
+      };
+
+      for inserted in self.position..self.position + length {
+        matches.insert(inserted);
+      }
+      self.position += length;
+      self.group_count += 1;
+
+      if self.group_count == 8 {
+        self.writer.write_all(&self.group)?;
+        self.group_count = 0;
+      }
+    }
+
+    let expired = self.position.saturating_sub(self.window);
+    self.buffer.drain(..expired);
+    self.position -= expired;
+
+    Ok(())
+  }
+}
+
+impl<W: Write> Write for Yaz0Encoder<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if buf.len() > self.remaining as usize {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "wrote more bytes than the encoder's uncompressed size",
+      ));
+    }
+
+    self.buffer.extend_from_slice(buf);
+    self.remaining -= buf.len() as u32;
+
+    // keep enough lookahead buffered that copies aren't cut short by data that hasn't arrived
+    if self.buffer.len() - self.position >= ENCODE_CHUNK + MAX_LONG_COPY {
+      self.encode_until(self.buffer.len() - MAX_LONG_COPY)?;
+    }
+
+    Ok(buf.len())
+  }
+
+  /// Flushes the inner writer. Bytes in an unfinished group stay buffered until
+  /// [`Yaz0Encoder::finish`], as a group can't be split.
+  fn flush(&mut self) -> io::Result<()> {
+    self.writer.flush()
+  }
+}
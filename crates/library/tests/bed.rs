@@ -0,0 +1,201 @@
+use std::{io::Cursor, thread};
+
+use senobi_library::{
+  BoxError, Error, align_up,
+  byml::{
+    BymlValue,
+    reader::{BymlDocument, BymlReader, BymlReaderNode},
+    writer::{BymlWriter, Version},
+  },
+  format_error,
+  nw::bntx::reader::BntxError,
+  round_up_to_multiple,
+  sarc::reader::{SarcArchive, SarcReader},
+  yaz0,
+};
+use zerocopy::LittleEndian;
+
+const BED_SZS: &[u8] = include_bytes!("../examples/Bed.szs");
+
+#[test]
+fn decompresses_and_reads_bed() {
+  let sarc = yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+
+  let byml = reader.get("Bed.byml").expect("Bed.byml is missing from the archive");
+  let dict = BymlReader::<LittleEndian>::new(byml)
+    .unwrap()
+    .unwrap_dictionary();
+
+  assert_eq!(dict.get_string("UnitConfigName").unwrap(), Some("HomeBed"));
+}
+
+#[test]
+fn looks_up_bed_by_path() {
+  let sarc = yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  let dict = BymlReader::<LittleEndian>::new(reader.get("Bed.byml").unwrap())
+    .unwrap()
+    .unwrap_dictionary();
+
+  let scale = dict.get_by_path("UnitConfig/DisplayScale/X").unwrap();
+  assert!(matches!(scale, Some(BymlReaderNode::F32(1.0))));
+  let category = dict.get_by_path("UnitConfig/GenerateCategory").unwrap();
+  assert!(matches!(category, Some(BymlReaderNode::String(name)) if name == c"ObjectList"));
+
+  assert!(dict.get_by_path("UnitConfig/Missing").unwrap().is_none());
+  // the path continues past a string
+  assert!(dict.get_by_path("Id/0").unwrap().is_none());
+}
+
+#[test]
+fn round_trips_bed_through_owned_values() {
+  let value = {
+    let sarc = yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap();
+    let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+    let dict = BymlReader::<LittleEndian>::new(reader.get("Bed.byml").unwrap())
+      .unwrap()
+      .unwrap_dictionary();
+    BymlValue::from_reader(&BymlReaderNode::Dictionary(dict)).unwrap()
+  };
+
+  let BymlValue::Dictionary(entries) = &value else {
+    panic!("the root isn't a dictionary");
+  };
+  assert_eq!(
+    entries["UnitConfigName"],
+    BymlValue::String("HomeBed".to_owned())
+  );
+
+  let mut data = Vec::new();
+  BymlWriter::from_value(&value)
+    .unwrap()
+    .write::<LittleEndian>(&mut Cursor::new(&mut data), Version::V2)
+    .unwrap();
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  let reread = BymlValue::from_reader(&BymlReaderNode::Dictionary(dict)).unwrap();
+  assert_eq!(reread, value);
+
+  assert!(BymlWriter::from_value(&BymlValue::Null).is_none());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn converts_bed_to_json() {
+  use senobi_library::byml::json;
+
+  let sarc = yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  let dict = BymlReader::<LittleEndian>::new(reader.get("Bed.byml").unwrap())
+    .unwrap()
+    .unwrap_dictionary();
+
+  let json = json::to_json(&BymlReaderNode::Dictionary(dict)).unwrap();
+  assert_eq!(json["UnitConfigName"], "HomeBed");
+  assert_eq!(json["UnitConfig"]["DisplayScale"]["X"], 1.0);
+  assert_eq!(json["IsLinkDest"], false);
+  assert!(json["comment"].is_null());
+}
+
+#[test]
+fn truncated_bed_fails_without_panicking() {
+  let sarc = yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  let byml = reader.get("Bed.byml").unwrap();
+
+  for len in 0..byml.len() {
+    let Ok(reader) = BymlReader::<LittleEndian>::new(&byml[..len]) else {
+      continue;
+    };
+    let root = match reader {
+      BymlReader::Array(array) => BymlReaderNode::Array(array),
+      BymlReader::Dictionary(dict) => BymlReaderNode::Dictionary(dict),
+      BymlReader::Empty => continue,
+    };
+    assert!(root.to_writer_node().is_err(), "{len} bytes read in full");
+  }
+}
+
+fn unit_config_name(szs: &[u8]) -> Result<Option<String>, Error> {
+  let sarc = yaz0::decompress(&mut Cursor::new(szs))?;
+  let reader = SarcReader::<LittleEndian>::new(&sarc)?;
+  let Some(byml) = reader.get("Bed.byml") else {
+    return Ok(None);
+  };
+  let dict = BymlReader::<LittleEndian>::new(byml)?.unwrap_dictionary();
+
+  Ok(dict.get_string("UnitConfigName")?.map(str::to_owned))
+}
+
+#[test]
+fn pipelines_share_one_error_type() {
+  assert_eq!(
+    unit_config_name(BED_SZS).unwrap().as_deref(),
+    Some("HomeBed")
+  );
+  assert!(matches!(
+    unit_config_name(b"not yaz0 data"),
+    Err(Error::Decompression { .. })
+  ));
+}
+
+#[test]
+fn readers_open_through_try_from() {
+  let sarc = yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap();
+  let reader: SarcReader<LittleEndian> = sarc.as_ref().try_into().unwrap();
+
+  let byml = BymlDocument::try_from(reader.get("Bed.byml").unwrap()).unwrap();
+  assert!(matches!(byml, BymlDocument::LittleEndian(_)));
+  assert!(SarcArchive::try_from(&b"not a sarc archive"[..]).is_err());
+}
+
+fn assert_thread_safe<T: std::error::Error + Send + Sync + 'static>() {}
+
+#[test]
+fn errors_box_into_thread_safe_errors() {
+  assert_thread_safe::<BntxError>();
+  assert_thread_safe::<Error>();
+
+  fn boxed(szs: &[u8]) -> Result<Option<String>, BoxError> {
+    Ok(unit_config_name(szs)?)
+  }
+
+  let error = boxed(b"not yaz0 data").unwrap_err();
+  assert!(matches!(
+    error.downcast_ref::<Error>(),
+    Some(Error::Decompression { .. })
+  ));
+  let message = thread::spawn(move || error.to_string()).join().unwrap();
+  assert!(message.starts_with("failed to decompress yaz0 data"));
+}
+
+#[test]
+fn formats_the_whole_error_chain() {
+  let error = unit_config_name(b"not yaz0 data").unwrap_err();
+  let report = format_error(&error);
+  let mut lines = report.lines();
+
+  assert_eq!(lines.next(), Some(format!("error: {error}").as_str()));
+  assert!(lines.next().unwrap().starts_with("caused by: "));
+}
+
+#[test]
+fn rounds_up_to_alignments_and_multiples() {
+  assert_eq!(align_up(0u32, 4), 0);
+  assert_eq!(align_up(5u32, 4), 8);
+  assert_eq!(align_up(0x2001usize, 0x2000), 0x4000);
+
+  assert_eq!(round_up_to_multiple(0u32, 3), 0);
+  assert_eq!(round_up_to_multiple(7u32, 3), 9);
+  assert_eq!(round_up_to_multiple(9u32, 3), 9);
+  assert_eq!(round_up_to_multiple(5u64, 4), align_up(5u64, 4));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "power of two")]
+fn align_up_rejects_other_alignments() {
+  align_up(5u32, 3);
+}
@@ -0,0 +1,61 @@
+use senobi_library::nw::{
+  bfres::{export_obj, reader::BfresReaderV8},
+  gfx::{IndexFormat, PrimitiveType},
+};
+
+const HOME_BED_BFRES: &[u8] = include_bytes!("../examples/HomeBed.bfres");
+
+#[test]
+fn reads_shape_meshes() {
+  let bfres = BfresReaderV8::new(HOME_BED_BFRES).unwrap();
+  let shape = &bfres.models["HomeBed"].shapes["Bed__BodyMT"];
+
+  let meshes = shape.meshes().collect::<Vec<_>>();
+  assert_eq!(meshes.len(), 1);
+
+  let mesh = meshes[0];
+  assert_eq!(mesh.primitive_type, PrimitiveType::Triangles);
+  assert_eq!(mesh.index_format, IndexFormat::U16);
+  assert_eq!(mesh.index_count, 3918);
+  assert_eq!(mesh.index_buffer.len(), 3918 * 2);
+  assert_eq!(mesh.indices().len(), 3918);
+  assert!(mesh.indices().all(|index| index < 954));
+}
+
+#[test]
+fn rejects_other_magic() {
+  let mut data = HOME_BED_BFRES.to_vec();
+  data[..4].copy_from_slice(b"BNTX");
+
+  assert!(BfresReaderV8::new(&data).is_err());
+}
+
+#[test]
+fn reads_unaligned_buffers() {
+  // one byte in, so nothing after it can be 2, 4 or 8 byte aligned
+  let mut data = vec![0];
+  data.extend_from_slice(HOME_BED_BFRES);
+
+  let bfres = BfresReaderV8::new(&data[1..]).unwrap();
+  let shape = &bfres.models["HomeBed"].shapes["Bed__BodyMT"];
+  assert_eq!(shape.meshes().count(), 1);
+}
+
+#[test]
+fn exports_obj() {
+  let bfres = BfresReaderV8::new(HOME_BED_BFRES).unwrap();
+  let mut obj = Vec::new();
+  export_obj(&bfres.models["HomeBed"], &mut obj).unwrap();
+  let obj = String::from_utf8(obj).unwrap();
+
+  assert!(obj.starts_with("o Bed__BodyMT\n"));
+  assert_eq!(
+    obj.lines().filter(|line| line.starts_with("v ")).count(),
+    954
+  );
+  assert_eq!(
+    obj.lines().filter(|line| line.starts_with("f ")).count(),
+    3918 / 3
+  );
+  assert!(obj.contains("\nv -18.540855 69.492966 -48.779457\n"));
+}
@@ -0,0 +1,254 @@
+use num_traits::FromPrimitive;
+use senobi_library::nw::{
+  bfres::reader::BfresReaderV8,
+  bntx::reader::{BntxError, BntxReader},
+  gfx::{self, ChannelFormat, FormatInfo, ImageDimension, TypeFormat},
+};
+use tegra_swizzle::surface::swizzle_surface;
+use zerocopy::LittleEndian;
+
+const HOME_BED_BNTX: &[u8] = include_bytes!("../examples/HomeBed.bntx");
+const HOME_BED_BFRES: &[u8] = include_bytes!("../examples/HomeBed.bfres");
+
+#[test]
+fn lists_textures_in_declaration_order() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  let names = bntx
+    .textures_ordered()
+    .map(|(name, _)| name)
+    .collect::<Vec<_>>();
+
+  assert_eq!(names.len(), bntx.textures.len());
+  assert!(names.contains(&"BedBody_alb"));
+  assert_eq!(
+    names,
+    BntxReader::<LittleEndian>::new(HOME_BED_BNTX)
+      .unwrap()
+      .textures_ordered()
+      .map(|(name, _)| name)
+      .collect::<Vec<_>>()
+  );
+}
+
+#[test]
+fn lenient_read_matches_strict_read_on_valid_files() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  let lenient = BntxReader::<LittleEndian>::read_lenient(HOME_BED_BNTX).unwrap();
+
+  assert!(
+    lenient
+      .iter()
+      .map(|(name, _)| *name)
+      .eq(bntx.textures_ordered().map(|(name, _)| name))
+  );
+  for (name, texture) in lenient {
+    let texture = texture.unwrap();
+    assert_eq!(texture.width(), bntx.textures[name].width());
+    assert_eq!(texture.height(), bntx.textures[name].height());
+  }
+}
+
+#[test]
+fn decodes_image_dimensions() {
+  let dimensions = [
+    ImageDimension::Tex1d,
+    ImageDimension::Tex2d,
+    ImageDimension::Tex3d,
+    ImageDimension::Cube,
+    ImageDimension::Tex1dArray,
+    ImageDimension::Tex2dArray,
+    ImageDimension::Tex2dMultisample,
+    ImageDimension::Tex2dMultisampleArray,
+    ImageDimension::CubeArray,
+  ];
+  for (value, dimension) in dimensions.into_iter().enumerate() {
+    assert_eq!(ImageDimension::from_u8(value as u8), Some(dimension));
+  }
+  assert_eq!(ImageDimension::from_u8(dimensions.len() as u8), None);
+
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  for texture in bntx.textures.values() {
+    assert_eq!(texture.dimension(), ImageDimension::Tex2d);
+  }
+}
+
+#[test]
+fn wraps_supported_formats_in_texture_readers() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  for texture in bntx.textures.values() {
+    let reader = texture.as_texture_reader();
+    match texture.image_format() {
+      (ChannelFormat::BC1, TypeFormat::SRGB) => {
+        let reader = reader.unwrap();
+        assert_eq!(reader.width(), texture.width());
+        assert_eq!(reader.height(), texture.height());
+        let pixels = reader.decompress().unwrap();
+        assert_eq!(
+          pixels.len(),
+          (texture.width() * texture.height() * 4) as usize
+        );
+      }
+      _ => assert!(reader.is_none()),
+    }
+  }
+}
+
+#[test]
+fn decodes_sample_count_and_access_flags() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  for texture in bntx.textures.values() {
+    assert_eq!(texture.sample_count(), 1);
+
+    let access = texture.access_flags();
+    assert!(access.texture());
+    assert!(!access.color_buffer());
+    assert!(!access.depth_stencil());
+  }
+}
+
+#[test]
+fn exposes_the_raw_format_word() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  for texture in bntx.textures.values() {
+    let word = texture.raw_format_word();
+    assert_eq!(texture.try_image_format(), Some(texture.image_format()));
+    assert_eq!(
+      ChannelFormat::from_u32(word >> 8).zip(TypeFormat::from_u32(word & 0xFF)),
+      texture.try_image_format()
+    );
+  }
+}
+
+#[test]
+fn deswizzles_without_a_texture() {
+  let format_info = FormatInfo::from_image_format(ChannelFormat::BC1, TypeFormat::SRGB).unwrap();
+  // a 64x64 BC1 surface is 16x16 blocks of 8 bytes
+  let linear = (0..16 * 16 * 8)
+    .map(|index| index as u8)
+    .collect::<Vec<_>>();
+  let swizzled = swizzle_surface(
+    64,
+    64,
+    1,
+    &linear,
+    format_info.block_dim,
+    None,
+    format_info.bytes_per_pixel,
+    1,
+    1,
+  )
+  .unwrap();
+
+  assert_ne!(swizzled, linear);
+  assert_eq!(
+    gfx::deswizzle(&format_info, 64, 64, 1, 1, 1, &swizzled).unwrap(),
+    linear
+  );
+  assert!(gfx::deswizzle(&format_info, 64, 64, 1, 1, 1, &swizzled[..64]).is_err());
+}
+
+#[test]
+fn deswizzles_single_layers() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  for texture in bntx.textures.values() {
+    assert_eq!(texture.array_layers(), 1);
+    assert_eq!(texture.mip_data(0, 0), Some(texture.image_data().unwrap()));
+    assert_eq!(
+      texture.deswizzled_layer(0).unwrap(),
+      texture.deswizzled_image_data().unwrap()
+    );
+    assert!(texture.mip_data(1, 0).is_none());
+    assert!(texture.deswizzled_layer(1).is_err());
+  }
+}
+
+#[test]
+fn rejects_more_mips_than_the_dimensions_allow() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  let texture = &bntx.textures["BedBody_alb"];
+  let maximum = texture.width().max(texture.height()).ilog2() + 1;
+  assert!(texture.mip_levels() <= maximum);
+
+  let offset = &texture.info.info.mip_levels as *const _ as usize - HOME_BED_BNTX.as_ptr() as usize;
+  let mut data = HOME_BED_BNTX.to_vec();
+  data[offset..offset + 2].copy_from_slice(&(maximum as u16 + 1).to_le_bytes());
+
+  let lenient = BntxReader::<LittleEndian>::read_lenient(&data).unwrap();
+  let (_, texture) = lenient
+    .iter()
+    .find(|(name, _)| *name == "BedBody_alb")
+    .unwrap();
+  assert!(matches!(
+    texture,
+    Err(BntxError::MipLevelCountInvalid { .. })
+  ));
+  assert!(BntxReader::<LittleEndian>::new(&data).is_err());
+}
+
+#[test]
+fn reads_metadata_from_files_without_texture_data() {
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+  let brtd = HOME_BED_BNTX
+    .windows(4)
+    .position(|magic| magic == b"BRTD")
+    .unwrap();
+  let truncated = &HOME_BED_BNTX[..brtd];
+  assert!(BntxReader::<LittleEndian>::new(truncated).is_err());
+
+  let metadata = BntxReader::<LittleEndian>::read_metadata_only(truncated).unwrap();
+  assert!(
+    metadata
+      .textures_ordered()
+      .map(|(name, _)| name)
+      .eq(bntx.textures_ordered().map(|(name, _)| name))
+  );
+  for (name, texture) in &metadata.textures {
+    assert_eq!(texture.width(), bntx.textures[name].width());
+    assert_eq!(texture.image_format(), bntx.textures[name].image_format());
+    assert!(matches!(
+      texture.image_data(),
+      Err(BntxError::NoImageData { .. })
+    ));
+    assert!(texture.deswizzled_image_data().is_err());
+    assert!(texture.mip_data(0, 0).is_none());
+  }
+}
+
+#[test]
+fn rejects_texture_counts_that_disagree_with_the_dictionary() {
+  // the texture count follows the file header and the container's magic
+  const TEXTURE_COUNT_OFFSET: usize = 0x24;
+  let mut data = HOME_BED_BNTX.to_vec();
+  data[TEXTURE_COUNT_OFFSET..TEXTURE_COUNT_OFFSET + 4].copy_from_slice(&5u32.to_le_bytes());
+
+  assert!(matches!(
+    BntxReader::<LittleEndian>::new(&data),
+    Err(BntxError::TextureCountMismatch {
+      header: 5,
+      dictionary: 4,
+      ..
+    })
+  ));
+  assert!(matches!(
+    BntxReader::<LittleEndian>::read_lenient(&data),
+    Err(BntxError::TextureCountMismatch { .. })
+  ));
+}
+
+#[test]
+fn reads_textures_embedded_in_bfres() {
+  let bfres = BfresReaderV8::new(HOME_BED_BFRES).unwrap();
+  let embedded = BntxReader::<LittleEndian>::from_bfres(&bfres, "textures.bntx").unwrap();
+  let bntx = BntxReader::<LittleEndian>::new(HOME_BED_BNTX).unwrap();
+
+  assert_eq!(embedded.textures.len(), bntx.textures.len());
+  for (name, texture) in &embedded.textures {
+    assert_eq!(texture.width(), bntx.textures[name].width());
+    assert_eq!(texture.height(), bntx.textures[name].height());
+  }
+
+  assert!(matches!(
+    BntxReader::<LittleEndian>::from_bfres(&bfres, "missing.bntx"),
+    Err(BntxError::EmbeddedFileNotFound { .. })
+  ));
+}
@@ -0,0 +1,1529 @@
+use std::{ffi::CString, io::Cursor, rc::Rc};
+
+use senobi_library::{
+  Order,
+  byml::{
+    self, BymlVisitor, ElementReadError, InsertError, NodeKey, OpenError, Region,
+    raw::{RawContainer, RawElement},
+    reader::{
+      AnyEndianReader, BymlDocument, BymlReader, BymlReaderArray, BymlReaderDict, BymlReaderNode,
+      KeyCache, detect_order,
+    },
+    types::{DataType, Header, InvalidDataType, TryDictEntry},
+    write_error::WriteError,
+    writer::{BymlWriter, BymlWriterArray, BymlWriterDict, BymlWriterNode, Version},
+  },
+  magic,
+  yaz0::{self, MAX_WINDOW},
+};
+use zerocopy::{BigEndian, FromBytes, LittleEndian};
+
+mod common;
+
+use common::{ROOT_OFFSET, array, container_header, document, write};
+
+#[test]
+fn nan_payloads_round_trip() {
+  let f32_nan = f32::from_bits(0x7FA0_0001);
+  let f64_nan = f64::from_bits(0x7FF4_0000_0000_0001);
+
+  let mut quiet = BymlWriterDict::new();
+  quiet.insert_f32("value", f32::NAN);
+  let mut signaling = BymlWriterDict::new();
+  signaling.insert_f32("value", f32_nan);
+  let mut negative_zero = BymlWriterDict::new();
+  negative_zero.insert_f32("value", -0.0);
+  let mut positive_zero = BymlWriterDict::new();
+  positive_zero.insert_f32("value", 0.0);
+
+  let mut array = BymlWriterArray::new();
+  array.push_dict(quiet);
+  array.push_dict(signaling);
+  array.push_dict(negative_zero);
+  array.push_dict(positive_zero);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_f32("f32", f32_nan);
+  root.insert_f64("f64", f64_nan);
+  root.insert_array("dicts", array);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+
+  assert_eq!(
+    dict.get_f32("f32").unwrap().unwrap().to_bits(),
+    f32_nan.to_bits()
+  );
+  assert_eq!(
+    dict.get_f64("f64").unwrap().unwrap().to_bits(),
+    f64_nan.to_bits()
+  );
+
+  let array = dict.get_array("dicts").unwrap().unwrap();
+  let expected = [f32::NAN, f32_nan, -0.0, 0.0];
+  for (index, expected) in expected.into_iter().enumerate() {
+    let element = array.get_dict(index as u32).unwrap().unwrap();
+    assert_eq!(
+      element.get_f32("value").unwrap().unwrap().to_bits(),
+      expected.to_bits()
+    );
+  }
+}
+
+#[test]
+fn aligns_array_values_after_their_types() {
+  // the types of 0, 4 and 8 elements already end on a word boundary and need no padding
+  let mut root = BymlWriterArray::new();
+  for len in 0..10 {
+    let mut array = BymlWriterArray::new();
+    for value in 0..len {
+      array.push_u32(value + 1);
+    }
+    root.push_array(array);
+  }
+  let data = write(&BymlWriter::from_array(root));
+
+  let root = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  for len in 0..10 {
+    let array = root.get_array(len).unwrap().unwrap();
+    let values = (0..len).map(|index| array.get_u32(index).unwrap().unwrap());
+    assert!(values.eq(1..=len), "array of {len} elements");
+  }
+}
+
+#[test]
+fn owned_and_shared_trees_write_identically() {
+  let build = || {
+    let mut point = BymlWriterArray::new();
+    point.push_f32(1.0);
+    point.push_f32(2.0);
+    let point = Rc::new(point);
+
+    let mut root = BymlWriterDict::new();
+    root.insert_array("a", point.clone());
+    root.insert_array("b", point);
+    root.insert_string("name", "shared");
+    root
+  };
+
+  let owned = write(&BymlWriter::from_owned_dictionary(build()));
+  let shared = write(&BymlWriter::from_dictionary(Rc::new(build())));
+  assert_eq!(owned, shared);
+
+  let dict = BymlReader::<LittleEndian>::new(&owned)
+    .unwrap()
+    .unwrap_dictionary();
+  for key in ["a", "b"] {
+    let point = dict.get_array(key).unwrap().unwrap();
+    assert_eq!(point.get_f32(1).unwrap(), Some(2.0));
+  }
+  assert_eq!(dict.get_string("name").unwrap(), Some("shared"));
+}
+
+#[test]
+fn writes_the_same_bytes_every_time() {
+  let build = || {
+    let mut root = BymlWriterDict::new();
+    for index in 0..64 {
+      let mut child = BymlWriterDict::new();
+      child.insert_string("name", format!("child {index}"));
+      child.insert_u64("id", index);
+      child.insert_f64("scale", index as f64 / 2.0);
+      root.insert_dict(&format!("child {index}"), child);
+    }
+    BymlWriter::from_dictionary(root)
+  };
+
+  let writer = build();
+  let first = write(&writer);
+  assert_eq!(write(&writer), first);
+  assert_eq!(write(&build()), first);
+}
+
+#[test]
+fn write_to_vec_matches_writing_to_a_cursor() {
+  let mut child = BymlWriterDict::new();
+  child.insert_string("name", "child");
+  child.insert_i64("id", -1);
+  let mut root = BymlWriterArray::new();
+  root.push_dict(child);
+  root.push_string("sibling");
+  let writer = BymlWriter::from_array(root);
+
+  let mut data = Vec::new();
+  writer
+    .write::<BigEndian>(&mut Cursor::new(&mut data), Version::V3)
+    .unwrap();
+  assert_eq!(writer.write_to_vec::<BigEndian>(Version::V3).unwrap(), data);
+}
+
+#[test]
+fn serialized_size_matches_the_written_length() {
+  let mut leaf = BymlWriterDict::new();
+  leaf.insert_string("name", "leaf");
+  leaf.insert_f64("scale", 0.5);
+  leaf.insert_binary("blob", [1, 2, 3, 4, 5]);
+  let leaf = Rc::new(leaf);
+
+  let mut branch = BymlWriterArray::new();
+  branch.push_dict(leaf.clone());
+  branch.push_dict(leaf);
+  branch.push_u64(7);
+  branch.push_i64(7);
+  branch.push_bool(true);
+  let mut root = BymlWriterDict::new();
+  root.insert_array("branch", branch);
+  root.insert_array("empty", BymlWriterArray::new());
+  root.insert_u64("seven", 7);
+  root.insert_string("name", "root");
+  let writer = BymlWriter::from_dictionary(root);
+
+  for version in [Version::V4, Version::V7] {
+    let data = writer.write_to_vec::<LittleEndian>(version).unwrap();
+    assert_eq!(
+      writer.serialized_size::<LittleEndian>(version).unwrap() as usize,
+      data.len()
+    );
+    let data = writer.write_to_vec::<BigEndian>(version).unwrap();
+    assert_eq!(
+      writer.serialized_size::<BigEndian>(version).unwrap() as usize,
+      data.len()
+    );
+  }
+  assert!(matches!(
+    writer.serialized_size::<LittleEndian>(Version::V3),
+    Err(WriteError::BinaryNeedsVersion4 { .. })
+  ));
+
+  let empty = BymlWriter::from_array(BymlWriterArray::new());
+  let data = empty.write_to_vec::<LittleEndian>(Version::V2).unwrap();
+  assert_eq!(
+    empty.serialized_size::<LittleEndian>(Version::V2).unwrap() as usize,
+    data.len()
+  );
+}
+
+#[test]
+fn get_element_distinguishes_absent_from_mistyped() {
+  let mut array = BymlWriterArray::new();
+  array.push_i32(-4);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("count", 3);
+  root.insert_array("values", array);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+
+  assert!(dict.get_i32("count").is_err());
+  assert!(matches!(
+    dict.get_element("count").unwrap(),
+    Some(BymlReaderNode::U32(3))
+  ));
+  assert!(dict.get_element("missing").unwrap().is_none());
+
+  let array = dict.get_array("values").unwrap().unwrap();
+  assert!(matches!(
+    array.get_element(0).unwrap(),
+    Some(BymlReaderNode::I32(-4))
+  ));
+  assert!(array.get_element(1).unwrap().is_none());
+}
+
+#[test]
+fn at_indexes_like_a_collection() {
+  let mut array = BymlWriterArray::new();
+  array.push_i32(-4);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("values", array);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+
+  let BymlReaderNode::Array(array) = dict.at("values") else {
+    panic!("values isn't an array");
+  };
+  assert!(matches!(array.at(0), BymlReaderNode::I32(-4)));
+}
+
+#[test]
+#[should_panic(expected = "the len is 1 but the index is 1")]
+fn at_panics_past_the_end() {
+  let mut array = BymlWriterArray::new();
+  array.push_i32(-4);
+
+  let data = write(&BymlWriter::from_array(array));
+  BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array()
+    .at(1);
+}
+
+#[test]
+fn paths_index_into_arrays() {
+  let mut point = BymlWriterDict::new();
+  point.insert_u32("id", 7);
+  let mut points = BymlWriterArray::new();
+  points.push_i32(-4);
+  points.push_dict(point);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("points", points);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+
+  let id = dict.get_by_path("points/1/id").unwrap();
+  assert!(matches!(id, Some(BymlReaderNode::U32(7))));
+  assert!(dict.get_by_path("points/2/id").unwrap().is_none());
+  assert!(dict.get_by_path("points/id").unwrap().is_none());
+}
+
+#[test]
+fn debug_summarises_and_pretty_print_recurses() {
+  let mut array = BymlWriterArray::new();
+  array.push_i32(-4);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("count", 3);
+  root.insert_array("values", array);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  let array = dict.get_array("values").unwrap().unwrap();
+
+  assert_eq!(format!("{dict:?}"), "BymlReaderDict { len: 2 }");
+  assert_eq!(format!("{array:?}"), "BymlReaderArray { len: 1 }");
+  assert_eq!(format!("{:?}", array.pretty_print()), "[I32(-4)]");
+  assert_eq!(
+    format!("{:?}", dict.pretty_print()),
+    r#"{"count": U32(3), "values": Array([I32(-4)])}"#
+  );
+}
+
+#[test]
+fn pretty_print_limited_elides_deep_containers() {
+  let mut inner = BymlWriterArray::new();
+  inner.push_i32(-4);
+  let mut outer = BymlWriterArray::new();
+  outer.push_array(inner);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("count", 3);
+  root.insert_array("values", outer);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+
+  assert_eq!(format!("{:?}", dict.pretty_print_limited(0)), "...");
+  assert_eq!(
+    format!("{:?}", dict.pretty_print_limited(1)),
+    r#"{"count": U32(3), "values": Array(...)}"#
+  );
+  assert_eq!(
+    format!("{:?}", dict.pretty_print_limited(2)),
+    r#"{"count": U32(3), "values": Array([Array(...)])}"#
+  );
+  assert_eq!(
+    format!("{:?}", dict.pretty_print_limited(3)),
+    format!("{:?}", dict.pretty_print())
+  );
+}
+
+#[test]
+fn validates_documents_within_limits() {
+  let mut shared = BymlWriterDict::new();
+  shared.insert_string("name", "shared");
+  let shared = Rc::new(shared);
+
+  let mut array = BymlWriterArray::new();
+  array.push_dict(shared.clone());
+  array.push_dict(shared.clone());
+  array.push_string("value");
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("items", array);
+  root.insert_dict("shared", shared);
+
+  BymlWriter::from_dictionary(root).validate().unwrap();
+  BymlWriter::from_array(BymlWriterArray::new()).validate().unwrap();
+}
+
+#[test]
+fn write_compressed_decompresses_to_write() {
+  let build = || {
+    let mut root = BymlWriterDict::new();
+    root.insert_string("UnitConfigName", "HomeBed");
+    root.insert_u32("count", 42);
+    BymlWriter::from_dictionary(root)
+  };
+
+  let mut compressed = Vec::new();
+  build()
+    .write_compressed::<LittleEndian>(&mut compressed, Version::V3, MAX_WINDOW)
+    .unwrap();
+  let decompressed = yaz0::decompress(&mut Cursor::new(compressed)).unwrap();
+
+  assert_eq!(*decompressed, *write(&build()));
+}
+
+#[test]
+fn raw_containers_expose_layout() {
+  let mut array = BymlWriterArray::new();
+  array.push_u32(7);
+  array.push_bool(true);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("array", array);
+  root.insert_i32("value", -1);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let header = Header::<LittleEndian>::ref_from_prefix(&data).unwrap().0;
+
+  let keys = RawContainer::<LittleEndian>::new(&data, header.hash_key_offset.get()).unwrap();
+  assert_eq!(keys.data_type(), DataType::StringTable as u8);
+  assert_eq!(keys.elements().count(), 3);
+
+  let root = RawContainer::<LittleEndian>::new(&data, header.root_node_offset.get()).unwrap();
+  let elements = root.elements().collect::<Vec<_>>();
+  assert_eq!(elements.len(), 2);
+  assert_eq!(elements[0].key_index, Some(0));
+  assert_eq!(elements[0].data_type, DataType::Array as u8);
+  assert_eq!(
+    elements[1],
+    RawElement {
+      key_index: Some(1),
+      data_type: DataType::I32 as u8,
+      value: u32::MAX,
+    }
+  );
+
+  let array = RawContainer::<LittleEndian>::new(&data, elements[0].value).unwrap();
+  let values = array
+    .elements()
+    .map(|element| (element.data_type, element.value))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    values,
+    [(DataType::U32 as u8, 7), (DataType::Bool as u8, 1)]
+  );
+
+  assert!(RawContainer::<LittleEndian>::new(&data, 0).is_err());
+}
+
+#[test]
+fn key_cache_lookups_match_uncached() {
+  let mut array = BymlWriterArray::new();
+  for index in 0..4 {
+    let mut dict = BymlWriterDict::new();
+    dict.insert_u32("id", index);
+    if index % 2 == 0 {
+      dict.insert_string("name", format!("obj{index}"));
+    }
+    array.push_dict(dict);
+  }
+
+  let data = write(&BymlWriter::from_array(array));
+  let array = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+
+  let mut cache = KeyCache::new();
+  for index in 0..4 {
+    let dict = array.get_dict(index).unwrap().unwrap();
+    assert!(matches!(
+      dict.get_with_cache(&mut cache, "id").unwrap(),
+      Some(BymlReaderNode::U32(id)) if id == index
+    ));
+    assert_eq!(
+      dict.get_with_cache(&mut cache, "name").unwrap().is_some(),
+      dict.get_string("name").unwrap().is_some()
+    );
+    assert!(dict.get_with_cache(&mut cache, "missing").unwrap().is_none());
+  }
+}
+
+#[test]
+fn key_cache_follows_the_document_it_is_used_with() {
+  let mut first = BymlWriterDict::new();
+  first.insert_u32("a", 1);
+  first.insert_u32("b", 2);
+  let first = write(&BymlWriter::from_dictionary(first));
+  // "b" is at index 1 in the first document's hash key table and at index 0 in the second's
+  let mut second = BymlWriterDict::new();
+  second.insert_u32("b", 3);
+  let second = write(&BymlWriter::from_dictionary(second));
+
+  let first = BymlReader::<LittleEndian>::new(&first)
+    .unwrap()
+    .unwrap_dictionary();
+  let second = BymlReader::<LittleEndian>::new(&second)
+    .unwrap()
+    .unwrap_dictionary();
+
+  let mut cache = KeyCache::new();
+  for _ in 0..2 {
+    assert!(matches!(
+      first.get_with_cache(&mut cache, "b").unwrap(),
+      Some(BymlReaderNode::U32(2))
+    ));
+    assert!(matches!(
+      second.get_with_cache(&mut cache, "b").unwrap(),
+      Some(BymlReaderNode::U32(3))
+    ));
+  }
+}
+
+#[test]
+fn rejects_entry_counts_past_the_buffer() {
+  let mut array = BymlWriterArray::new();
+  array.push_i32(1);
+  array.push_i32(2);
+  let mut data = write(&BymlWriter::from_array(array));
+
+  let root = BymlReader::<LittleEndian>::new(&data).unwrap().unwrap_array();
+  assert_eq!(root.values().len(), 2);
+
+  let root_offset = Header::<LittleEndian>::read_from_prefix(&data)
+    .unwrap()
+    .0
+    .root_node_offset
+    .get() as usize;
+  data[root_offset + 1..root_offset + 4].copy_from_slice(&[0xFF; 3]);
+  assert!(BymlReader::<LittleEndian>::new(&data).is_err());
+}
+
+#[test]
+fn rejects_root_inside_string_tables() {
+  let mut root = BymlWriterDict::new();
+  root.insert_string("key", "value");
+  let mut data = write(&BymlWriter::from_dictionary(root));
+
+  let header = Header::<LittleEndian>::read_from_prefix(&data).unwrap().0;
+  for table in [header.hash_key_offset.get(), header.string_table_offset.get()] {
+    for offset in [table, table + 4] {
+      data[12..16].copy_from_slice(&offset.to_le_bytes());
+      let Err(error) = BymlReader::<LittleEndian>::new(&data) else {
+        panic!("root node at 0x{offset:X} was accepted");
+      };
+      assert!(error.to_string().contains("points into a string table"));
+    }
+  }
+}
+
+#[test]
+fn new_checked_suggests_the_other_byte_order() {
+  let mut root = BymlWriterDict::new();
+  root.insert_string("key", "value");
+  let mut data = write(&BymlWriter::from_dictionary(root));
+  assert!(BymlReader::<LittleEndian>::new_checked(&data).is_ok());
+
+  // a big endian magic on little endian data
+  data[..2].copy_from_slice(&magic::BYML_BIG_ENDIAN);
+  assert!(matches!(
+    BymlReader::<BigEndian>::new_checked(&data),
+    Err(OpenError::OffsetsSuggestWrongEndianness { .. })
+  ));
+  assert!(matches!(
+    BymlReader::<BigEndian>::new(&data),
+    Err(OpenError::UnsupportedVersion { .. })
+  ));
+  // the magic mismatch is still reported as such
+  assert!(matches!(
+    BymlReader::<LittleEndian>::new_checked(&data),
+    Err(OpenError::EndiannessMismatch { .. })
+  ));
+}
+
+#[test]
+fn validates_dict_entries() {
+  let mut data = Vec::new();
+  for (hash_key_index, data_type) in [(0u32, DataType::I32 as u8), (1, 0x42), (2, 0x43)] {
+    data.extend(&hash_key_index.to_le_bytes()[..3]);
+    data.push(data_type);
+    data.extend(7u32.to_le_bytes());
+  }
+  let entries = <[TryDictEntry<LittleEndian>]>::ref_from_bytes(&data).unwrap();
+
+  let entry = entries[0].validate().unwrap();
+  assert_eq!(entry.data_type, DataType::I32);
+  assert_eq!(entry.value.get(), 7);
+  assert_eq!(entries[1].validate().err(), Some(InvalidDataType(0x42)));
+  assert_eq!(
+    TryDictEntry::validate_all(entries).err(),
+    Some((1, InvalidDataType(0x42)))
+  );
+  assert_eq!(TryDictEntry::validate_all(&entries[..1]).unwrap().len(), 1);
+}
+
+#[test]
+fn checks_document_versions() {
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("key", 1);
+  let mut data = write(&BymlWriter::from_dictionary(root));
+
+  for version in [0u16, 2, 3, 4, 5, 6, 7] {
+    data[2..4].copy_from_slice(&version.to_le_bytes());
+    assert!(BymlReader::<LittleEndian>::new(&data).is_ok());
+  }
+  for version in [1u16, 8] {
+    data[2..4].copy_from_slice(&version.to_le_bytes());
+    assert!(matches!(
+      BymlReader::<LittleEndian>::new(&data),
+      Err(OpenError::UnsupportedVersion { actual, .. }) if actual == version
+    ));
+  }
+}
+
+#[test]
+fn keyless_documents_have_no_hash_key_table() {
+  let mut array = BymlWriterArray::new();
+  array.push_u32(1);
+  array.push_string("two");
+  array.push_f64(3.0);
+
+  let data = write(&BymlWriter::from_array(array));
+  let header = Header::<LittleEndian>::read_from_prefix(&data).unwrap().0;
+  assert_eq!(header.hash_key_offset.get(), 0);
+  assert_ne!(header.string_table_offset.get(), 0);
+
+  let array = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  assert_eq!(array.get_u32(0).unwrap(), Some(1));
+  assert_eq!(
+    array.get_cstring(1).unwrap().map(|value| value.to_bytes()),
+    Some(&b"two"[..])
+  );
+  assert_eq!(array.get_f64(2).unwrap(), Some(3.0));
+}
+
+#[test]
+fn try_insert_rejects_duplicate_keys() {
+  let mut dict = BymlWriterDict::new();
+  dict.try_insert("key", BymlWriterNode::U32(1)).unwrap();
+  assert!(matches!(
+    dict.try_insert("key", BymlWriterNode::U32(2)),
+    Err(InsertError::DuplicateKey { key, .. }) if key == "key"
+  ));
+  assert!(dict.values().eq([&BymlWriterNode::U32(1)]));
+
+  dict.insert_u32("key", 3);
+  assert!(dict.values().eq([&BymlWriterNode::U32(3)]));
+}
+
+#[test]
+fn fallible_inserts_reject_nul_bytes() {
+  let mut array = BymlWriterArray::new();
+  assert!(array.try_push_string("a\0b").is_err());
+  array.try_push_string("ab").unwrap();
+  assert!(array.iter().eq([&BymlWriterNode::String(c"ab".to_owned())]));
+
+  let mut dict = BymlWriterDict::new();
+  let error = dict.try_insert_string("key", "a\0b").unwrap_err();
+  assert_eq!(error.nul_position(), 1);
+  assert!(dict.try_insert_string("a\0b", "value").is_err());
+  assert!(matches!(
+    dict.try_insert("a\0b", BymlWriterNode::Null),
+    Err(InsertError::NulInKey { .. })
+  ));
+  assert!(dict.is_empty());
+
+  dict.try_insert_string("key", "value").unwrap();
+  assert_eq!(dict.len(), 1);
+}
+
+#[test]
+fn entries_yield_every_duplicate_key() {
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("a", 1);
+  root.insert_u32("b", 2);
+
+  let mut data = write(&BymlWriter::from_dictionary(root));
+  let header = Header::<LittleEndian>::read_from_prefix(&data).unwrap().0;
+  // point the second entry's key at "a" as well
+  let second_entry = header.root_node_offset.get() as usize + 4 + 8;
+  data[second_entry..second_entry + 3].fill(0);
+
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  let entries = dict
+    .entries()
+    .map(|entry| match entry.unwrap() {
+      (key, BymlReaderNode::U32(value)) => (key, value),
+      (key, node) => panic!("unexpected node for {key:?}: {node:?}"),
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(entries, [("a", 1), ("a", 2)]);
+  assert!(matches!(dict.get_u32("a").unwrap(), Some(1 | 2)));
+}
+
+#[test]
+fn annotate_labels_every_region() {
+  let mut array = BymlWriterArray::new();
+  array.push_u64(1 << 40);
+  array.push_bool(false);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("array", array);
+  root.insert_string("name", "value");
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let header = Header::<LittleEndian>::read_from_prefix(&data).unwrap().0;
+  let annotations = byml::annotate::<LittleEndian>(&data);
+
+  let regions = annotations
+    .iter()
+    .map(|annotation| annotation.region)
+    .collect::<Vec<_>>();
+  assert_eq!(regions[0], Region::Header);
+  assert!(regions.contains(&Region::HashKeyTable));
+  assert!(regions.contains(&Region::StringTable));
+  assert!(regions.contains(&Region::Node {
+    offset: header.root_node_offset.get(),
+    data_type: DataType::Dictionary as u8,
+  }));
+  assert_eq!(regions.iter().filter(|region| matches!(region, Region::Node { .. })).count(), 2);
+  assert_eq!(regions.iter().filter(|region| **region == Region::LongValue).count(), 1);
+
+  for pair in annotations.windows(2) {
+    assert!(pair[0].range.end <= pair[1].range.start, "{pair:?} overlap");
+  }
+  assert!(annotations.iter().all(|annotation| annotation.range.end <= data.len()));
+
+  assert!(byml::annotate::<LittleEndian>(&data[..8]).is_empty());
+}
+
+#[test]
+fn document_detects_byte_order() {
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("count", 3);
+  root.insert_bool("flag", true);
+  let writer = BymlWriter::from_dictionary(root);
+
+  let little = write(&writer);
+  let document = BymlDocument::new(&little).unwrap();
+  assert!(matches!(document, BymlDocument::LittleEndian(_)));
+  assert_eq!(document.len(), 2);
+
+  let mut big = Vec::new();
+  writer
+    .write::<BigEndian>(&mut Cursor::new(&mut big), Version::V3)
+    .unwrap();
+  let document = BymlDocument::new(&big).unwrap();
+  assert!(matches!(document, BymlDocument::BigEndian(_)));
+  assert_eq!(document.len(), 2);
+
+  assert!(BymlDocument::new(b"NOPE").is_err());
+}
+
+#[test]
+fn detects_the_order_from_the_magic() {
+  let writer = BymlWriter::from_array(BymlWriterArray::new());
+  let little = write(&writer);
+  let mut big = Vec::new();
+  writer
+    .write::<BigEndian>(&mut Cursor::new(&mut big), Version::V3)
+    .unwrap();
+
+  assert_eq!(detect_order(&little).unwrap(), Order::LittleEndian);
+  assert_eq!(detect_order(&big).unwrap(), Order::BigEndian);
+  assert!(matches!(
+    detect_order(b"Y"),
+    Err(OpenError::NotEnoughDataForHeader { size: 1, .. })
+  ));
+  assert!(matches!(
+    detect_order(b"NOPE"),
+    Err(OpenError::IncorrectMagic {
+      actual: [b'N', b'O'],
+      ..
+    })
+  ));
+
+  let reader = BymlReader::open_auto(&little).unwrap();
+  assert!(matches!(reader, AnyEndianReader::LittleEndian(_)));
+  let reader = BymlReader::open_auto(&big).unwrap();
+  assert!(matches!(reader, AnyEndianReader::BigEndian(_)));
+}
+
+#[test]
+fn reports_container_sizes_and_keys() {
+  let mut array = BymlWriterArray::new();
+  array.push_i32(1);
+  array.push_null();
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("values", array);
+  root.insert_bool("flag", true);
+  root.insert_null("nothing");
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  assert_eq!(dict.len(), 3);
+  assert!(!dict.is_empty());
+  assert!(dict.contains_key("flag").unwrap());
+  assert!(dict.contains_key("nothing").unwrap());
+  assert!(!dict.contains_key("flags").unwrap());
+
+  let array = dict.get_array("values").unwrap().unwrap();
+  assert_eq!(array.len(), 2);
+  assert!(!array.is_empty());
+}
+
+#[test]
+fn reads_empty_containers() {
+  let data = write(&BymlWriter::from_dictionary(BymlWriterDict::new()));
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  assert!(dict.is_empty());
+  assert!(dict.get_element("missing").unwrap().is_none());
+  assert_eq!(dict.get_string("missing").unwrap(), None);
+  assert!(!dict.contains_key("missing").unwrap());
+
+  let data = document(&array(&[]));
+  let array = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  assert!(array.is_empty());
+  assert!(array.first().unwrap().is_none());
+  assert!(array.last().unwrap().is_none());
+}
+
+#[test]
+fn array_index_helpers() {
+  let mut array = BymlWriterArray::new();
+  array.push_i32(-1);
+  array.push_bool(true);
+  array.push_u32(7);
+
+  let data = write(&BymlWriter::from_array(array));
+  let array = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+
+  assert!(matches!(array.first().unwrap(), Some(BymlReaderNode::I32(-1))));
+  assert!(matches!(array.get(1).unwrap(), Some(BymlReaderNode::Bool(true))));
+  assert!(matches!(array.last().unwrap(), Some(BymlReaderNode::U32(7))));
+  assert!(array.get(3).unwrap().is_none());
+  assert!(array.get(usize::MAX).unwrap().is_none());
+}
+
+#[test]
+fn rejects_hand_built_oversized_containers() {
+  for data_type in [DataType::Array, DataType::Dictionary] {
+    let data = document(&container_header(data_type, 0xFF_FFFF));
+    assert!(BymlReader::<LittleEndian>::new(&data).is_err());
+  }
+}
+
+#[test]
+fn reads_cyclic_pointers_lazily() {
+  let data = document(&array(&[(DataType::Array, ROOT_OFFSET)]));
+  // nothing is followed until it's read, so the cycle doesn't stop the document from opening
+  let root = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  assert_eq!(root.len(), 1);
+  assert!(matches!(
+    root.get_array(0),
+    Err(ElementReadError::CyclicContainer { .. })
+  ));
+
+  let annotations = byml::annotate::<LittleEndian>(&data);
+  assert_eq!(
+    annotations
+      .iter()
+      .filter(|annotation| matches!(annotation.region, Region::Node { .. }))
+      .count(),
+    1
+  );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserializes_typed_configs() {
+  #[derive(serde::Deserialize, Debug, PartialEq)]
+  #[serde(rename_all = "PascalCase")]
+  struct BedConfig {
+    unit_config_name: String,
+    scale: f32,
+    sleep_frames: u16,
+    tags: Vec<String>,
+    parent: Option<String>,
+    kind: Kind,
+  }
+
+  #[derive(serde::Deserialize, Debug, PartialEq)]
+  enum Kind {
+    Single,
+    Bunk { levels: u32 },
+  }
+
+  let mut tags = BymlWriterArray::new();
+  tags.push_string("Furniture");
+  tags.push_string("Home");
+
+  let mut bunk = BymlWriterDict::new();
+  bunk.insert_u32("levels", 2);
+  let mut kind = BymlWriterDict::new();
+  kind.insert_dict("Bunk", bunk);
+
+  let mut root = BymlWriterDict::new();
+  root.insert_string("UnitConfigName", "HomeBed");
+  root.insert_f32("Scale", 1.5);
+  root.insert_i32("SleepFrames", 120);
+  root.insert_array("Tags", tags);
+  root.insert_null("Parent");
+  root.insert_dict("Kind", kind);
+
+  let data = write(&BymlWriter::from_dictionary(root));
+  let node = BymlReaderNode::Dictionary(
+    BymlReader::<LittleEndian>::new(&data)
+      .unwrap()
+      .unwrap_dictionary(),
+  );
+
+  assert_eq!(
+    byml::from_node::<BedConfig, _>(&node).unwrap(),
+    BedConfig {
+      unit_config_name: "HomeBed".to_owned(),
+      scale: 1.5,
+      sleep_frames: 120,
+      tags: vec!["Furniture".to_owned(), "Home".to_owned()],
+      parent: None,
+      kind: Kind::Bunk { levels: 2 },
+    }
+  );
+
+  #[derive(serde::Deserialize, Debug)]
+  #[allow(dead_code)]
+  struct Mistyped {
+    #[serde(rename = "Scale")]
+    scale: String,
+  }
+  assert!(byml::from_node::<Mistyped, _>(&node).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serializes_typed_configs() {
+  #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+  #[serde(rename_all = "PascalCase")]
+  struct Rail {
+    name: String,
+    points: Vec<(f32, f32, f32)>,
+    speed: f64,
+    loops: u64,
+    offset: i8,
+    parent: Option<String>,
+    kind: Kind,
+  }
+
+  #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+  enum Kind {
+    Linear,
+    Bezier { tension: f32 },
+  }
+
+  let rail = Rail {
+    name: "Rail00".to_owned(),
+    points: vec![(0.0, 1.0, 2.0), (3.0, 4.0, 5.0)],
+    speed: 0.25,
+    loops: u64::MAX,
+    offset: -3,
+    parent: None,
+    kind: Kind::Bezier { tension: 0.5 },
+  };
+
+  let data = write(&byml::to_writer(&rail).unwrap());
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+
+  assert!(matches!(
+    dict.get_element("Offset").unwrap(),
+    Some(BymlReaderNode::I32(-3))
+  ));
+  assert!(matches!(
+    dict.get_element("Loops").unwrap(),
+    Some(BymlReaderNode::U64(u64::MAX))
+  ));
+  assert!(matches!(
+    dict.get_element("Parent").unwrap(),
+    Some(BymlReaderNode::Null)
+  ));
+
+  let node = BymlReaderNode::Dictionary(dict);
+  assert_eq!(byml::from_node::<Rail, _>(&node).unwrap(), rail);
+
+  assert!(byml::to_writer(&3u32).is_err());
+  assert!(byml::to_writer(&Kind::Linear).is_err());
+  assert!(byml::to_writer(&std::collections::HashMap::from([(1, 2)])).is_err());
+}
+
+#[test]
+fn reports_consumed_length() {
+  let mut inner = BymlWriterDict::new();
+  inner.insert_u64("big", u64::MAX);
+  inner.insert_string("name", "inner");
+
+  let mut root = BymlWriterArray::new();
+  root.push_dict(inner);
+  root.push_f64(0.5);
+
+  let mut data = write(&BymlWriter::from_array(root));
+  let consumed = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .consumed_len();
+  assert!(consumed <= data.len());
+  assert!(data.len() - consumed < 4);
+
+  data.extend([0; 0x10]);
+  assert_eq!(BymlDocument::new(&data).unwrap().consumed_len(), consumed);
+
+  // a document without a root
+  let mut data = document(&[]);
+  data[0xC..0x10].fill(0);
+  assert_eq!(
+    BymlReader::<LittleEndian>::new(&data).unwrap().consumed_len(),
+    size_of::<Header<LittleEndian>>()
+  );
+}
+
+#[test]
+fn copies_subtrees_between_documents() {
+  let mut inner = BymlWriterDict::new();
+  inner.insert_f32("scale", 1.5);
+  inner.insert_string("name", "bed");
+  let mut items = BymlWriterArray::new();
+  items.push_dict(inner);
+  items.push_u64(u64::MAX);
+  items.push_null();
+  let mut root = BymlWriterDict::new();
+  root.insert_array("items", items);
+  root.insert_bool("other", true);
+  let source = write(&BymlWriter::from_dictionary(root));
+
+  let source = BymlReader::<LittleEndian>::new(&source)
+    .unwrap()
+    .unwrap_dictionary();
+  let items = source.get_element("items").unwrap().unwrap();
+  let mut copy = BymlWriterDict::new();
+  copy
+    .try_insert("copied", items.to_writer_node().unwrap())
+    .unwrap();
+  let copy = write(&BymlWriter::from_dictionary(copy));
+
+  let copy = BymlReader::<LittleEndian>::new(&copy)
+    .unwrap()
+    .unwrap_dictionary();
+  let items = copy.get_array("copied").unwrap().unwrap();
+  assert_eq!(items.len(), 3);
+  let inner = items.get_dict(0).unwrap().unwrap();
+  assert_eq!(inner.get_f32("scale").unwrap(), Some(1.5));
+  assert_eq!(inner.get_string("name").unwrap(), Some("bed"));
+  assert_eq!(items.get_u64(1).unwrap(), Some(u64::MAX));
+  assert!(matches!(items.get(2).unwrap(), Some(BymlReaderNode::Null)));
+  assert_eq!(copy.get_bool("other").unwrap(), None);
+}
+
+#[test]
+fn looks_up_non_utf8_keys_by_bytes() {
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("a", 1);
+  root.insert(
+    CString::new(b"\xFFbinary".to_vec()).unwrap(),
+    BymlWriterNode::U32(2),
+  );
+  root.insert_u32("z", 3);
+  let data = write(&BymlWriter::from_dictionary(root));
+
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  assert!(matches!(
+    dict.get_by_bytes(b"\xFFbinary").unwrap(),
+    Some(BymlReaderNode::U32(2))
+  ));
+  assert!(matches!(
+    dict.get_by_bytes(b"a").unwrap(),
+    Some(BymlReaderNode::U32(1))
+  ));
+  assert!(matches!(
+    dict.get_by_bytes(b"z").unwrap(),
+    Some(BymlReaderNode::U32(3))
+  ));
+  assert!(dict.get_by_bytes(b"\xFF").unwrap().is_none());
+  assert!(dict.get_by_bytes(b"").unwrap().is_none());
+}
+
+#[test]
+fn sorts_arrays_by_a_derived_key() {
+  let mut objects = BymlWriterArray::new();
+  for id in ["c", "a", "b"] {
+    let mut object = BymlWriterDict::new();
+    object.insert_string("Id", id);
+    objects.push_dict(object);
+  }
+  objects.push_null();
+
+  // nodes that aren't objects with an id sort first
+  objects.sort_by_key(|node| match node {
+    BymlWriterNode::Dictionary(object) => match object.get(c"Id") {
+      Some(BymlWriterNode::String(id)) => Some(id.clone()),
+      _ => None,
+    },
+    _ => None,
+  });
+
+  let mut root = BymlWriterDict::new();
+  root.insert_array("Objs", objects);
+  let data = write(&BymlWriter::from_dictionary(root));
+  let root = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  let objects = root.get_array("Objs").unwrap().unwrap();
+
+  assert!(matches!(
+    objects.get_element(0).unwrap(),
+    Some(BymlReaderNode::Null)
+  ));
+  for (index, id) in ["a", "b", "c"].into_iter().enumerate() {
+    let object = objects.get_dict(index as u32 + 1).unwrap().unwrap();
+    assert_eq!(object.get_string("Id").unwrap(), Some(id));
+  }
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn owned_readers_share_their_data_across_threads() {
+  use senobi_library::byml::owned::OwnedBymlReader;
+
+  let mut root = BymlWriterDict::new();
+  root.insert_u32("count", 3);
+  let data = bytes::Bytes::from(write(&BymlWriter::from_dictionary(root)));
+
+  let owned = OwnedBymlReader::<LittleEndian>::from_bytes(data.clone()).unwrap();
+  let clone = owned.clone();
+  assert_eq!(clone.bytes().as_ptr(), data.as_ptr());
+
+  let count =
+    std::thread::spawn(move || clone.reader().unwrap_dictionary().get_u32("count").unwrap())
+      .join()
+      .unwrap();
+  assert_eq!(count, Some(3));
+
+  assert!(OwnedBymlReader::<LittleEndian>::from_bytes(data.slice(..4)).is_err());
+}
+
+#[test]
+fn deduplicates_long_values() {
+  const VALUE: u64 = 0x0123_4567_89AB_CDEF;
+
+  let mut array = BymlWriterArray::new();
+  for _ in 0..1000 {
+    array.push_u64(VALUE);
+  }
+  // equal bits of a different type still get their own copy
+  array.push_i64(VALUE as i64);
+  let data = write(&BymlWriter::from_array(array));
+
+  let copies = data
+    .windows(8)
+    .filter(|window| *window == VALUE.to_le_bytes())
+    .count();
+  assert_eq!(copies, 2);
+
+  let array = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  assert_eq!(array.len(), 1001);
+  for index in 0..1000 {
+    assert_eq!(array.get_u64(index).unwrap(), Some(VALUE));
+  }
+  assert_eq!(array.get_i64(1000).unwrap(), Some(VALUE as i64));
+}
+
+#[test]
+fn compares_containers_structurally() {
+  let build = |x: i32, padding: usize| {
+    let mut point = BymlWriterArray::new();
+    point.push_f32(f32::NAN);
+    point.push_i32(x);
+    let mut object = BymlWriterDict::new();
+    object.insert_array("point", point);
+    object.insert_string("name", "bed");
+
+    let mut root = BymlWriterDict::new();
+    root.insert_dict("object", object);
+    root.insert_string("padding", "_".repeat(padding));
+    write(&BymlWriter::from_dictionary(root))
+  };
+  // the padding string pushes the second document's containers further along
+  let documents = [build(1, 0), build(1, 64), build(2, 0)];
+  let [first, second, different] = documents.each_ref().map(|data| {
+    BymlReader::<LittleEndian>::new(data)
+      .unwrap()
+      .unwrap_dictionary()
+  });
+  let first = first.get_dict("object").unwrap().unwrap();
+  let second = second.get_dict("object").unwrap().unwrap();
+  let different = different.get_dict("object").unwrap().unwrap();
+
+  assert_eq!(first, second);
+  assert_ne!(first, different);
+  assert_eq!(
+    first.get_array("point").unwrap().unwrap(),
+    second.get_array("point").unwrap().unwrap()
+  );
+  assert_ne!(
+    first.get_element("point").unwrap().unwrap(),
+    first.get_element("name").unwrap().unwrap()
+  );
+}
+
+#[test]
+fn reads_non_utf8_strings_lossily() {
+  let mut root = BymlWriterDict::new();
+  // "ベッド" in Shift-JIS
+  root.insert(
+    CString::new("name").unwrap(),
+    BymlWriterNode::String(CString::new(b"\x83\x78\x83\x62\x83\x68".to_vec()).unwrap()),
+  );
+  root.insert_u32("count", 1);
+  let data = write(&BymlWriter::from_dictionary(root));
+
+  let dict = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  assert!(dict.get_string("name").is_err());
+  let name = dict.get_string_lossy("name").unwrap().unwrap();
+  assert_eq!(name, "\u{FFFD}x\u{FFFD}b\u{FFFD}h");
+  assert!(dict.get_string_lossy("missing").unwrap().is_none());
+  assert!(dict.get_string_lossy("count").is_err());
+}
+
+#[derive(Default)]
+struct Recorder {
+  events: Vec<String>,
+  depth: usize,
+  max_depth: usize,
+}
+
+impl<'a> BymlVisitor<'a, LittleEndian> for Recorder {
+  fn enter_array(&mut self, key: NodeKey<'a>, array: &BymlReaderArray<'a, LittleEndian>) {
+    self
+      .events
+      .push(format!("enter {key:?} array of {}", array.len()));
+    self.depth += 1;
+    self.max_depth = self.max_depth.max(self.depth);
+  }
+
+  fn exit_array(&mut self) {
+    self.events.push("exit array".to_owned());
+    self.depth -= 1;
+  }
+
+  fn enter_dict(&mut self, key: NodeKey<'a>, dict: &BymlReaderDict<'a, LittleEndian>) {
+    self
+      .events
+      .push(format!("enter {key:?} dict of {}", dict.len()));
+    self.depth += 1;
+    self.max_depth = self.max_depth.max(self.depth);
+  }
+
+  fn exit_dict(&mut self) {
+    self.events.push("exit dict".to_owned());
+    self.depth -= 1;
+  }
+
+  fn scalar(&mut self, key: NodeKey<'a>, node: &BymlReaderNode<'a, LittleEndian>) {
+    self.events.push(format!("{key:?} {node:?}"));
+  }
+}
+
+#[test]
+fn visits_nodes_in_document_order() {
+  let mut inner = BymlWriterDict::new();
+  inner.insert_null("b");
+  let mut items = BymlWriterArray::new();
+  items.push_i32(1);
+  items.push_dict(inner);
+  let mut root = BymlWriterDict::new();
+  root.insert_array("a", items);
+  root.insert_string("c", "x");
+  let data = write(&BymlWriter::from_dictionary(root));
+
+  let mut recorder = Recorder::default();
+  BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary()
+    .visit(&mut recorder)
+    .unwrap();
+
+  assert_eq!(
+    recorder.events,
+    [
+      "enter Root dict of 2",
+      r#"enter Key("a") array of 2"#,
+      "Index(0) I32(1)",
+      "enter Index(1) dict of 1",
+      r#"Key("b") Null"#,
+      "exit dict",
+      "exit array",
+      r#"Key("c") String("x")"#,
+      "exit dict",
+    ]
+  );
+  assert_eq!(recorder.depth, 0);
+}
+
+#[test]
+fn visits_deep_documents_without_recursing() {
+  const DEPTH: u32 = 100_000;
+  // every array holds a single pointer to the next one, 12 bytes further along
+  let mut root = Vec::new();
+  for depth in 1..DEPTH {
+    root.extend(array(&[(DataType::Array, ROOT_OFFSET + depth * 12)]));
+  }
+  root.extend(array(&[]));
+  let data = document(&root);
+
+  let mut recorder = Recorder::default();
+  BymlReader::<LittleEndian>::with_max_depth(&data, DEPTH)
+    .unwrap()
+    .unwrap_array()
+    .visit(&mut recorder)
+    .unwrap();
+  assert_eq!(recorder.max_depth, DEPTH as usize);
+  assert_eq!(recorder.depth, 0);
+
+  let data = document(&array(&[(DataType::Array, ROOT_OFFSET)]));
+  let error = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array()
+    .visit(&mut Recorder::default())
+    .unwrap_err();
+  assert!(matches!(
+    error,
+    ElementReadError::CyclicContainer {
+      offset: ROOT_OFFSET,
+      ..
+    }
+  ));
+}
+
+#[test]
+fn limits_how_deep_containers_nest() {
+  // every array holds a single pointer to the next one, 12 bytes further along
+  let mut root = Vec::new();
+  for depth in 1..4 {
+    root.extend(array(&[(DataType::Array, ROOT_OFFSET + depth * 12)]));
+  }
+  root.extend(array(&[]));
+  let data = document(&root);
+
+  let reader = BymlReader::<LittleEndian>::with_max_depth(&data, 5).unwrap();
+  BymlReaderNode::Array(reader.unwrap_array())
+    .to_writer_node()
+    .unwrap();
+
+  let reader = BymlReader::<LittleEndian>::with_max_depth(&data, 3).unwrap();
+  let copied = BymlReaderNode::Array(reader.unwrap_array()).to_writer_node();
+  assert!(matches!(
+    copied,
+    Err(ElementReadError::MaxDepthExceeded { depth: 3, .. })
+  ));
+
+  // two arrays pointing at each other would otherwise recurse until the stack overflows
+  let mut root = array(&[(DataType::Array, ROOT_OFFSET + 12)]);
+  root.extend(array(&[(DataType::Array, ROOT_OFFSET)]));
+  let data = document(&root);
+  let reader = BymlReader::<LittleEndian>::new(&data).unwrap();
+  let copied = BymlReaderNode::Array(reader.unwrap_array()).to_writer_node();
+  assert!(matches!(
+    copied,
+    Err(ElementReadError::MaxDepthExceeded { depth, .. }) if depth == byml::DEFAULT_MAX_DEPTH
+  ));
+
+  let data = document(&array(&[(DataType::Array, ROOT_OFFSET)]));
+  let reader = BymlReader::<LittleEndian>::new(&data).unwrap();
+  assert!(matches!(
+    reader.unwrap_array().get_element(0),
+    Err(ElementReadError::CyclicContainer {
+      offset: ROOT_OFFSET,
+      ..
+    })
+  ));
+}
+
+#[test]
+fn rejects_pointers_to_the_wrong_kind_of_container() {
+  const STRING_TABLE: u32 = ROOT_OFFSET + 20;
+  const GARBAGE: u32 = STRING_TABLE + 16;
+
+  let mut root = array(&[
+    (DataType::Array, STRING_TABLE),
+    (DataType::Dictionary, ROOT_OFFSET),
+    (DataType::Array, GARBAGE),
+  ]);
+  root.extend(container_header(DataType::StringTable, 1));
+  root.extend(12u32.to_le_bytes());
+  root.extend(14u32.to_le_bytes());
+  root.extend(b"a\0\0\0");
+  root.extend([0x12, 0, 0, 0]);
+  let data = document(&root);
+
+  let root = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  assert!(matches!(
+    root.get_element(0),
+    Err(ElementReadError::UnexpectedDataType {
+      expected: DataType::Array,
+      actual: DataType::StringTable,
+      ..
+    })
+  ));
+  assert!(matches!(
+    root.get_element(1),
+    Err(ElementReadError::UnexpectedDataType {
+      expected: DataType::Dictionary,
+      actual: DataType::Array,
+      ..
+    })
+  ));
+  assert!(matches!(
+    root.get_element(2),
+    Err(ElementReadError::InvalidDataType { value: 0x12, .. })
+  ));
+}
+
+#[test]
+fn round_trips_binary_values() {
+  let mut dict = BymlWriterDict::new();
+  dict.insert_binary("Empty", Vec::new());
+  // five bytes leave the next value unaligned unless the blob is padded
+  dict.insert_binary("Odd", [1, 2, 3, 4, 5]);
+  dict.insert_u64("Long", u64::MAX);
+  let mut array = BymlWriterArray::new();
+  array.push_binary(b"abcd".to_vec());
+  dict.insert_array("Array", array);
+  let mut data = Vec::new();
+  BymlWriter::from_dictionary(dict)
+    .write::<LittleEndian>(&mut Cursor::new(&mut data), Version::V4)
+    .unwrap();
+
+  let root = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_dictionary();
+  assert_eq!(root.get_binary("Empty").unwrap(), Some(&[][..]));
+  assert_eq!(root.get_binary("Odd").unwrap(), Some(&[1, 2, 3, 4, 5][..]));
+  assert_eq!(root.get_u64("Long").unwrap(), Some(u64::MAX));
+  assert!(matches!(
+    root.get_u32("Odd"),
+    Err(ElementReadError::UnexpectedDataType {
+      expected: DataType::U32,
+      actual: DataType::Binary,
+      ..
+    })
+  ));
+  let array = root.get_array("Array").unwrap().unwrap();
+  assert_eq!(
+    array.get_element(0).unwrap(),
+    Some(BymlReaderNode::Binary(&b"abcd"[..]))
+  );
+}
+
+#[test]
+fn rejects_binary_values_past_the_end() {
+  const BLOB: u32 = ROOT_OFFSET + 16;
+
+  let mut root = array(&[(DataType::Binary, BLOB), (DataType::Binary, BLOB + 8)]);
+  root.extend(4u32.to_le_bytes());
+  root.extend(b"abc");
+  let data = document(&root);
+
+  let root = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  assert!(matches!(
+    root.get_binary(0),
+    Err(ElementReadError::BinaryOutOfBounds { length: 4, .. })
+  ));
+  assert!(matches!(
+    root.get_binary(1),
+    Err(ElementReadError::ValueOutOfBounds { .. })
+  ));
+}
+
+#[test]
+fn writes_every_version() {
+  let mut root = BymlWriterDict::new();
+  root.insert_string("name", "Link");
+  root.insert_f64("weight", 1.5);
+  let writer = BymlWriter::from_dictionary(root);
+
+  for (version, number) in [
+    (Version::V2, 2u16),
+    (Version::V3, 3),
+    (Version::V4, 4),
+    (Version::V5, 5),
+    (Version::V6, 6),
+    (Version::V7, 7),
+  ] {
+    assert_eq!(version.number(), number);
+    let mut data = Vec::new();
+    writer
+      .write::<LittleEndian>(&mut Cursor::new(&mut data), version)
+      .unwrap();
+    assert_eq!(data[2..4], number.to_le_bytes());
+
+    let root = BymlReader::<LittleEndian>::new(&data)
+      .unwrap()
+      .unwrap_dictionary();
+    assert_eq!(root.get_string("name").unwrap(), Some("Link"));
+    assert_eq!(root.get_f64("weight").unwrap(), Some(1.5));
+  }
+}
+
+#[test]
+fn binary_values_need_version_4() {
+  let mut root = BymlWriterArray::new();
+  root.push_binary([1, 2, 3]);
+  let writer = BymlWriter::from_array(root);
+
+  for version in [Version::V2, Version::V3] {
+    assert!(matches!(
+      writer.write::<LittleEndian>(&mut Cursor::new(Vec::new()), version),
+      Err(WriteError::BinaryNeedsVersion4 { version: actual, .. }) if actual == version.number()
+    ));
+  }
+  let mut data = Vec::new();
+  writer
+    .write::<LittleEndian>(&mut Cursor::new(&mut data), Version::V7)
+    .unwrap();
+  let root = BymlReader::<LittleEndian>::new(&data)
+    .unwrap()
+    .unwrap_array();
+  assert_eq!(root.get_binary(0).unwrap(), Some(&[1, 2, 3][..]));
+}
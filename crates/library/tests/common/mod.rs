@@ -0,0 +1,51 @@
+//! Builds small BYML documents in memory, so malformed input can be tested without committing
+//! binary fixtures.
+
+use std::io::Cursor;
+
+use senobi_library::byml::{
+  types::DataType,
+  writer::{BymlWriter, Version},
+};
+use zerocopy::LittleEndian;
+
+/// Where [`document`] places the root node, right after the header.
+pub const ROOT_OFFSET: u32 = 0x10;
+
+/// Writes `writer` as a little endian version 3 document.
+pub fn write(writer: &BymlWriter) -> Vec<u8> {
+  let mut data = Vec::new();
+  writer
+    .write::<LittleEndian>(&mut Cursor::new(&mut data), Version::V3)
+    .unwrap();
+  data
+}
+
+/// A little endian version 3 document without string tables, with `root` at [`ROOT_OFFSET`].
+pub fn document(root: &[u8]) -> Vec<u8> {
+  let mut data = b"YB".to_vec();
+  data.extend(3u16.to_le_bytes());
+  data.extend(0u32.to_le_bytes());
+  data.extend(0u32.to_le_bytes());
+  data.extend(ROOT_OFFSET.to_le_bytes());
+  data.extend(root);
+  data
+}
+
+/// A container header claiming `entries` entries, without anything after it.
+pub fn container_header(data_type: DataType, entries: u32) -> Vec<u8> {
+  let mut data = vec![data_type as u8];
+  data.extend(&entries.to_le_bytes()[..3]);
+  data
+}
+
+/// An array holding each `(data type, value)` pair, where containers' values are offsets.
+pub fn array(elements: &[(DataType, u32)]) -> Vec<u8> {
+  let mut data = container_header(DataType::Array, elements.len() as u32);
+  data.extend(elements.iter().map(|(data_type, _)| *data_type as u8));
+  data.resize(data.len().next_multiple_of(4), 0);
+  for (_, value) in elements {
+    data.extend(value.to_le_bytes());
+  }
+  data
+}
@@ -0,0 +1,63 @@
+use senobi_library::{
+  Error,
+  byml::reader::BymlDocument,
+  game_file::{FileKind, GameFile},
+  yaz0::DecompressionError,
+};
+
+const BED_SZS: &[u8] = include_bytes!("../examples/Bed.szs");
+const HOME_BED_BNTX: &[u8] = include_bytes!("../examples/HomeBed.bntx");
+
+#[test]
+fn opens_compressed_archives() {
+  let file = GameFile::open(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/Bed.szs")).unwrap();
+  assert!(file.was_compressed());
+  assert_eq!(file.kind(), Some(FileKind::Sarc));
+
+  let byml = file.as_sarc().unwrap().get("Bed.byml").unwrap().to_vec();
+  let byml = GameFile::from_bytes(byml).unwrap();
+  assert!(!byml.was_compressed());
+  assert_eq!(byml.kind(), Some(FileKind::Byml));
+  assert!(matches!(
+    byml.as_byml().unwrap(),
+    BymlDocument::LittleEndian(_)
+  ));
+
+  assert_eq!(GameFile::from_bytes(BED_SZS).unwrap().data(), file.data());
+}
+
+#[test]
+fn reports_the_wrong_format() {
+  let file = GameFile::from_bytes(HOME_BED_BNTX).unwrap();
+  assert_eq!(file.kind(), Some(FileKind::Bntx));
+  assert!(file.as_bntx().is_ok());
+  assert!(matches!(file.as_sarc(), Err(Error::SarcRead { .. })));
+
+  let unknown = GameFile::from_bytes(vec![0; 32]).unwrap();
+  assert_eq!(unknown.kind(), None);
+  assert!(unknown.as_byml().is_err());
+}
+
+#[test]
+fn limits_how_large_compressed_data_gets() {
+  let file = GameFile::from_bytes(BED_SZS).unwrap();
+  let size = file.data().len() as u32;
+  assert!(GameFile::from_bytes_with_limit(BED_SZS, size).is_ok());
+  assert!(matches!(
+    GameFile::from_bytes_with_limit(BED_SZS, size - 1),
+    Err(Error::Decompression {
+      source: DecompressionError::SizeLimitExceeded { .. },
+    })
+  ));
+
+  // a header claiming 4 GiB is rejected before anything is allocated
+  let mut header = b"Yaz0".to_vec();
+  header.extend(u32::MAX.to_be_bytes());
+  header.extend([0; 8]);
+  assert!(matches!(
+    GameFile::from_bytes(header),
+    Err(Error::Decompression {
+      source: DecompressionError::SizeLimitExceeded { .. },
+    })
+  ));
+}
@@ -0,0 +1,87 @@
+use senobi_library::{
+  magic,
+  nw::res_dict::{ResDictError, read_res_dict_indexed},
+};
+use snafu::{OptionExt, Snafu};
+use zerocopy::LittleEndian;
+
+#[derive(Snafu, Debug)]
+#[snafu(display("value {index} is out of bounds"))]
+struct ValueOutOfBounds {
+  index: usize,
+}
+
+/// Builds a dictionary of `keys` followed by their strings, with each node's value stored
+/// somewhere else entirely.
+fn dictionary(keys: &[&str]) -> Vec<u8> {
+  let mut data = magic::RES_DICT.to_vec();
+  data.extend((keys.len() as u32).to_le_bytes());
+  // the root node, which isn't one of the entries
+  data.extend([0; 16]);
+
+  let mut key_offset = 8 + 16 * (keys.len() + 1);
+  for key in keys {
+    data.extend(0u32.to_le_bytes());
+    data.extend([0; 4]);
+    data.extend((key_offset as u64).to_le_bytes());
+    key_offset += 2 + key.len() + 1;
+  }
+  for key in keys {
+    data.extend((key.len() as u16).to_le_bytes());
+    data.extend(key.as_bytes());
+    data.push(0);
+  }
+
+  data
+}
+
+/// Reads the length prefixed value that the `index`th entry of the offset table at `table` points
+/// to.
+fn read_value(data: &[u8], table: usize, index: usize) -> Option<&[u8]> {
+  let entry = data.get(table + index * 4..table + index * 4 + 4)?;
+  let offset = u32::from_le_bytes(entry.try_into().unwrap()) as usize;
+  let length = *data.get(offset)? as usize;
+  data.get(offset + 1..offset + 1 + length)
+}
+
+#[test]
+fn reads_values_that_are_not_laid_out_as_an_array() {
+  let mut data = dictionary(&["short", "long"]);
+  // the values differ in size and come in the opposite order to the nodes, found through a table
+  // of offsets
+  let table = data.len();
+  data.extend([0; 8]);
+  let long = data.len() as u32;
+  data.push(6);
+  data.extend(b"cdefgh");
+  let short = data.len() as u32;
+  data.push(2);
+  data.extend(b"ab");
+  data[table..table + 4].copy_from_slice(&short.to_le_bytes());
+  data[table + 4..table + 8].copy_from_slice(&long.to_le_bytes());
+
+  let dictionary =
+    read_res_dict_indexed::<_, LittleEndian, _>(&data, &magic::RES_DICT, 0, |index, _| {
+      read_value(&data, table, index).context(ValueOutOfBoundsSnafu { index })
+    })
+    .unwrap();
+  assert_eq!(dictionary.len(), 2);
+  assert_eq!(dictionary["short"], b"ab");
+  assert_eq!(dictionary["long"], b"cdefgh");
+
+  // the second node's value points past the end of the file
+  let mut broken = data.clone();
+  broken[table + 4..table + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+  let error =
+    read_res_dict_indexed::<_, LittleEndian, _>(&broken, &magic::RES_DICT, 0, |index, _| {
+      read_value(&broken, table, index).context(ValueOutOfBoundsSnafu { index })
+    })
+    .unwrap_err();
+  assert!(matches!(
+    error,
+    ResDictError::NodeReadFailed {
+      index: 1,
+      source: ValueOutOfBounds { index: 1 },
+    }
+  ));
+}
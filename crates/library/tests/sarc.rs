@@ -0,0 +1,344 @@
+use std::io::Cursor;
+
+use senobi_library::{
+  sarc::{
+    self,
+    reader::{ReadError, SarcArchive, SarcReader, VerifyError},
+    types::SfatNode,
+    writer::SarcWriter,
+  },
+  yaz0::{self, MAX_WINDOW},
+};
+use zerocopy::{IntoBytes, LittleEndian};
+
+const BED_SZS: &[u8] = include_bytes!("../examples/Bed.szs");
+
+fn bed_sarc() -> Box<[u8]> {
+  yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap()
+}
+
+#[test]
+fn verifies_name_hashes() {
+  let sarc = bed_sarc();
+  SarcReader::<LittleEndian>::new(&sarc)
+    .unwrap()
+    .verify()
+    .unwrap();
+}
+
+#[test]
+fn reports_mismatched_name_hashes() {
+  let mut sarc = bed_sarc().into_vec();
+  // the first node's hash sits right after the SARC and SFAT headers
+  sarc[0x20] ^= 0xFF;
+
+  let error = SarcReader::<LittleEndian>::new(&sarc)
+    .unwrap()
+    .verify()
+    .unwrap_err();
+  let VerifyError::HashMismatch { mismatches, .. } = error;
+  assert_eq!(mismatches.len(), 1);
+  assert_eq!(mismatches[0].index, 0);
+  assert_ne!(mismatches[0].stored, mismatches[0].computed);
+
+  assert!(matches!(
+    SarcReader::<LittleEndian>::new_validated(&sarc),
+    Err(ReadError::HashMismatch { offset: 0x20, expected, actual, .. })
+      if expected == mismatches[0].computed && actual == mismatches[0].stored
+  ));
+  assert!(SarcReader::<LittleEndian>::new_validated(&bed_sarc()).is_ok());
+  // nameless nodes are skipped
+  assert!(SarcReader::<LittleEndian>::new_validated(&nameless_sarc(&[(0x1234, b"data")])).is_ok());
+}
+
+/// Builds a little endian archive whose nodes all lack names.
+fn nameless_sarc(files: &[(u32, &[u8])]) -> Vec<u8> {
+  aligned_nameless_sarc(files, 1)
+}
+
+/// Like [`nameless_sarc`], but pads the start of each file to `alignment`.
+fn aligned_nameless_sarc(files: &[(u32, &[u8])], alignment: usize) -> Vec<u8> {
+  let nodes_end = 0x14 + 0xC + files.len() * 0x10;
+  let data_start = (nodes_end + 0x8).next_multiple_of(alignment);
+  let mut file_data = Vec::new();
+  let mut ranges = Vec::new();
+  for (_, data) in files {
+    file_data.resize(file_data.len().next_multiple_of(alignment), 0);
+    ranges.push((file_data.len() as u32, (file_data.len() + data.len()) as u32));
+    file_data.extend(*data);
+  }
+
+  let mut sarc = Vec::new();
+  sarc.extend(b"SARC");
+  sarc.extend(0x14u16.to_le_bytes());
+  sarc.extend(0xFEFFu16.to_le_bytes());
+  sarc.extend(((data_start + file_data.len()) as u32).to_le_bytes());
+  sarc.extend((data_start as u32).to_le_bytes());
+  sarc.extend(0x0100u16.to_le_bytes());
+  sarc.extend([0; 2]);
+
+  sarc.extend(b"SFAT");
+  sarc.extend(0xCu16.to_le_bytes());
+  sarc.extend((files.len() as u16).to_le_bytes());
+  sarc.extend(0x65u32.to_le_bytes());
+  for ((hash, _), (start, end)) in files.iter().zip(ranges) {
+    sarc.extend(SfatNode::<LittleEndian>::nameless(*hash, start, end).as_bytes());
+  }
+
+  sarc.extend(b"SFNT");
+  sarc.extend(0x8u16.to_le_bytes());
+  sarc.extend([0; 2]);
+  sarc.resize(data_start, 0);
+  sarc.extend(file_data);
+  sarc
+}
+
+#[test]
+fn reads_all_nameless_archives() {
+  let sarc = nameless_sarc(&[(0x1234, b"first"), (0x5678, b"second")]);
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+
+  let entries = reader.entries().collect::<Vec<_>>();
+  assert_eq!(entries, [(None, &b"first"[..]), (None, &b"second"[..])]);
+  assert_eq!(reader.entries().len(), 2);
+  assert_eq!((&reader).into_iter().collect::<Vec<_>>(), entries);
+  assert_eq!(reader.get("first"), None);
+  reader.verify().unwrap();
+}
+
+#[test]
+fn reads_nameless_archives_without_file_data() {
+  let sarc = nameless_sarc(&[(0x1234, b"")]);
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  assert_eq!(reader.entries().collect::<Vec<_>>(), [(None, &b""[..])]);
+
+  let sarc = nameless_sarc(&[]);
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  assert_eq!(reader.entries().count(), 0);
+}
+
+#[test]
+fn encodes_node_attributes() {
+  let named = SfatNode::<LittleEndian>::named(0x1234, 0x40, 0, 4).unwrap();
+  assert_eq!(named.file_attributes.get(), 0x01000010);
+  assert_eq!(named.name_offset(), Some(0x40));
+
+  let nameless = SfatNode::<LittleEndian>::nameless(0x1234, 0, 4);
+  assert_eq!(nameless.file_attributes.get(), 0);
+  assert_eq!(nameless.name_offset(), None);
+
+  assert!(SfatNode::<LittleEndian>::named(0, 0x41, 0, 0).is_none());
+  assert!(SfatNode::<LittleEndian>::named(0, 0x40000, 0, 0).is_none());
+}
+
+#[test]
+fn rejects_bad_versions_and_header_lengths() {
+  let sarc = bed_sarc();
+
+  let mut bad_version = sarc.to_vec();
+  bad_version[0x10..0x12].copy_from_slice(&0x0200u16.to_le_bytes());
+  assert!(matches!(
+    SarcReader::<LittleEndian>::new(&bad_version),
+    Err(ReadError::UnsupportedVersion { actual: 0x0200, .. })
+  ));
+
+  let mut bad_length = sarc.to_vec();
+  bad_length[0x4..0x6].copy_from_slice(&0x10u16.to_le_bytes());
+  assert!(matches!(
+    SarcReader::<LittleEndian>::new(&bad_length),
+    Err(ReadError::IncorrectHeaderLength { actual: 0x10, .. })
+  ));
+}
+
+#[test]
+fn infers_data_alignment() {
+  let sarc = bed_sarc();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  assert_eq!(reader.data_alignment(), 0x40);
+
+  let sarc = nameless_sarc(&[(0x1234, b"first"), (0x5678, b"second")]);
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  assert_eq!(reader.data_alignment(), 1);
+
+  let sarc = aligned_nameless_sarc(&[(0, b"first"), (0, b"second")], 0x10);
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  assert_eq!(reader.data_alignment(), 0x10);
+}
+
+#[test]
+fn archive_detects_byte_order() {
+  let sarc = bed_sarc();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+  let archive = SarcArchive::new(&sarc).unwrap();
+  assert!(matches!(archive, SarcArchive::LittleEndian(_)));
+  assert_eq!(archive.len(), reader.len());
+  assert!(archive.entries().eq(reader.entries()));
+  archive.verify().unwrap();
+
+  let mut bad_bom = sarc.to_vec();
+  bad_bom[0x6..0x8].copy_from_slice(&[0x12, 0x34]);
+  assert!(matches!(
+    SarcArchive::new(&bad_bom),
+    Err(ReadError::InvalidByteOrderMark { actual: [0x12, 0x34], .. })
+  ));
+}
+
+#[test]
+fn reports_consumed_length() {
+  let mut sarc = nameless_sarc(&[(0x1234, b"first"), (0x5678, b"second")]);
+  let length = sarc.len();
+  assert_eq!(
+    SarcReader::<LittleEndian>::new(&sarc).unwrap().consumed_len(),
+    length
+  );
+
+  sarc.extend([0; 0x10]);
+  assert_eq!(SarcArchive::new(&sarc).unwrap().consumed_len(), length);
+}
+
+#[test]
+fn rebuilds_archives_from_readers() {
+  const HOME_BED_SZS: &[u8] = include_bytes!("../examples/HomeBed.szs");
+
+  let home_bed = yaz0::decompress(&mut Cursor::new(HOME_BED_SZS)).unwrap();
+  let nameless = aligned_nameless_sarc(&[(0x1234, b"first"), (0x5678, b"second")], 0x10);
+  for sarc in [&*bed_sarc(), &*home_bed, &nameless[..]] {
+    let reader = SarcReader::<LittleEndian>::new(sarc).unwrap();
+    let mut rebuilt = Vec::new();
+    SarcWriter::from_reader(&reader)
+      .write(&mut rebuilt)
+      .unwrap();
+
+    assert_eq!(rebuilt, sarc);
+  }
+}
+
+#[cfg(feature = "manifest")]
+#[test]
+fn rebuilds_archives_from_manifests() {
+  use senobi_library::sarc::manifest::SarcManifest;
+
+  const HOME_BED_SZS: &[u8] = include_bytes!("../examples/HomeBed.szs");
+
+  let sarc = yaz0::decompress(&mut Cursor::new(HOME_BED_SZS)).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+
+  let mut json = Vec::new();
+  reader.write_manifest(&mut json).unwrap();
+  let manifest = SarcManifest::read(json.as_slice()).unwrap();
+  assert_eq!(manifest, reader.manifest());
+  assert_eq!(manifest.entries.len(), reader.len());
+
+  let writer = SarcWriter::<LittleEndian>::from_manifest(&manifest, |entry| {
+    let name = entry.name.as_deref().unwrap();
+    reader.get(name).map(<[u8]>::to_vec).ok_or(name.to_owned())
+  })
+  .unwrap();
+  let mut rebuilt = Vec::new();
+  writer.write(&mut rebuilt).unwrap();
+
+  assert_eq!(rebuilt, *sarc);
+}
+
+#[test]
+fn writes_archives_from_scratch() {
+  let mut writer = SarcWriter::<LittleEndian>::new();
+  writer.add_file("b.byml", b"second");
+  writer.add_file("a.byml", b"first");
+  writer.add_unnamed(0x1234, b"nameless");
+  writer.add_file_aligned("model.bfres", b"model", 0x100);
+  let mut data = Vec::new();
+  writer.write(&mut data).unwrap();
+
+  // the SARC and SFAT header lengths
+  assert_eq!(data[4..6], 0x14u16.to_le_bytes());
+  assert_eq!(data[0x18..0x1A], 0xCu16.to_le_bytes());
+
+  let reader = SarcReader::<LittleEndian>::new(&data).unwrap();
+  reader.verify().unwrap();
+  assert_eq!(reader.hash_key(), 0x65);
+  assert_eq!(reader.len(), 4);
+  assert_eq!(reader.get("a.byml"), Some(&b"first"[..]));
+  assert_eq!(reader.get("b.byml"), Some(&b"second"[..]));
+  assert!(
+    reader
+      .entries()
+      .any(|(name, file)| name.is_none() && file == b"nameless")
+  );
+
+  let model = reader.get("model.bfres").unwrap();
+  assert_eq!(model, b"model");
+  let offset = model.as_ptr() as usize - data.as_ptr() as usize;
+  assert_eq!(offset % 0x100, 0);
+}
+
+#[test]
+fn write_compressed_decompresses_to_write() {
+  let mut writer = SarcWriter::<LittleEndian>::new();
+  writer.add_file("Bed.byml", b"bed");
+  writer.add_file("Bed.bfres", b"model");
+  let mut data = Vec::new();
+  writer.write(&mut data).unwrap();
+
+  let mut compressed = Vec::new();
+  writer
+    .write_compressed(&mut compressed, MAX_WINDOW)
+    .unwrap();
+  assert_eq!(
+    *yaz0::decompress(&mut Cursor::new(&compressed)).unwrap(),
+    *data
+  );
+}
+
+#[test]
+fn finds_files_by_hash() {
+  fn linear_get<'a>(reader: &SarcReader<'a, LittleEndian>, search_name: &str) -> Option<&'a [u8]> {
+    reader
+      .entries()
+      .find_map(|(name, data)| (name?.to_bytes() == search_name.as_bytes()).then_some(data))
+  }
+
+  let mut writer = SarcWriter::<LittleEndian>::new();
+  for index in 0..500 {
+    let name = format!("Object{index}.byml");
+    writer.add_file(&name, index.to_string().as_bytes());
+  }
+  let mut data = Vec::new();
+  writer.write(&mut data).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&data).unwrap();
+
+  for index in 0..510 {
+    let name = format!("Object{index}.byml");
+    assert_eq!(reader.get(&name), linear_get(&reader, &name));
+  }
+  assert_eq!(reader.get("Object42.byml"), Some(&b"42"[..]));
+
+  // with a hash key of 1 a name hashes to the sum of its bytes, so anagrams collide
+  let mut writer = SarcWriter::<LittleEndian>::with_hash_key(1);
+  writer.add_file("ab", b"first");
+  writer.add_file("ba", b"second");
+  writer.add_unnamed(sarc::compute_hash("ab", 1), b"nameless");
+  let mut data = Vec::new();
+  writer.write(&mut data).unwrap();
+  let reader = SarcReader::<LittleEndian>::new(&data).unwrap();
+
+  assert_eq!(reader.get("ab"), Some(&b"first"[..]));
+  assert_eq!(reader.get("ba"), Some(&b"second"[..]));
+  assert_eq!(reader.get("aa"), None);
+}
+
+#[test]
+fn gets_files_by_index() {
+  let sarc = bed_sarc();
+  let reader = SarcReader::<LittleEndian>::new(&sarc).unwrap();
+
+  assert!(!reader.is_empty());
+  for (index, entry) in reader.entries().enumerate() {
+    assert_eq!(reader.get_by_index(index), Some(entry));
+  }
+  assert_eq!(reader.get_by_index(reader.len()), None);
+  assert_eq!(
+    SarcArchive::new(&sarc).unwrap().get_by_index(0),
+    reader.get_by_index(0)
+  );
+}
@@ -0,0 +1,285 @@
+use std::io::{self, Cursor, Read, Write};
+
+use senobi_library::{
+  magic,
+  yaz0::{
+    self, CompressionError, DecompressionError, MAX_WINDOW, PROGRESS_INTERVAL, Yaz0Decoder,
+    Yaz0Encoder,
+  },
+};
+
+const BED_SZS: &[u8] = include_bytes!("../examples/Bed.szs");
+const HOME_BED_SZS: &[u8] = include_bytes!("../examples/HomeBed.szs");
+
+fn round_trip(data: &[u8], window: u16) -> Box<[u8]> {
+  let mut compressed = Vec::new();
+  yaz0::compress(&mut Cursor::new(data), &mut compressed, window).unwrap();
+  yaz0::decompress(&mut Cursor::new(compressed)).unwrap()
+}
+
+#[test]
+fn decompresses_overlapping_copies() {
+  let decompressed = yaz0::decompress(&mut Cursor::new(HOME_BED_SZS)).unwrap();
+  assert_eq!(*decompressed, *szs::decode(HOME_BED_SZS).unwrap());
+}
+
+#[test]
+fn decodes_short_and_long_copy_counts() {
+  let mut stream = b"Yaz0".to_vec();
+  stream.extend(314u32.to_be_bytes());
+  stream.extend([0; 8]);
+  stream.extend([
+    0b1110_0000,
+    b'a',
+    b'b',
+    b'c',
+    // short copy of the most it can hold, 0x11 bytes from 3 back
+    0xF0,
+    0x02,
+    // long copy of the least it can hold, 0x12 bytes from 1 back
+    0x00,
+    0x00,
+    0x00,
+    // long copy of the most it can hold, 0x111 bytes from 2 back
+    0x00,
+    0x01,
+    0xFF,
+    // short copy of the least it can hold, 3 bytes from the start
+    0x11,
+    0x36,
+  ]);
+
+  let mut expected = b"abc".repeat(7)[..20].to_vec();
+  expected.extend([b'b'; 0x12]);
+  expected.extend([b'b'; 0x111]);
+  expected.extend(b"abc");
+
+  assert_eq!(*yaz0::decompress(&mut Cursor::new(stream)).unwrap(), *expected);
+}
+
+#[test]
+fn round_trips_copy_count_boundaries() {
+  for count in [0x3, 0x11, 0x12, 0x13, 0x111, 0x112] {
+    let mut data = b"xy".to_vec();
+    data.extend(std::iter::repeat_n(b'z', count));
+    assert_eq!(*round_trip(&data, MAX_WINDOW), *data);
+  }
+}
+
+#[test]
+fn compress_round_trips() {
+  let sarc = yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap();
+  for window in [0, 1, 0x100, MAX_WINDOW] {
+    assert_eq!(round_trip(&sarc, window), sarc);
+  }
+
+  assert!(round_trip(&[], MAX_WINDOW).is_empty());
+}
+
+#[test]
+fn compress_writes_the_header() {
+  let data = vec![b'z'; 0x1000];
+  let mut compressed = Vec::new();
+  yaz0::compress(&mut Cursor::new(&data), &mut compressed, MAX_WINDOW).unwrap();
+
+  assert_eq!(compressed[..4], magic::YAZ0);
+  assert_eq!(compressed[4..8], 0x1000u32.to_be_bytes());
+  assert_eq!(compressed[8..16], [0; 8]);
+  // the run after the first byte is made of long copies from a distance of 1
+  assert!(compressed.len() < 16 + 0x1000 / 0x111 * 3 + 0x10);
+  assert_eq!(
+    *yaz0::decompress(&mut Cursor::new(compressed)).unwrap(),
+    *data
+  );
+}
+
+#[test]
+fn decompresses_with_known_size() {
+  let mut reader = Cursor::new(BED_SZS);
+  let size = yaz0::decompressed_size(&mut reader).unwrap();
+  let decompressed = yaz0::decompress_with_size(&mut reader, size).unwrap();
+
+  assert_eq!(
+    decompressed,
+    yaz0::decompress(&mut Cursor::new(BED_SZS)).unwrap()
+  );
+}
+
+#[test]
+fn encoder_streams_across_chunks() {
+  // long enough that the encoder compresses some of it before `finish`
+  let mut data = Vec::new();
+  let mut state = 0x1234_5678u32;
+  while data.len() < 0x30000 {
+    state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+    let run = (state >> 24) as usize % 0x140;
+    data.extend(std::iter::repeat_n((state >> 16) as u8, run));
+    data.extend(state.to_le_bytes());
+  }
+
+  let mut encoder = Yaz0Encoder::new(Vec::new(), data.len() as u32, MAX_WINDOW).unwrap();
+  for chunk in data.chunks(0x777) {
+    io::copy(&mut Cursor::new(chunk), &mut encoder).unwrap();
+  }
+  let compressed = encoder.finish().unwrap();
+
+  assert_eq!(*yaz0::decompress(&mut Cursor::new(compressed)).unwrap(), *data);
+}
+
+#[test]
+fn encoder_checks_written_size() {
+  let mut encoder = Yaz0Encoder::new(Vec::new(), 4, MAX_WINDOW).unwrap();
+  encoder.write_all(b"abc").unwrap();
+  assert!(encoder.write_all(b"de").is_err());
+  assert!(matches!(
+    encoder.finish(),
+    Err(CompressionError::IncompleteInput {
+      expected: 4,
+      written: 3,
+      ..
+    })
+  ));
+}
+
+#[test]
+fn reports_the_expected_magic() {
+  let mut data = vec![0; 16];
+  data[..4].copy_from_slice(b"Yaz1");
+
+  let Err(DecompressionError::IncorrectMagic { source }) = yaz0::decompress(&mut Cursor::new(data))
+  else {
+    panic!("decompressed data with the wrong magic");
+  };
+  assert_eq!(*source.expected, magic::YAZ0);
+  assert_eq!(*source.actual, *b"Yaz1");
+}
+
+#[test]
+fn reports_decompression_progress() {
+  let mut progress = Vec::new();
+  let decompressed =
+    yaz0::decompress_with_progress(&mut Cursor::new(HOME_BED_SZS), |done, total| {
+      progress.push((done, total))
+    })
+    .unwrap();
+
+  let total = decompressed.len() as u32;
+  assert_eq!(progress.last(), Some(&(total, total)));
+  assert!(progress.len() as u32 >= total / PROGRESS_INTERVAL);
+  assert!(progress.iter().all(|&(_, reported)| reported == total));
+  // every report but the final one is at least an interval after the previous
+  let reports = &progress[..progress.len() - 1];
+  assert!(reports[0].0 >= PROGRESS_INTERVAL);
+  assert!(
+    reports
+      .windows(2)
+      .all(|pair| pair[1].0 - pair[0].0 >= PROGRESS_INTERVAL)
+  );
+}
+
+#[test]
+fn validates_trailing_data() {
+  // Bed.szs is padded with a zero after its last group
+  yaz0::validate(&mut Cursor::new(BED_SZS)).unwrap();
+  yaz0::validate(&mut Cursor::new(HOME_BED_SZS)).unwrap();
+
+  let mut padded = HOME_BED_SZS.to_vec();
+  padded.extend([0; 16]);
+  yaz0::validate(&mut Cursor::new(&padded)).unwrap();
+
+  let mut corrupted = padded;
+  corrupted.extend([0x80, b'x']);
+  assert!(yaz0::decompress(&mut Cursor::new(&corrupted)).is_ok());
+  assert!(matches!(
+    yaz0::validate(&mut Cursor::new(&corrupted)),
+    Err(DecompressionError::TrailingData {
+      offset,
+      size: 18,
+      first_nonzero,
+      ..
+    }) if offset == HOME_BED_SZS.len() as u64 && first_nonzero == offset + 16
+  ));
+}
+
+#[test]
+fn refuses_sizes_past_the_limit() {
+  // a bare header claiming 4 GiB
+  let mut data = magic::YAZ0.to_vec();
+  data.extend(u32::MAX.to_be_bytes());
+  data.extend([0; 8]);
+
+  assert!(matches!(
+    yaz0::decompress_with_limit(&mut Cursor::new(&data), 0x100_0000),
+    Err(DecompressionError::SizeLimitExceeded {
+      claimed: u32::MAX,
+      limit: 0x100_0000,
+      ..
+    })
+  ));
+
+  let size = yaz0::decompressed_size(&mut Cursor::new(BED_SZS)).unwrap();
+  assert!(yaz0::decompress_with_limit(&mut Cursor::new(BED_SZS), size).is_ok());
+  assert!(yaz0::decompress_with_limit(&mut Cursor::new(BED_SZS), size - 1).is_err());
+}
+
+#[test]
+fn decoder_streams_output() {
+  for szs in [BED_SZS, HOME_BED_SZS] {
+    let expected = yaz0::decompress(&mut Cursor::new(szs)).unwrap();
+
+    let mut decoder = Yaz0Decoder::new(szs).unwrap();
+    assert_eq!(decoder.decompressed_size() as usize, expected.len());
+    let mut decoded = Vec::new();
+    io::copy(&mut decoder, &mut decoded).unwrap();
+    assert_eq!(*decoded, *expected);
+
+    // small reads split copies between calls
+    let mut decoder = Yaz0Decoder::new(szs).unwrap();
+    let mut decoded = Vec::<u8>::new();
+    let mut buffer = [0; 5];
+    loop {
+      match decoder.read(&mut buffer).unwrap() {
+        0 => break,
+        read => decoded.extend(&buffer[..read]),
+      }
+    }
+    assert_eq!(*decoded, *expected);
+  }
+}
+
+#[test]
+fn decoder_rejects_copies_from_before_the_start() {
+  let mut data = magic::YAZ0.to_vec();
+  data.extend(4u32.to_be_bytes());
+  data.extend([0; 8]);
+  // a copy of 4 bytes from 1 byte back, with nothing decoded yet
+  data.extend([0x00, 0x20, 0x00]);
+
+  let error = io::copy(&mut Yaz0Decoder::new(&data[..]).unwrap(), &mut io::sink()).unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+  assert!(matches!(
+    error
+      .into_inner()
+      .unwrap()
+      .downcast_ref::<DecompressionError>(),
+    Some(DecompressionError::CopyingFromBeforeStart { .. })
+  ));
+}
+
+#[test]
+fn reads_the_alignment_hint() {
+  let info = yaz0::read_info(&mut Cursor::new(HOME_BED_SZS)).unwrap();
+  assert_eq!(info.alignment, 0x1000);
+  assert_eq!(
+    info.uncompressed_size,
+    yaz0::decompressed_size(&mut Cursor::new(HOME_BED_SZS)).unwrap()
+  );
+  let info = yaz0::read_info(&mut Cursor::new(BED_SZS)).unwrap();
+  assert_eq!(info.alignment, 0x80);
+
+  // the encoder leaves it unspecified
+  let mut compressed = Vec::new();
+  yaz0::compress(&mut Cursor::new(b"data"), &mut compressed, MAX_WINDOW).unwrap();
+  let info = yaz0::read_info(&mut Cursor::new(compressed)).unwrap();
+  assert_eq!(info.alignment, 0);
+}
@@ -0,0 +1,257 @@
+//! Loading a BYML document and laying out the tree the tree view shows, where every node
+//! remembers which bytes it came from.
+
+use std::{
+  ffi::CStr,
+  fs,
+  io::Cursor,
+  ops::Range,
+  path::{Path, PathBuf},
+};
+
+use senobi_library::{
+  byml::{
+    self, Region, RegionAnnotation,
+    raw::{RawContainer, RawContainerBody, RawElement},
+    types::{ContainerHeader, DataType, Header, TryDictEntry},
+  },
+  magic, yaz0,
+};
+use zerocopy::{BigEndian, ByteOrder, FromBytes, LittleEndian, U32, U64};
+
+pub struct Document {
+  pub path: PathBuf,
+  pub data: Vec<u8>,
+  /// Sorted by start offset, as returned by [`byml::annotate`].
+  pub annotations: Vec<RegionAnnotation>,
+  /// `None` for documents without a root node.
+  pub root: Option<TreeNode>,
+}
+
+pub struct TreeNode {
+  pub label: String,
+  /// The bytes behind the node: a container's header and body, the 8 bytes a 64-bit value
+  /// points to, a binary value's length and bytes, or the slot in its parent that any other
+  /// value is stored in.
+  pub range: Range<usize>,
+  pub children: Vec<TreeNode>,
+}
+
+impl Document {
+  /// Reads a BYML document, decompressing it first if it's Yaz0 compressed.
+  pub fn open(path: &Path) -> Result<Self, String> {
+    let mut data =
+      fs::read(path).map_err(|error| format!("couldn't read {}: {error}", path.display()))?;
+    if data.starts_with(&magic::YAZ0) {
+      data = yaz0::decompress(&mut Cursor::new(&data))
+        .map_err(|error| format!("couldn't decompress {}: {error}", path.display()))?
+        .into_vec();
+    }
+
+    let (annotations, root) = match data.get(..2) {
+      Some(magic) if magic == magic::BYML_LITTLE_ENDIAN => lay_out::<LittleEndian>(&data),
+      Some(magic) if magic == magic::BYML_BIG_ENDIAN => lay_out::<BigEndian>(&data),
+      _ => return Err(format!("{} isn't a BYML document", path.display())),
+    };
+
+    Ok(Self {
+      path: path.to_owned(),
+      data,
+      annotations,
+      root,
+    })
+  }
+
+  /// The annotation covering `offset`, if any.
+  pub fn annotation_at(&self, offset: usize) -> Option<&RegionAnnotation> {
+    let index = self
+      .annotations
+      .partition_point(|annotation| annotation.range.start <= offset);
+    index
+      .checked_sub(1)
+      .map(|index| &self.annotations[index])
+      .filter(|annotation| annotation.range.contains(&offset))
+  }
+}
+
+fn lay_out<O: ByteOrder>(data: &[u8]) -> (Vec<RegionAnnotation>, Option<TreeNode>) {
+  let annotations = byml::annotate::<O>(data);
+  let Ok((header, _)) = Header::<O>::ref_from_prefix(data) else {
+    return (annotations, None);
+  };
+
+  let table = |offset: u32| match offset {
+    0 => None,
+    offset => RawContainer::<O>::new(data, offset).ok(),
+  };
+  let layout = Layout {
+    data,
+    annotations: &annotations,
+    hash_keys: table(header.hash_key_offset.get()),
+    strings: table(header.string_table_offset.get()),
+  };
+
+  let root = match header.root_node_offset.get() {
+    0 => None,
+    offset => Some(layout.container("root".to_owned(), offset, &mut Vec::new())),
+  };
+  (annotations, root)
+}
+
+struct Layout<'a, O> {
+  data: &'a [u8],
+  annotations: &'a [RegionAnnotation],
+  hash_keys: Option<RawContainer<'a, O>>,
+  strings: Option<RawContainer<'a, O>>,
+}
+
+impl<'a, O: ByteOrder> Layout<'a, O> {
+  /// `ancestors` holds the offsets of the containers above this one, so that a container
+  /// pointing back at one of them doesn't recurse forever.
+  fn container(&self, name: String, offset: u32, ancestors: &mut Vec<u32>) -> TreeNode {
+    let header_range = offset as usize..offset as usize + size_of::<ContainerHeader<O>>();
+    if ancestors.contains(&offset) {
+      return leaf(format!("{name}: cycle back to 0x{offset:X}"), header_range);
+    }
+    let container = match RawContainer::<O>::new(self.data, offset) {
+      Ok(container) => container,
+      Err(error) => return leaf(format!("{name}: {error}"), header_range),
+    };
+
+    let range = self.node_range(offset).unwrap_or(header_range.clone());
+    let values_start = (header_range.end + container.entries() as usize).next_multiple_of(4);
+
+    ancestors.push(offset);
+    let children = container
+      .elements()
+      .enumerate()
+      .map(|(index, element)| {
+        let (name, slot) = match element.key_index {
+          Some(key_index) => {
+            let start = header_range.end + index * size_of::<TryDictEntry<O>>();
+            (
+              self.key(key_index),
+              start..start + size_of::<TryDictEntry<O>>(),
+            )
+          }
+          None => {
+            let start = values_start + index * size_of::<u32>();
+            (format!("[{index}]"), start..start + size_of::<u32>())
+          }
+        };
+        self.element(name, element, slot, ancestors)
+      })
+      .collect::<Vec<_>>();
+    ancestors.pop();
+
+    let kind = match container.data_type() {
+      value if value == DataType::Array as u8 => "array",
+      _ => "dictionary",
+    };
+    TreeNode {
+      label: format!("{name}: {kind} of {}", children.len()),
+      range,
+      children,
+    }
+  }
+
+  fn element(
+    &self,
+    name: String,
+    element: RawElement,
+    slot: Range<usize>,
+    ancestors: &mut Vec<u32>,
+  ) -> TreeNode {
+    let value = element.value;
+    let long = || {
+      let start = value as usize;
+      let bits = self
+        .data
+        .get(start..start + size_of::<u64>())
+        .and_then(|bytes| U64::<O>::read_from_bytes(bytes).ok())?;
+      Some((bits.get(), start..start + size_of::<u64>()))
+    };
+    let binary = || {
+      let start = value as usize;
+      let (length, _) = U32::<O>::read_from_prefix(self.data.get(start..)?).ok()?;
+      let end = start + size_of::<u32>() + length.get() as usize;
+      (end <= self.data.len()).then_some((length.get(), start..end))
+    };
+
+    let (text, range) = match element.data_type {
+      data_type
+        if data_type == DataType::Array as u8 || data_type == DataType::Dictionary as u8 =>
+      {
+        return self.container(name, value, ancestors);
+      }
+      data_type if data_type == DataType::String as u8 => (
+        match self.string(self.strings.as_ref(), value) {
+          Some(string) => format!("{string:?}"),
+          None => format!("string {value} is missing"),
+        },
+        slot,
+      ),
+      data_type if data_type == DataType::Bool as u8 => ((value != 0).to_string(), slot),
+      data_type if data_type == DataType::I32 as u8 => ((value as i32).to_string(), slot),
+      data_type if data_type == DataType::F32 as u8 => (f32::from_bits(value).to_string(), slot),
+      data_type if data_type == DataType::U32 as u8 => (value.to_string(), slot),
+      data_type
+        if data_type == DataType::I64 as u8
+          || data_type == DataType::U64 as u8
+          || data_type == DataType::F64 as u8 =>
+      {
+        match long() {
+          Some((bits, range)) if data_type == DataType::I64 as u8 => {
+            (bits.cast_signed().to_string(), range)
+          }
+          Some((bits, range)) if data_type == DataType::U64 as u8 => (bits.to_string(), range),
+          Some((bits, range)) => (f64::from_bits(bits).to_string(), range),
+          None => (format!("value at 0x{value:X} is out of bounds"), slot),
+        }
+      }
+      data_type if data_type == DataType::Binary as u8 => match binary() {
+        Some((length, range)) => (format!("{length} bytes"), range),
+        None => (format!("value at 0x{value:X} is out of bounds"), slot),
+      },
+      data_type if data_type == DataType::Null as u8 => ("null".to_owned(), slot),
+      data_type => (format!("unknown data type 0x{data_type:02X}"), slot),
+    };
+    leaf(format!("{name}: {text}"), range)
+  }
+
+  fn key(&self, index: u32) -> String {
+    match self.string(self.hash_keys.as_ref(), index) {
+      Some(key) => key.to_string_lossy().into_owned(),
+      None => format!("<key {index}>"),
+    }
+  }
+
+  fn string(&self, table: Option<&RawContainer<'a, O>>, index: u32) -> Option<&'a CStr> {
+    let table = table?;
+    let RawContainerBody::StringTable { offsets } = &table.body else {
+      return None;
+    };
+    let start = table.offset as usize + offsets.get(index as usize)?.get() as usize;
+    CStr::from_bytes_until_nul(self.data.get(start..)?).ok()
+  }
+
+  /// The range [`byml::annotate`] found for the container at `offset`.
+  fn node_range(&self, offset: u32) -> Option<Range<usize>> {
+    let start = self
+      .annotations
+      .partition_point(|annotation| annotation.range.start < offset as usize);
+    self.annotations[start..]
+      .iter()
+      .take_while(|annotation| annotation.range.start == offset as usize)
+      .find(|annotation| matches!(annotation.region, Region::Node { .. }))
+      .map(|annotation| annotation.range.clone())
+  }
+}
+
+fn leaf(label: String, range: Range<usize>) -> TreeNode {
+  TreeNode {
+    label,
+    range,
+    children: Vec::new(),
+  }
+}
@@ -0,0 +1,104 @@
+use eframe::egui::{Color32, Label, RichText, ScrollArea, Sense, TextStyle, Ui};
+use senobi_library::byml::{Region, types::DataType};
+
+use crate::{Selection, document::Document};
+
+const BYTES_PER_ROW: usize = 16;
+
+/// Shows the document's bytes, colored by the region they belong to. Clicking a byte selects
+/// its whole region.
+pub fn show(ui: &mut Ui, document: &Document, selection: &mut Selection) {
+  let row_height = ui.text_style_height(&TextStyle::Monospace);
+  let rows = document.data.len().div_ceil(BYTES_PER_ROW);
+
+  let mut scroll_area = ScrollArea::both().auto_shrink(false);
+  if let Some(range) = selection.range.as_ref().filter(|_| selection.reveal) {
+    let row = range.start / BYTES_PER_ROW;
+    scroll_area =
+      scroll_area.vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+  }
+  selection.reveal = false;
+
+  scroll_area.show_rows(ui, row_height, rows, |ui, rows| {
+    for row in rows {
+      let start = row * BYTES_PER_ROW;
+      let bytes = &document.data[start..(start + BYTES_PER_ROW).min(document.data.len())];
+
+      ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        ui.label(RichText::new(format!("{start:08X}  ")).monospace().weak());
+
+        for (column, byte) in bytes.iter().enumerate() {
+          let offset = start + column;
+          let annotation = document.annotation_at(offset);
+          let selected = selection
+            .range
+            .as_ref()
+            .is_some_and(|range| range.contains(&offset));
+
+          let mut text = RichText::new(format!("{byte:02X} ")).monospace();
+          if selected {
+            let visuals = &ui.visuals().selection;
+            text = text
+              .background_color(visuals.bg_fill)
+              .color(visuals.stroke.color);
+          } else if let Some(annotation) = annotation {
+            text = text.background_color(region_color(annotation.region));
+          }
+
+          let response = ui.add(Label::new(text).selectable(false).sense(Sense::click()));
+          if let Some(annotation) = annotation {
+            let range = &annotation.range;
+            let response = response.on_hover_text(format!(
+              "{} 0x{:X}..0x{:X}",
+              region_name(annotation.region),
+              range.start,
+              range.end
+            ));
+            if response.clicked() {
+              selection.range = Some(range.clone());
+            }
+          }
+        }
+
+        let padding = "   ".repeat(BYTES_PER_ROW - bytes.len());
+        let ascii = bytes
+          .iter()
+          .map(|&byte| match byte {
+            b' ' | 0x21..=0x7E => byte as char,
+            _ => '.',
+          })
+          .collect::<String>();
+        ui.label(RichText::new(format!("{padding} {ascii}")).monospace());
+      });
+    }
+  });
+}
+
+fn region_name(region: Region) -> String {
+  match region {
+    Region::Header => "header".to_owned(),
+    Region::HashKeyTable => "hash key table".to_owned(),
+    Region::StringTable => "string table".to_owned(),
+    Region::Node { offset, data_type } if data_type == DataType::Array as u8 => {
+      format!("array at 0x{offset:X}")
+    }
+    Region::Node { offset, .. } => format!("dictionary at 0x{offset:X}"),
+    Region::LongValue => "64-bit value".to_owned(),
+    Region::Binary => "binary value".to_owned(),
+  }
+}
+
+/// Translucent, so that text stays readable with both the light and dark themes.
+fn region_color(region: Region) -> Color32 {
+  let (r, g, b) = match region {
+    Region::Header => (0x9C, 0x6A, 0xDE),
+    Region::HashKeyTable => (0x3D, 0x9E, 0xD9),
+    Region::StringTable => (0x3D, 0xC2, 0xA6),
+    Region::Node { data_type, .. } if data_type == DataType::Array as u8 => (0xE0, 0x9A, 0x3A),
+    Region::Node { .. } => (0xD9, 0x5C, 0x5C),
+    Region::LongValue => (0x8C, 0xB8, 0x45),
+    Region::Binary => (0xC8, 0xB0, 0x3C),
+  };
+  Color32::from_rgba_unmultiplied(r, g, b, 0x50)
+}
@@ -1,18 +1,126 @@
-use eframe::NativeOptions;
+mod document;
+mod hex_view;
+mod tree_view;
+
+use std::{env, ops::Range, path::Path};
+
+use eframe::{
+  NativeOptions,
+  egui::{self, WidgetText},
+};
+use egui_dock::{DockArea, DockState, NodeIndex, Style, TabViewer};
+
+use crate::document::Document;
 
 fn main() {
+  let document = env::args_os()
+    .nth(1)
+    .map(|path| Document::open(Path::new(&path)));
+
   eframe::run_native(
     "orin",
     NativeOptions::default(),
-    Box::new(|_| Ok(Box::new(App {}))),
+    Box::new(|_| Ok(Box::new(App::new(document)))),
   )
   .unwrap();
 }
 
-struct App {}
+enum Tab {
+  Tree,
+  Hex,
+}
+
+struct App {
+  dock: DockState<Tab>,
+  panels: Panels,
+}
+
+impl App {
+  fn new(document: Option<Result<Document, String>>) -> Self {
+    let mut dock = DockState::new(vec![Tab::Tree]);
+    let [_, _] = dock
+      .main_surface_mut()
+      .split_right(NodeIndex::root(), 0.35, vec![Tab::Hex]);
+
+    Self {
+      dock,
+      panels: Panels {
+        document,
+        selection: Selection::default(),
+      },
+    }
+  }
+}
 
 impl eframe::App for App {
-  fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
-    dock
+  fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    let dropped = ctx.input(|input| {
+      input
+        .raw
+        .dropped_files
+        .first()
+        .and_then(|file| file.path.clone())
+    });
+    if let Some(path) = dropped {
+      self.panels.document = Some(Document::open(&path));
+      self.panels.selection = Selection::default();
+    }
+
+    DockArea::new(&mut self.dock)
+      .style(Style::from_egui(ctx.style().as_ref()))
+      .show_close_buttons(false)
+      .show(ctx, &mut self.panels);
+  }
+}
+
+/// The byte range picked in either panel, shared between them.
+#[derive(Default)]
+pub struct Selection {
+  pub range: Option<Range<usize>>,
+  /// Whether the hex view should scroll to the selection on its next frame.
+  pub reveal: bool,
+}
+
+impl Selection {
+  /// Selects `range` and scrolls the hex view to it.
+  pub fn reveal(&mut self, range: Range<usize>) {
+    self.range = Some(range);
+    self.reveal = true;
+  }
+}
+
+struct Panels {
+  /// `None` until a file is opened, either from the command line or by dropping it in.
+  document: Option<Result<Document, String>>,
+  selection: Selection,
+}
+
+impl TabViewer for Panels {
+  type Tab = Tab;
+
+  fn title(&mut self, tab: &mut Tab) -> WidgetText {
+    let title = match tab {
+      Tab::Tree => "Tree",
+      Tab::Hex => "Hex",
+    };
+    match &self.document {
+      Some(Ok(document)) => format!("{title} - {}", document.path.display()).into(),
+      _ => title.into(),
+    }
+  }
+
+  fn ui(&mut self, ui: &mut egui::Ui, tab: &mut Tab) {
+    match &self.document {
+      None => {
+        ui.label("Drop a BYML file here to open it.");
+      }
+      Some(Err(error)) => {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+      }
+      Some(Ok(document)) => match tab {
+        Tab::Tree => tree_view::show(ui, document, &mut self.selection),
+        Tab::Hex => hex_view::show(ui, document, &mut self.selection),
+      },
+    }
   }
 }
@@ -0,0 +1,42 @@
+use eframe::egui::{Id, ScrollArea, Ui, collapsing_header::CollapsingState};
+
+use crate::{
+  Selection,
+  document::{Document, TreeNode},
+};
+
+/// Shows the document's nodes. Clicking one selects its bytes and scrolls the hex view to them.
+pub fn show(ui: &mut Ui, document: &Document, selection: &mut Selection) {
+  let Some(root) = &document.root else {
+    ui.label("The document has no root node.");
+    return;
+  };
+
+  ScrollArea::both().auto_shrink(false).show(ui, |ui| {
+    show_node(ui, root, Id::new("byml tree"), true, selection)
+  });
+}
+
+/// `id` is derived from the path to the node rather than its offset, since the same container
+/// can appear in several places.
+fn show_node(ui: &mut Ui, node: &TreeNode, id: Id, default_open: bool, selection: &mut Selection) {
+  let mut label = |ui: &mut Ui| {
+    let selected = selection.range.as_ref() == Some(&node.range);
+    if ui.selectable_label(selected, &node.label).clicked() {
+      selection.reveal(node.range.clone());
+    }
+  };
+
+  if node.children.is_empty() {
+    label(ui);
+    return;
+  }
+
+  CollapsingState::load_with_default_open(ui.ctx(), id, default_open)
+    .show_header(ui, label)
+    .body(|ui| {
+      for (index, child) in node.children.iter().enumerate() {
+        show_node(ui, child, id.with(index), false, selection);
+      }
+    });
+}
@@ -2,6 +2,10 @@ use std::marker::PhantomData;
 
 use crate::{formats::{Rgb565, Srgb}, TextureReader};
 
+pub(super) const BLOCK_SIZE: usize = 8;
+
+pub(super) type Rgb = [u8; 3];
+
 pub struct Bc1<F> {
   data: Vec<u8>,
   width: u32,
@@ -30,13 +34,84 @@ impl TextureReader for Bc1<Srgb> {
     self.height
   }
 
+  /// Decodes linear (unswizzled) BC1 blocks into tightly packed RGBA8 pixels. Fails if there are
+  /// fewer blocks than the dimensions need, anything after them (like other mips) is ignored.
   fn decompress(&self) -> Result<Vec<u8>, Self::Error> {
-    // let 
-    // for block in self.data.chunks(8) {
-        
-    // }
-    
-    // Ok(())
-    todo!()
+    let (width, height) = (self.width as usize, self.height as usize);
+    let blocks_wide = width.div_ceil(4);
+    let blocks_high = height.div_ceil(4);
+    let blocks = self
+      .data
+      .get(..blocks_wide * blocks_high * BLOCK_SIZE)
+      .ok_or(())?;
+
+    let mut rgba = vec![0; width * height * 4];
+    for (block_index, block) in blocks.chunks_exact(BLOCK_SIZE).enumerate() {
+      let block_x = block_index % blocks_wide * 4;
+      let block_y = block_index / blocks_wide * 4;
+      for (index, pixel) in decode_block(block.try_into().unwrap()).iter().enumerate() {
+        let (x, y) = (block_x + index % 4, block_y + index / 4);
+        // edge blocks hang over the right and bottom of the image
+        if x < width && y < height {
+          let offset = (y * width + x) * 4;
+          rgba[offset..offset + 4].copy_from_slice(pixel);
+        }
+      }
+    }
+
+    Ok(rgba)
   }
 }
+
+fn decode_block(block: &[u8; BLOCK_SIZE]) -> [[u8; 4]; 16] {
+  let color0 = u16::from_le_bytes([block[0], block[1]]);
+  let color1 = u16::from_le_bytes([block[2], block[3]]);
+  let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+  let palette = palette(color0, color1);
+  std::array::from_fn(|index| {
+    let selector = (indices >> (index * 2)) as usize & 0b11;
+    palette[selector].map_or([0; 4], |[r, g, b]| [r, g, b, 0xFF])
+  })
+}
+
+/// The four colours a block's indices pick from. The endpoints' order picks the mode:
+/// color0 > color1 is four colours, otherwise three colours and transparent black (`None`).
+pub(super) fn palette(color0: u16, color1: u16) -> [Option<Rgb>; 4] {
+  let endpoints = [unpack_rgb565(color0), unpack_rgb565(color1)];
+  if color0 > color1 {
+    [
+      Some(endpoints[0]),
+      Some(endpoints[1]),
+      Some(mix(endpoints[0], endpoints[1], 2, 1)),
+      Some(mix(endpoints[0], endpoints[1], 1, 2)),
+    ]
+  } else {
+    [
+      Some(endpoints[0]),
+      Some(endpoints[1]),
+      Some(mix(endpoints[0], endpoints[1], 1, 1)),
+      None,
+    ]
+  }
+}
+
+// red sits in the top bits and blue in the bottom ones
+fn unpack_rgb565(color: u16) -> Rgb {
+  let r = (color >> 11) as u8 & 0x1F;
+  let g = (color >> 5) as u8 & 0x3F;
+  let b = color as u8 & 0x1F;
+  [
+    (r << 3) | (r >> 2),
+    (g << 2) | (g >> 4),
+    (b << 3) | (b >> 2),
+  ]
+}
+
+/// Blends `a` and `b` by the given weights, the way decoders build the in-between colours.
+fn mix(a: Rgb, b: Rgb, weight_a: u16, weight_b: u16) -> Rgb {
+  [0, 1, 2].map(|channel| {
+    ((u16::from(a[channel]) * weight_a + u16::from(b[channel]) * weight_b) / (weight_a + weight_b))
+      as u8
+  })
+}
@@ -0,0 +1,102 @@
+use crate::formats::bc1::reader::{BLOCK_SIZE, Bc1, Rgb, palette};
+
+impl<F> Bc1<F> {
+  /// Compresses tightly packed RGBA8 pixels into linear (unswizzled) BC1 blocks.
+  ///
+  /// Each 4x4 block uses the corners of its colour bounding box as endpoints, which is fast but
+  /// leaves visible banding on busy blocks. Blocks with any pixel under half alpha switch to
+  /// BC1's three colour mode and mark those pixels transparent. Edge blocks repeat the last
+  /// row and column to fill themselves out.
+  pub fn encode(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    assert!(
+      rgba.len() >= width * height * 4,
+      "expected {} bytes of rgba data, got {}",
+      width * height * 4,
+      rgba.len()
+    );
+
+    let blocks_wide = width.div_ceil(4);
+    let blocks_high = height.div_ceil(4);
+    let mut data = Vec::with_capacity(blocks_wide * blocks_high * BLOCK_SIZE);
+
+    for block_y in 0..blocks_high {
+      for block_x in 0..blocks_wide {
+        let mut pixels = [[0; 4]; 16];
+        for (index, pixel) in pixels.iter_mut().enumerate() {
+          let x = (block_x * 4 + index % 4).min(width - 1);
+          let y = (block_y * 4 + index / 4).min(height - 1);
+          let offset = (y * width + x) * 4;
+          pixel.copy_from_slice(&rgba[offset..offset + 4]);
+        }
+
+        data.extend_from_slice(&encode_block(&pixels));
+      }
+    }
+
+    data
+  }
+}
+
+fn encode_block(pixels: &[[u8; 4]; 16]) -> [u8; BLOCK_SIZE] {
+  let transparent = pixels.map(|pixel| pixel[3] < 0x80);
+  let has_transparency = transparent.contains(&true);
+
+  let mut min = [u8::MAX; 3];
+  let mut max = [u8::MIN; 3];
+  for (pixel, _) in pixels
+    .iter()
+    .zip(transparent)
+    .filter(|(_, transparent)| !transparent)
+  {
+    for ((min, max), value) in min.iter_mut().zip(&mut max).zip(&pixel[..3]) {
+      *min = (*min).min(*value);
+      *max = (*max).max(*value);
+    }
+  }
+  if min > max {
+    // every pixel is transparent
+    min = [0; 3];
+    max = [0; 3];
+  }
+
+  let (mut color0, mut color1) = (pack_rgb565(max), pack_rgb565(min));
+  // the endpoints' order picks the mode: color0 > color1 is four colours, otherwise three
+  // colours and transparent black
+  if (color0 > color1) == has_transparency {
+    (color0, color1) = (color1, color0);
+  }
+
+  let palette = palette(color0, color1);
+
+  let mut indices = 0u32;
+  for (index, (pixel, transparent)) in pixels.iter().zip(transparent).enumerate() {
+    let selector = if transparent {
+      3
+    } else {
+      (0..4)
+        .filter_map(|selector| palette[selector].map(|color| (selector, color)))
+        .min_by_key(|(_, color)| distance(*color, [pixel[0], pixel[1], pixel[2]]))
+        .unwrap()
+        .0 as u32
+    };
+    indices |= selector << (index * 2);
+  }
+
+  let mut block = [0; BLOCK_SIZE];
+  block[0..2].copy_from_slice(&color0.to_le_bytes());
+  block[2..4].copy_from_slice(&color1.to_le_bytes());
+  block[4..8].copy_from_slice(&indices.to_le_bytes());
+  block
+}
+
+// red sits in the top bits and blue in the bottom ones
+fn pack_rgb565([r, g, b]: Rgb) -> u16 {
+  ((u16::from(r) >> 3) << 11) | ((u16::from(g) >> 2) << 5) | (u16::from(b) >> 3)
+}
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+  (0..3)
+    .map(|channel| (i32::from(a[channel]) - i32::from(b[channel])).pow(2) as u32)
+    .sum()
+}
@@ -1,3 +1,5 @@
+use std::fmt::Debug;
+
 pub mod formats;
 
 pub trait TextureReader {
@@ -9,3 +11,28 @@ pub trait TextureReader {
   
   fn decompress(&self) -> Result<Vec<u8>, Self::Error>;
 }
+
+/// An object-safe [`TextureReader`], for when the format is only known at runtime.
+pub trait DynTextureReader {
+  fn width(&self) -> u32;
+  fn height(&self) -> u32;
+
+  fn decompress(&self) -> Result<Vec<u8>, Box<dyn Debug>>;
+}
+
+impl<T> DynTextureReader for T
+where
+  T: TextureReader,
+  T::Error: Debug + 'static,
+{
+  fn width(&self) -> u32 {
+    TextureReader::width(self)
+  }
+  fn height(&self) -> u32 {
+    TextureReader::height(self)
+  }
+
+  fn decompress(&self) -> Result<Vec<u8>, Box<dyn Debug>> {
+    TextureReader::decompress(self).map_err(|error| Box::new(error) as Box<dyn Debug>)
+  }
+}
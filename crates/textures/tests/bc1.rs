@@ -0,0 +1,69 @@
+use senobi_textures::{
+  TextureReader,
+  formats::{Srgb, bc1::reader::Bc1},
+};
+
+fn solid(width: u32, height: u32, pixel: [u8; 4]) -> Vec<u8> {
+  pixel.repeat((width * height) as usize)
+}
+
+fn block(data: &[u8], index: usize) -> (u16, u16, u32) {
+  let block = &data[index * 8..index * 8 + 8];
+  (
+    u16::from_le_bytes([block[0], block[1]]),
+    u16::from_le_bytes([block[2], block[3]]),
+    u32::from_le_bytes([block[4], block[5], block[6], block[7]]),
+  )
+}
+
+#[test]
+fn encodes_one_block_per_4x4_tile() {
+  let data = Bc1::<Srgb>::encode(&solid(9, 5, [0xFF; 4]), 9, 5);
+  assert_eq!(data.len(), 3 * 2 * 8);
+  assert!(data.chunks(8).all(|chunk| chunk == data[..8].as_ref()));
+}
+
+#[test]
+fn encodes_solid_blocks_with_a_single_endpoint() {
+  let data = Bc1::<Srgb>::encode(&solid(4, 4, [0xFF, 0x00, 0x00, 0xFF]), 4, 4);
+  assert_eq!(block(&data, 0), (0xF800, 0xF800, 0));
+}
+
+#[test]
+fn picks_the_nearest_palette_entry() {
+  let mut rgba = solid(4, 4, [0x00, 0x00, 0x00, 0xFF]);
+  // the right half of the block is white
+  for row in rgba.chunks_mut(16) {
+    row[8..].fill(0xFF);
+  }
+
+  let (color0, color1, indices) = block(&Bc1::<Srgb>::encode(&rgba, 4, 4), 0);
+  assert_eq!((color0, color1), (0xFFFF, 0x0000));
+  assert_eq!(indices, 0b00_00_01_01u32 * 0x01010101);
+}
+
+#[test]
+fn marks_transparent_pixels() {
+  let mut rgba = solid(4, 4, [0x00, 0xFF, 0x00, 0xFF]);
+  rgba[3] = 0;
+
+  let (color0, color1, indices) = block(&Bc1::<Srgb>::encode(&rgba, 4, 4), 0);
+  assert!(color0 <= color1);
+  assert_eq!(indices & 0b11, 0b11);
+  assert_eq!(indices >> 2, 0);
+}
+
+#[test]
+fn decodes_what_it_encodes() {
+  let mut rgba = solid(6, 5, [0xFF, 0x00, 0x00, 0xFF]);
+  rgba[4..8].fill(0);
+
+  let data = Bc1::<Srgb>::encode(&rgba, 6, 5);
+  assert_eq!(Bc1::<Srgb>::new(6, 5, data).decompress(), Ok(rgba));
+}
+
+#[test]
+fn rejects_missing_blocks() {
+  let data = Bc1::<Srgb>::encode(&solid(8, 8, [0xFF; 4]), 8, 8);
+  assert_eq!(Bc1::<Srgb>::new(8, 12, data).decompress(), Err(()));
+}